@@ -16,19 +16,47 @@
 extern crate chrono;
 
 use std::fmt;
+use std::fmt::Write as _; // import without risk of name clashing
 
-use chrono::{Datelike, Duration, NaiveDateTime};
+use chrono::{Duration, NaiveDate, NaiveDateTime};
 
-use crate::store::Entry;
+use crate::i18n::tr;
+use crate::intern::Symbol;
+use crate::json;
+use crate::locale::{Locale, EN};
+use crate::markers::Kind;
+use crate::store::{Entry, Options};
 
 /**
  * Activity: Duration of all Entry's with the same task
  */
+#[derive(Clone)]
 pub struct Activity {
-    name: String,
+    name: Symbol,
     duration: Duration,
 }
 
+impl Activity {
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /**
+     * JSON object like {"name": "code", "minutes": 90}
+     */
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"name\": {}, \"minutes\": {}}}",
+            json::string(self.name()),
+            self.duration.num_minutes()
+        )
+    }
+}
+
 impl fmt::Display for Activity {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -41,66 +69,520 @@ impl fmt::Display for Activity {
     }
 }
 
+/**
+ * Format a duration as "H h M min", which may be negative
+ */
+pub fn format_duration(d: Duration) -> String {
+    let sign = if d < Duration::zero() { "-" } else { "" };
+    let d = d.abs();
+    format!("{}{} h {} min", sign, d.num_hours(), d.num_minutes() % 60)
+}
+
+/**
+ * How durations of activities get rounded, like for invoicing
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RoundingMode {
+    #[default]
+    None,
+    // to the nearest multiple of these minutes
+    Nearest(u32),
+    // up to the next multiple of these minutes
+    Up(u32),
+}
+
+impl RoundingMode {
+    /**
+     * Parse "none", "nearest:15" (or just "15"), or "up:5"
+     */
+    pub fn parse(s: &str) -> Result<RoundingMode, String> {
+        let s = s.trim();
+        if s == "none" {
+            return Ok(RoundingMode::None);
+        }
+        let (mode, minutes) = s.split_once(':').unwrap_or(("nearest", s));
+        let minutes = minutes
+            .trim()
+            .parse::<u32>()
+            .ok()
+            .filter(|m| (1..=24 * 60).contains(m));
+        match (mode.trim(), minutes) {
+            ("nearest", Some(m)) => Ok(RoundingMode::Nearest(m)),
+            ("up", Some(m)) => Ok(RoundingMode::Up(m)),
+            _ => Err(format!(
+                "invalid rounding {}, expected none, nearest:MINUTES, or up:MINUTES",
+                s
+            )),
+        }
+    }
+
+    pub fn apply(self, d: Duration) -> Duration {
+        let (unit, offset) = match self {
+            RoundingMode::None => return d,
+            RoundingMode::Nearest(m) => (i64::from(m) * 60, i64::from(m) * 30),
+            RoundingMode::Up(m) => (i64::from(m) * 60, i64::from(m) * 60 - 1),
+        };
+        Duration::seconds((d.num_seconds() + offset).div_euclid(unit) * unit)
+    }
+}
+
+impl fmt::Display for RoundingMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RoundingMode::None => write!(f, "none"),
+            RoundingMode::Nearest(m) => write!(f, "nearest:{}", m),
+            RoundingMode::Up(m) => write!(f, "up:{}", m),
+        }
+    }
+}
+
+/**
+ * Category of a task in gtimelog's "category: task" convention
+ */
+pub fn category(task: &str) -> Option<&str> {
+    task.split_once(':')
+        .map(|(c, _)| c.trim())
+        .filter(|c| !c.is_empty())
+}
+
+/**
+ * Parse a percentage allocation like "70% acme: code, 30% internal: review"
+ *
+ * Returns the tasks with their percentages, if all parts have one and they add up to 100%.
+ */
+fn parse_allocation(task: &str) -> Option<Vec<(&str, i32)>> {
+    let mut parts = Vec::new();
+    for part in task.split(',') {
+        let (percent, name) = part.trim().split_once('%')?;
+        let percent: i32 = percent.trim().parse().ok()?;
+        let name = name.trim();
+        if name.is_empty() || percent <= 0 {
+            return None;
+        }
+        parts.push((name, percent));
+    }
+    if parts.iter().map(|(_, p)| p).sum::<i32>() == 100 {
+        Some(parts)
+    } else {
+        None
+    }
+}
+
 /**
  * Activities: Collection of Activity with total durations
  */
+#[derive(Clone)]
 pub struct Activities {
     activities: Vec<Activity>,
     total_work: Duration,
     total_slack: Duration,
+    // for adding entries incrementally
+    last_stop: Option<NaiveDateTime>,
+    pending: Duration,
+    // how tasks count, and when days begin
+    options: Options,
 }
 
 impl Activities {
-    pub fn new_from_entries(entries: &[Entry]) -> Activities {
+    fn new(options: &Options) -> Activities {
+        Activities {
+            activities: Vec::new(),
+            total_work: Duration::minutes(0),
+            total_slack: Duration::minutes(0),
+            last_stop: None,
+            pending: Duration::minutes(0),
+            options: options.clone(),
+        }
+    }
+
+    /**
+     * The options by which the activities were classified and split into days
+     */
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    fn add(&mut self, task: Symbol, duration: Duration) {
+        self.add_keyed(task, duration, &|task| task);
+    }
+
+    // `key` gives the name under which each (part of an allocated) task gets accounted
+    fn add_keyed(&mut self, task: Symbol, duration: Duration, key: &dyn Fn(Symbol) -> Symbol) {
+        match parse_allocation(task.as_str()) {
+            Some(parts) => {
+                for (part, percent) in parts {
+                    let part = part.into();
+                    self.add_one(part, key(part), duration * percent / 100);
+                }
+            }
+            None => self.add_one(task, key(task), duration),
+        }
+    }
+
+    fn add_one(&mut self, task: Symbol, name: Symbol, duration: Duration) {
+        match self.options.markers.kind(&task) {
+            Kind::Work => self.total_work += duration,
+            Kind::Slack => self.total_slack += duration,
+            Kind::Ignored => return,
+        }
+        self.accumulate(name, duration);
+    }
+
+    fn accumulate(&mut self, name: Symbol, duration: Duration) {
+        // meh quadratic loop, but not important
+        match self
+            .activities
+            .iter_mut()
+            .find(|a: &&mut Activity| a.name == name)
+        {
+            Some(a) => a.duration += duration,
+            None => self.activities.push(Activity { name, duration }),
+        }
+    }
+
+    pub fn new_from_entries(entries: &[Entry], options: &Options) -> Activities {
         // don't use a hashmap here, we do want to keep this sorted by "first occurrence of task"
-        let mut activities = Vec::new();
-        let mut total_work = Duration::minutes(0);
-        let mut total_slack = Duration::minutes(0);
-        let mut prev_stop: Option<NaiveDateTime> = None;
+        let mut activities = Activities::new(options);
+        for entry in entries {
+            activities.push(entry);
+        }
+        activities
+    }
 
+    /**
+     * Like new_from_entries(), but aggregate by an arbitrary key of the entries instead of by task
+     *
+     * For example, `|e| category(e.task.as_str()).unwrap_or("other")` or
+     * `|e| e.stop.weekday().to_string()`. Entries still count as work or slack by their task. Tasks
+     * with a percentage allocation are split first, so each part gets its own key.
+     */
+    pub fn group_by<K: Into<Symbol>>(
+        entries: &[Entry],
+        key: impl Fn(&Entry) -> K,
+        options: &Options,
+    ) -> Activities {
+        let mut activities = Activities::new(options);
         for entry in entries {
-            match prev_stop {
-                Some(prev_stop_time) => {
-                    // continue if not the same day
-                    // first entry of every day gets ignored
-                    if prev_stop_time.day() != entry.stop.day() {
-                        prev_stop = Some(entry.stop);
-                        continue;
-                    }
-
-                    let duration = entry.stop.signed_duration_since(prev_stop_time);
-                    if entry.task.starts_with("**") {
-                        total_slack = total_slack + duration;
-                    } else {
-                        total_work = total_work + duration;
-                    }
-
-                    // meh quadratic loop, but not important
-                    match activities
-                        .iter_mut()
-                        .find(|a: &&mut Activity| a.name == entry.task)
-                    {
-                        Some(a) => a.duration = a.duration + duration,
-                        None => activities.push(Activity {
-                            name: entry.task.to_string(),
-                            duration,
-                        }),
-                    }
-
-                    prev_stop = Some(entry.stop);
-                }
-                None => {
-                    // first entry's task is ignored, it just provides the start time
-                    prev_stop = Some(entry.stop);
+            activities.push_by(entry, &key);
+        }
+        activities
+    }
+
+    /**
+     * Activities of each day with entries, plus their grand total, like gtimelog's weekly report
+     */
+    pub fn new_per_day(entries: &[Entry], options: &Options) -> DailyActivities {
+        Activities::new_per_day_where(entries, |_| true, options)
+    }
+
+    /**
+     * Like new_per_day(), but only count the entries for which `keep` is true; days without any
+     * of them are left out
+     */
+    pub fn new_per_day_where(
+        entries: &[Entry],
+        keep: impl Fn(&Entry) -> bool,
+        options: &Options,
+    ) -> DailyActivities {
+        let mut days = Vec::new();
+        let mut rest = entries;
+        while let Some(first) = rest.first() {
+            let date = options.day(first);
+            let (day, tail) = rest.split_at(rest.partition_point(|e| options.day(e) == date));
+            if day.iter().any(&keep) {
+                days.push((
+                    date,
+                    Activities::new_from_entries_where(day, &keep, options),
+                ));
+            }
+            rest = tail;
+        }
+        let total = Activities::merge(days.iter().map(|(_, a)| a), options);
+        DailyActivities { days, total }
+    }
+
+    /**
+     * Like new_from_entries(), but only count the entries for which `keep` is true
+     */
+    pub fn new_from_entries_where(
+        entries: &[Entry],
+        keep: impl Fn(&Entry) -> bool,
+        options: &Options,
+    ) -> Activities {
+        let mut activities = Activities::new(options);
+        for entry in entries {
+            if keep(entry) {
+                activities.push(entry);
+            } else {
+                activities.last_stop = Some(entry.stop);
+            }
+        }
+        activities
+    }
+
+    /**
+     * Like new_from_entries(), but aggregate chunks of whole days on all CPUs and merge them
+     */
+    #[cfg(feature = "parallel")]
+    pub fn new_from_entries_parallel(entries: &[Entry], options: &Options) -> Activities {
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk_size = entries.len() / threads + 1;
+
+        // intervals across days don't count, so splitting between days does not change the result
+        let mut chunks = Vec::new();
+        let mut rest = entries;
+        while !rest.is_empty() {
+            let mut end = chunk_size.min(rest.len());
+            while end < rest.len() && options.day(&rest[end]) == options.day(&rest[end - 1]) {
+                end += 1;
+            }
+            let (chunk, tail) = rest.split_at(end);
+            chunks.push(chunk);
+            rest = tail;
+        }
+
+        let parts: Vec<Activities> = std::thread::scope(|s| {
+            let handles: Vec<_> = chunks
+                .iter()
+                .map(|chunk| s.spawn(|| Activities::new_from_entries(chunk, options)))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        Activities::merge(&parts, options)
+    }
+
+    /**
+     * Combine activities, e.g. of several days, in order of first occurrence
+     */
+    pub fn merge<'a>(
+        parts: impl IntoIterator<Item = &'a Activities>,
+        options: &Options,
+    ) -> Activities {
+        let mut activities = Activities::new(options);
+        for part in parts {
+            activities.total_work += part.total_work;
+            activities.total_slack += part.total_slack;
+            for a in &part.activities {
+                activities.accumulate(a.name, a.duration);
+            }
+        }
+        activities
+    }
+
+    /**
+     * Account a new entry, which must not be older than the previous one
+     *
+     * The first entry of every day is ignored, it just provides the start time.
+     */
+    pub fn push(&mut self, entry: &Entry) {
+        self.push_by(entry, |e| e.task);
+    }
+
+    /**
+     * Like push(), but account the entry under its `key` instead of its task
+     */
+    pub fn push_by<K: Into<Symbol>>(&mut self, entry: &Entry, key: impl Fn(&Entry) -> K) {
+        if let Some(prev_stop) = self.last_stop {
+            if self.options.logical_date(prev_stop) == self.options.day(entry) {
+                self.add_keyed(
+                    entry.task,
+                    entry.stop.signed_duration_since(prev_stop),
+                    &|task| {
+                        key(&Entry {
+                            stop: entry.stop,
+                            task,
+                        })
+                        .into()
+                    },
+                );
+            }
+        }
+        self.last_stop = Some(entry.stop);
+        self.pending = Duration::zero();
+    }
+
+    /**
+     * Advance the current time, for the provisional interval since the last entry
+     */
+    pub fn set_now(&mut self, now: NaiveDateTime) {
+        self.pending = match self.last_stop {
+            Some(stop)
+                if self.options.logical_date(stop) == self.options.logical_date(now)
+                    && now > stop =>
+            {
+                now - stop
+            }
+            _ => Duration::zero(),
+        };
+    }
+
+    /**
+     * Time since the last entry up to set_now(), which is not accounted to any task yet
+     */
+    pub fn pending(&self) -> Duration {
+        self.pending
+    }
+
+    /**
+     * Group the activities by their category (the part before the first ":"), in order of first
+     * occurrence; tasks without one end up in NO_CATEGORY
+     */
+    pub fn grouped_by_category(&self) -> CategoryGroups {
+        let mut groups: Vec<CategoryGroup> = Vec::new();
+        for a in &self.activities {
+            let name = category(a.name()).unwrap_or(NO_CATEGORY);
+            match groups.iter_mut().find(|g| g.name == name) {
+                Some(g) => {
+                    g.duration += a.duration;
+                    g.activities.push(a.clone());
                 }
+                None => groups.push(CategoryGroup {
+                    name: name.to_string(),
+                    duration: a.duration,
+                    activities: vec![a.clone()],
+                }),
             }
         }
+        CategoryGroups {
+            groups,
+            total_work: self.total_work,
+            total_slack: self.total_slack,
+        }
+    }
 
-        Activities {
-            activities,
-            total_work,
-            total_slack,
+    /**
+     * JSON object with the activities and the totals in minutes
+     */
+    pub fn to_json(&self) -> String {
+        let activities: Vec<String> = self.activities.iter().map(Activity::to_json).collect();
+        format!(
+            "{{\"activities\": [{}], \"work_minutes\": {}, \"slack_minutes\": {}}}",
+            activities.join(", "),
+            self.total_work.num_minutes(),
+            self.total_slack.num_minutes()
+        )
+    }
+
+    /**
+     * Activities in order of first occurrence
+     */
+    pub fn iter(&self) -> impl Iterator<Item = &Activity> {
+        self.activities.iter()
+    }
+
+    pub fn total_work(&self) -> Duration {
+        self.total_work
+    }
+
+    pub fn total_slack(&self) -> Duration {
+        self.total_slack
+    }
+
+    /**
+     * Total duration of all activities in the given category
+     */
+    pub fn category_total(&self, cat: &str) -> Duration {
+        self.activities
+            .iter()
+            .filter(|a| category(&a.name) == Some(cat))
+            .fold(Duration::minutes(0), |sum, a| sum + a.duration)
+    }
+
+    /**
+     * Total duration of all activities with the billable marker
+     */
+    pub fn billable_total(&self) -> Duration {
+        self.activities
+            .iter()
+            .filter(|a| self.options.markers.is_billable(&a.name))
+            .fold(Duration::minutes(0), |sum, a| sum + a.duration)
+    }
+
+    /**
+     * Total work with on-call multipliers applied
+     *
+     * `multipliers` map categories to a factor, like ("standby", 0.25) or ("callout", 1.5); other
+     * work counts once.
+     */
+    pub fn weighted_work(&self, multipliers: &[(String, f64)]) -> Duration {
+        self.activities
+            .iter()
+            .filter(|a| self.options.markers.kind(&a.name) == Kind::Work)
+            .fold(Duration::minutes(0), |sum, a| {
+                let factor = category(&a.name)
+                    .and_then(|c| multipliers.iter().find(|(m, _)| m == c))
+                    .map_or(1.0, |(_, f)| *f);
+                sum + Duration::seconds((a.duration.num_seconds() as f64 * factor).round() as i64)
+            })
+    }
+
+    /**
+     * Split activities of concurrent tasks like "code / mentoring" evenly between the tasks
+     *
+     * Each part is accounted as slack or work on its own.
+     */
+    pub fn split_concurrent(self, separator: &str) -> Activities {
+        let mut activities = Activities::new(&self.options);
+        for a in self.activities {
+            let parts: Vec<&str> = a
+                .name
+                .split(separator)
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .collect();
+            if parts.len() < 2 {
+                activities.add(a.name, a.duration);
+                continue;
+            }
+            let share = a.duration / parts.len() as i32;
+            for part in parts {
+                activities.add(part.into(), share);
+            }
+        }
+        activities
+    }
+
+    /**
+     * Round the duration of each activity; the totals are the sums of the rounded durations
+     */
+    pub fn round(self, mode: RoundingMode) -> Activities {
+        if mode == RoundingMode::None {
+            return self;
+        }
+        let mut activities = Activities::new(&self.options);
+        for a in self.activities {
+            activities.add_one(a.name, a.name, mode.apply(a.duration));
+        }
+        activities
+    }
+
+    /**
+     * Activities of work shifts (see store::split_shifts), which may cross midnight
+     *
+     * The first entry of every shift just provides its start time.
+     */
+    pub fn new_from_shifts(shifts: &[&[Entry]], options: &Options) -> Activities {
+        Activities::new_from_shifts_where(shifts, |_| true, options)
+    }
+
+    /**
+     * Like new_from_shifts(), but only count the entries for which `keep` is true
+     */
+    pub fn new_from_shifts_where(
+        shifts: &[&[Entry]],
+        keep: impl Fn(&Entry) -> bool,
+        options: &Options,
+    ) -> Activities {
+        let mut activities = Activities::new(options);
+        for shift in shifts {
+            for pair in shift.windows(2).filter(|pair| keep(&pair[1])) {
+                activities.add(
+                    pair[1].task,
+                    pair[1].stop.signed_duration_since(pair[0].stop),
+                );
+            }
         }
+        activities
     }
 }
 
@@ -112,22 +594,182 @@ impl fmt::Display for Activities {
         writeln!(f, "-------")?;
         writeln!(
             f,
-            "Total work done: {} h {} min",
+            "{}: {} h {} min",
+            tr("Total work done"),
+            self.total_work.num_hours(),
+            self.total_work.num_minutes() % 60
+        )?;
+        writeln!(
+            f,
+            "{}: {} h {} min",
+            tr("Total slacking"),
+            self.total_slack.num_hours(),
+            self.total_slack.num_minutes() % 60
+        )
+    }
+}
+
+/// group of tasks without a "category: " prefix
+pub const NO_CATEGORY: &str = "(no category)";
+
+/**
+ * Category with its total and its activities, see Activities::grouped_by_category()
+ */
+#[derive(Clone)]
+pub struct CategoryGroup {
+    pub name: String,
+    pub duration: Duration,
+    pub activities: Vec<Activity>,
+}
+
+/**
+ * Activities grouped by category, with the totals of the ungrouped activities
+ */
+#[derive(Clone)]
+pub struct CategoryGroups {
+    pub groups: Vec<CategoryGroup>,
+    pub total_work: Duration,
+    pub total_slack: Duration,
+}
+
+impl fmt::Display for CategoryGroups {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for g in &self.groups {
+            writeln!(
+                f,
+                "{:>2} h {:>2} min: {}",
+                g.duration.num_hours(),
+                g.duration.num_minutes() % 60,
+                g.name
+            )?;
+            for a in &g.activities {
+                // just the task, the category is in the heading
+                let task = match category(a.name()) {
+                    Some(_) => a.name().split_once(':').unwrap().1.trim(),
+                    None => a.name(),
+                };
+                writeln!(
+                    f,
+                    "    {:>2} h {:>2} min: {}",
+                    a.duration.num_hours(),
+                    a.duration.num_minutes() % 60,
+                    task
+                )?;
+            }
+        }
+        writeln!(f, "-------")?;
+        writeln!(
+            f,
+            "{}: {} h {} min",
+            tr("Total work done"),
             self.total_work.num_hours(),
             self.total_work.num_minutes() % 60
         )?;
         writeln!(
             f,
-            "Total slacking: {} h {} min",
+            "{}: {} h {} min",
+            tr("Total slacking"),
             self.total_slack.num_hours(),
             self.total_slack.num_minutes() % 60
         )
     }
 }
 
+/**
+ * Per-day subtotals of a longer period and the grand total, see Activities::new_per_day()
+ */
+#[derive(Clone)]
+pub struct DailyActivities {
+    pub days: Vec<(NaiveDate, Activities)>,
+    pub total: Activities,
+}
+
+impl DailyActivities {
+    /**
+     * Apply a transformation like Activities::split_concurrent() to every day and the total
+     */
+    pub fn map(self, f: impl Fn(Activities) -> Activities) -> DailyActivities {
+        DailyActivities {
+            days: self.days.into_iter().map(|(d, a)| (d, f(a))).collect(),
+            total: f(self.total),
+        }
+    }
+
+    /**
+     * The days, each with a "Weekday, <date_fmt>:" header in `locale`, without the grand total
+     */
+    pub fn format_days(&self, locale: &Locale, date_fmt: &str) -> String {
+        let fmt = format!("%A, {}", date_fmt);
+        let mut out = String::new();
+        for (day, activities) in &self.days {
+            write!(out, "{}:\n{}\n", locale.format_date(day, &fmt), activities).unwrap();
+        }
+        out
+    }
+
+    /**
+     * One line with the total work and slack of each day, with dates like format_days()
+     */
+    pub fn format_totals(&self, locale: &Locale, date_fmt: &str) -> String {
+        let fmt = format!("%A, {}", date_fmt);
+        let mut out = String::new();
+        for (day, activities) in &self.days {
+            writeln!(
+                out,
+                "{}: {}",
+                format_duration(activities.total_work + activities.total_slack),
+                locale.format_date(day, &fmt)
+            )
+            .unwrap();
+        }
+        out
+    }
+}
+
+impl fmt::Display for DailyActivities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.format_days(&EN, "%Y-%m-%d"))?;
+        write!(f, "{}:\n{}", tr("Grand total"), self.total)
+    }
+}
+
+/**
+ * Chronological listing of entries with their start/stop times, formatted with `time_fmt`
+ */
+pub fn format_chronological(entries: &[Entry], time_fmt: &str, options: &Options) -> String {
+    let mut output = String::new();
+    let mut prev_stop: Option<NaiveDateTime> = None;
+
+    for entry in entries {
+        let stop = entry.stop.format(time_fmt);
+        match prev_stop {
+            // the first entry of a day just provides the start time
+            Some(prev_stop_time) if options.logical_date(prev_stop_time) == options.day(entry) => {
+                let duration = entry.stop.signed_duration_since(prev_stop_time);
+                writeln!(
+                    output,
+                    "{} - {} ({:>2} h {:>2} min): {}",
+                    prev_stop_time.format(time_fmt),
+                    stop,
+                    duration.num_hours(),
+                    duration.num_minutes() % 60,
+                    entry.task
+                )
+            }
+            _ => writeln!(output, "{}: {}", stop, entry.task),
+        }
+        .expect("failed to format entry");
+        prev_stop = Some(entry.stop);
+    }
+
+    output
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::locale::DE;
+    use crate::markers::Markers;
     use crate::store::Timelog;
     use chrono::NaiveDate;
 
@@ -137,7 +779,7 @@ mod tests {
             &format!(
                 "{}",
                 Activity {
-                    name: "code this".to_string(),
+                    name: "code this".into(),
                     duration: Duration::minutes(3)
                 }
             ),
@@ -147,7 +789,7 @@ mod tests {
             &format!(
                 "{}",
                 Activity {
-                    name: "code this".to_string(),
+                    name: "code this".into(),
                     duration: Duration::minutes(59)
                 }
             ),
@@ -157,7 +799,7 @@ mod tests {
             &format!(
                 "{}",
                 Activity {
-                    name: "code this".to_string(),
+                    name: "code this".into(),
                     duration: Duration::minutes(60)
                 }
             ),
@@ -167,7 +809,7 @@ mod tests {
             &format!(
                 "{}",
                 Activity {
-                    name: "code this".to_string(),
+                    name: "code this".into(),
                     duration: Duration::minutes(23 * 60 + 1)
                 }
             ),
@@ -177,7 +819,7 @@ mod tests {
 
     #[test]
     fn test_activities_empty() {
-        let a = Activities::new_from_entries(&[]);
+        let a = Activities::new_from_entries(&[], &Options::default());
         assert_eq!(a.activities.len(), 0);
         assert_eq!(a.total_work, Duration::minutes(0));
         assert_eq!(a.total_slack, Duration::minutes(0));
@@ -202,6 +844,7 @@ mod tests {
 
         let a = Activities::new_from_entries(
             tl.get_day(&NaiveDate::from_ymd_opt(2022, 6, 10).unwrap()),
+            &Options::default(),
         );
         assert_eq!(a.total_work, Duration::minutes(475));
         assert_eq!(a.total_slack, Duration::minutes(65));
@@ -255,6 +898,7 @@ Total slacking: 1 h 5 min\n"
 
         let a = Activities::new_from_entries(
             tl.get_week(&NaiveDate::from_ymd_opt(2022, 6, 7).unwrap()),
+            &Options::default(),
         );
         assert_eq!(a.total_work, Duration::hours(3));
         assert_eq!(a.total_slack, Duration::minutes(20));
@@ -274,4 +918,587 @@ Total slacking: 0 h 20 min
 "
         );
     }
+
+    #[test]
+    fn test_format_chronological() {
+        let tl = Timelog::new_from_string(
+            "
+2022-06-09 16:00: arrived
+2022-06-09 17:05: code
+
+2022-06-10 07:00: arrived
+2022-06-10 08:45: gtimelog: code
+2022-06-10 09:00: ** tea
+",
+        );
+        let entries: Vec<Entry> = tl.get_all().cloned().collect();
+
+        assert_eq!(format_chronological(&[], "%H:%M", &Options::default()), "");
+        assert_eq!(
+            format_chronological(&entries, "%H:%M", &Options::default()),
+            "16:00: arrived
+16:00 - 17:05 ( 1 h  5 min): code
+07:00: arrived
+07:00 - 08:45 ( 1 h 45 min): gtimelog: code
+08:45 - 09:00 ( 0 h 15 min): ** tea
+"
+        );
+        assert_eq!(
+            format_chronological(&entries[..2], "%I.%M %p", &Options::default()),
+            "04.00 PM: arrived
+04.00 PM - 05.05 PM ( 1 h  5 min): code
+"
+        );
+    }
+
+    #[test]
+    fn test_activities_shifts() {
+        let tl = Timelog::new_from_string(
+            "
+2022-06-09 22:00: arrived
+2022-06-09 23:30: patrol
+2022-06-10 01:00: ** break
+2022-06-10 06:00: patrol
+
+2022-06-10 22:00: arrived
+2022-06-10 23:00: paperwork
+",
+        );
+        let entries: Vec<Entry> = tl.get_all().cloned().collect();
+        let shifts = crate::store::split_shifts(&entries, Duration::hours(6));
+        assert_eq!(shifts.len(), 2);
+
+        let a = Activities::new_from_shifts(&shifts[..1], &Options::default());
+        assert_eq!(a.total_work, Duration::hours(6) + Duration::minutes(30));
+        assert_eq!(a.total_slack, Duration::minutes(90));
+        assert_eq!(a.activities.len(), 2);
+        assert_eq!(a.activities[0].name, "patrol");
+
+        let a = Activities::new_from_shifts(&shifts, &Options::default());
+        assert_eq!(a.total_work, Duration::hours(7) + Duration::minutes(30));
+
+        assert_eq!(
+            Activities::new_from_shifts(&[], &Options::default())
+                .activities
+                .len(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_rounding_mode() {
+        assert_eq!(RoundingMode::parse("none"), Ok(RoundingMode::None));
+        assert_eq!(RoundingMode::parse("15"), Ok(RoundingMode::Nearest(15)));
+        assert_eq!(
+            RoundingMode::parse("nearest:15"),
+            Ok(RoundingMode::Nearest(15))
+        );
+        assert_eq!(RoundingMode::parse(" up:5 "), Ok(RoundingMode::Up(5)));
+        assert!(RoundingMode::parse("up:0").is_err());
+        assert!(RoundingMode::parse("down:5").is_err());
+        assert!(RoundingMode::parse("").is_err());
+        assert_eq!(RoundingMode::Up(5).to_string(), "up:5");
+        assert_eq!(
+            RoundingMode::parse(&RoundingMode::Nearest(6).to_string()),
+            Ok(RoundingMode::Nearest(6))
+        );
+
+        let m = Duration::minutes;
+        let nearest = RoundingMode::Nearest(15);
+        assert_eq!(nearest.apply(m(7)), m(0));
+        assert_eq!(nearest.apply(m(8)), m(15));
+        assert_eq!(nearest.apply(m(52) + Duration::seconds(30)), m(60));
+        assert_eq!(nearest.apply(m(0)), m(0));
+        let up = RoundingMode::Up(5);
+        assert_eq!(up.apply(m(1)), m(5));
+        assert_eq!(up.apply(m(10)), m(10));
+        assert_eq!(up.apply(m(10) + Duration::seconds(1)), m(15));
+        assert_eq!(RoundingMode::None.apply(m(7)), m(7));
+    }
+
+    #[test]
+    fn test_round() {
+        let tl = Timelog::new_from_string(
+            "
+2022-06-10 08:00: arrived
+2022-06-10 08:07: email
+2022-06-10 09:50: code
+2022-06-10 10:02: ** tea
+2022-06-10 10:10: email
+",
+        );
+        let entries: Vec<Entry> = tl.get_all().cloned().collect();
+        let activities = Activities::new_from_entries(&entries, &Options::default());
+
+        let rounded = activities.clone().round(RoundingMode::Nearest(15));
+        let durations: Vec<(String, Duration)> = rounded
+            .iter()
+            .map(|a| (a.name().to_string(), a.duration()))
+            .collect();
+        assert_eq!(
+            durations,
+            vec![
+                ("email".to_string(), Duration::minutes(15)),
+                ("code".to_string(), Duration::minutes(105)),
+                ("** tea".to_string(), Duration::minutes(15)),
+            ]
+        );
+        assert_eq!(rounded.total_work(), Duration::minutes(120));
+        assert_eq!(rounded.total_slack(), Duration::minutes(15));
+
+        let rounded = activities.round(RoundingMode::Up(30));
+        assert_eq!(rounded.total_work(), Duration::minutes(150));
+        assert_eq!(rounded.total_slack(), Duration::minutes(30));
+    }
+
+    #[test]
+    fn test_split_concurrent() {
+        let tl = Timelog::new_from_string(
+            "
+2022-06-10 07:00: arrived
+2022-06-10 08:00: code / mentoring
+2022-06-10 09:00: code
+2022-06-10 09:30: ** tea / review
+2022-06-10 10:00: a / b / c
+",
+        );
+
+        let a = Activities::new_from_entries(
+            tl.get_day(&NaiveDate::from_ymd_opt(2022, 6, 10).unwrap()),
+            &Options::default(),
+        )
+        .split_concurrent("/");
+        assert_eq!(
+            format!("{}", a),
+            " 1 h 30 min: code
+ 0 h 30 min: mentoring
+ 0 h 15 min: ** tea
+ 0 h 15 min: review
+ 0 h 10 min: a
+ 0 h 10 min: b
+ 0 h 10 min: c
+-------
+Total work done: 2 h 45 min
+Total slacking: 0 h 15 min
+"
+        );
+
+        // other separator does not split
+        let a = Activities::new_from_entries(
+            tl.get_day(&NaiveDate::from_ymd_opt(2022, 6, 10).unwrap()),
+            &Options::default(),
+        )
+        .split_concurrent("+");
+        assert_eq!(a.activities.len(), 4);
+        assert_eq!(a.activities[0].name, "code / mentoring");
+    }
+
+    #[test]
+    fn test_parse_allocation() {
+        assert_eq!(
+            parse_allocation("70% acme: code, 30% internal: review"),
+            Some(vec![("acme: code", 70), ("internal: review", 30)])
+        );
+        assert_eq!(parse_allocation("100%code"), Some(vec![("code", 100)]));
+        // does not add up
+        assert_eq!(parse_allocation("70% a, 20% b"), None);
+        assert_eq!(parse_allocation("70% a, 40% b"), None);
+        // not all parts have a percentage
+        assert_eq!(parse_allocation("70% a, b"), None);
+        assert_eq!(parse_allocation("code, review"), None);
+        assert_eq!(parse_allocation("code"), None);
+        assert_eq!(parse_allocation("100% "), None);
+        assert_eq!(parse_allocation("-50% a, 150% b"), None);
+    }
+
+    #[test]
+    fn test_activities_allocation() {
+        let tl = Timelog::new_from_string(
+            "
+2022-06-10 07:00: arrived
+2022-06-10 09:00: 70% acme: code, 30% internal: review
+2022-06-10 10:00: acme: code
+2022-06-10 10:30: 50% ** lunch, 50% internal: review
+",
+        );
+
+        let a = Activities::new_from_entries(
+            tl.get_day(&NaiveDate::from_ymd_opt(2022, 6, 10).unwrap()),
+            &Options::default(),
+        );
+        assert_eq!(
+            format!("{}", a),
+            " 2 h 24 min: acme: code
+ 0 h 51 min: internal: review
+ 0 h 15 min: ** lunch
+-------
+Total work done: 3 h 15 min
+Total slacking: 0 h 15 min
+"
+        );
+    }
+
+    #[test]
+    fn test_category() {
+        assert_eq!(category("customer joe: support"), Some("customer joe"));
+        assert_eq!(category("a: b: c"), Some("a"));
+        assert_eq!(category("code"), None);
+        assert_eq!(category(": code"), None);
+
+        let tl = Timelog::new_from_string(
+            "
+2022-06-10 07:00: arrived
+2022-06-10 08:00: acme: code
+2022-06-10 08:30: internal: review
+2022-06-10 09:00: acme: meeting
+",
+        );
+        let a = Activities::new_from_entries(
+            tl.get_day(&NaiveDate::from_ymd_opt(2022, 6, 10).unwrap()),
+            &Options::default(),
+        );
+        assert_eq!(a.category_total("acme"), Duration::minutes(90));
+        assert_eq!(a.category_total("internal"), Duration::minutes(30));
+        assert_eq!(a.category_total("other"), Duration::minutes(0));
+    }
+
+    #[test]
+    fn test_weighted_work() {
+        let tl = Timelog::new_from_string(
+            "
+2022-06-10 07:00: arrived
+2022-06-10 08:00: acme: code
+2022-06-10 12:00: standby: at home
+2022-06-10 13:00: callout: outage
+2022-06-10 13:30: **lunch
+",
+        );
+        let a = Activities::new_from_entries(
+            tl.get_day(&NaiveDate::from_ymd_opt(2022, 6, 10).unwrap()),
+            &Options::default(),
+        );
+        let multipliers = vec![("standby".to_string(), 0.25), ("callout".to_string(), 1.5)];
+        assert_eq!(a.total_work(), Duration::hours(6));
+        // 1 h + 4 h * 0.25 + 1 h * 1.5
+        assert_eq!(a.weighted_work(&multipliers), Duration::minutes(210));
+        assert_eq!(a.weighted_work(&[]), Duration::hours(6));
+    }
+
+    #[test]
+    fn test_where() {
+        let tl = Timelog::new_from_string(
+            "
+2022-06-10 07:00: arrived
+2022-06-10 08:00: code
+2022-06-10 08:30: **tea
+2022-06-10 09:00: code
+",
+        );
+        let entries: Vec<Entry> = tl.get_all().cloned().collect();
+        let a =
+            Activities::new_from_entries_where(&entries, |e| e.task == "code", &Options::default());
+        assert_eq!(a.total_work(), Duration::minutes(90));
+        assert_eq!(a.total_slack(), Duration::zero());
+
+        let a = Activities::new_from_shifts_where(
+            &[&entries],
+            |e| e.task != "code",
+            &Options::default(),
+        );
+        assert_eq!(a.total_work(), Duration::zero());
+        assert_eq!(a.total_slack(), Duration::minutes(30));
+    }
+
+    #[test]
+    fn test_group_by() {
+        let tl = Timelog::new_from_string(
+            "
+2022-06-09 07:00: arrived
+2022-06-09 08:00: acme: code
+2022-06-09 08:30: ** tea
+2022-06-10 07:00: arrived
+2022-06-10 08:00: 50% acme: review, 50% internal: meeting
+2022-06-10 09:00: internal: email
+2022-06-10 09:30: *** doctor
+",
+        );
+        let entries: Vec<Entry> = tl.get_all().cloned().collect();
+        let names = |a: &Activities| {
+            a.iter()
+                .map(|a| (a.name().to_string(), a.duration().num_minutes()))
+                .collect::<Vec<_>>()
+        };
+
+        let a = Activities::group_by(
+            &entries,
+            |e| category(e.task.as_str()).unwrap_or("other"),
+            &Options::default(),
+        );
+        assert_eq!(
+            names(&a),
+            vec![
+                ("acme".to_string(), 90),
+                ("other".to_string(), 30),
+                ("internal".to_string(), 90)
+            ]
+        );
+        assert_eq!(a.total_work(), Duration::minutes(180));
+        assert_eq!(a.total_slack(), Duration::minutes(30));
+
+        let a = Activities::group_by(
+            &entries,
+            |e| e.stop.format("%a").to_string(),
+            &Options::default(),
+        );
+        assert_eq!(
+            names(&a),
+            vec![("Thu".to_string(), 90), ("Fri".to_string(), 120)]
+        );
+
+        // merging keeps the work/slack totals of the parts
+        let merged = Activities::merge([&a, &a], &Options::default());
+        assert_eq!(merged.total_work(), Duration::minutes(360));
+        assert_eq!(merged.total_slack(), Duration::minutes(60));
+    }
+
+    #[test]
+    fn test_grouped_by_category() {
+        let tl = Timelog::new_from_string(
+            "
+2022-06-10 07:00: arrived
+2022-06-10 08:00: customer joe: support
+2022-06-10 08:30: internal: meeting
+2022-06-10 09:00: ** tea
+2022-06-10 10:30: customer joe: billing
+2022-06-10 10:45: email
+2022-06-10 11:00: customer joe: support
+",
+        );
+        let entries: Vec<Entry> = tl.get_all().cloned().collect();
+        let groups =
+            Activities::new_from_entries(&entries, &Options::default()).grouped_by_category();
+        assert_eq!(
+            groups
+                .groups
+                .iter()
+                .map(|g| (
+                    g.name.as_str(),
+                    g.duration.num_minutes(),
+                    g.activities.len()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("customer joe", 165, 2),
+                ("internal", 30, 1),
+                (NO_CATEGORY, 45, 2)
+            ]
+        );
+        assert_eq!(
+            groups.to_string(),
+            " 2 h 45 min: customer joe
+     1 h 15 min: support
+     1 h 30 min: billing
+ 0 h 30 min: internal
+     0 h 30 min: meeting
+ 0 h 45 min: (no category)
+     0 h 30 min: ** tea
+     0 h 15 min: email
+-------
+Total work done: 3 h 30 min
+Total slacking: 0 h 30 min
+"
+        );
+    }
+
+    #[test]
+    fn test_per_day() {
+        let tl = Timelog::new_from_string(
+            "
+2022-06-09 07:00: arrived
+2022-06-09 08:00: code
+2022-06-09 08:30: ** tea
+2022-06-10 07:00: arrived
+2022-06-10 09:00: code
+2022-06-10 09:15: email
+",
+        );
+        let entries: Vec<Entry> = tl.get_all().cloned().collect();
+        let daily = Activities::new_per_day(&entries, &Options::default());
+        assert_eq!(daily.days.len(), 2);
+        assert_eq!(
+            daily.days[0].0,
+            NaiveDate::from_ymd_opt(2022, 6, 9).unwrap()
+        );
+        assert_eq!(daily.days[0].1.total_work(), Duration::hours(1));
+        assert_eq!(daily.days[1].1.total_work(), Duration::minutes(135));
+        assert_eq!(daily.total.total_work(), Duration::minutes(195));
+        assert_eq!(daily.total.total_slack(), Duration::minutes(30));
+
+        assert_eq!(
+            daily.to_string(),
+            "Thursday, 2022-06-09:
+ 1 h  0 min: code
+ 0 h 30 min: ** tea
+-------
+Total work done: 1 h 0 min
+Total slacking: 0 h 30 min
+
+Friday, 2022-06-10:
+ 2 h  0 min: code
+ 0 h 15 min: email
+-------
+Total work done: 2 h 15 min
+Total slacking: 0 h 0 min
+
+Grand total:
+ 3 h  0 min: code
+ 0 h 30 min: ** tea
+ 0 h 15 min: email
+-------
+Total work done: 3 h 15 min
+Total slacking: 0 h 30 min
+"
+        );
+        assert!(daily
+            .format_days(&DE, "%x")
+            .starts_with("Donnerstag, 09.06.2022:\n 1 h  0 min: code\n"));
+        assert!(Activities::new_per_day(&[], &Options::default())
+            .days
+            .is_empty());
+    }
+
+    #[test]
+    fn test_per_day_where() {
+        let tl = Timelog::new_from_string(
+            "
+2022-06-08 07:00: arrived
+2022-06-08 09:00: code
+2022-06-09 07:00: arrived
+2022-06-09 08:00: joe: call
+2022-06-09 08:30: code
+2022-06-10 07:00: arrived
+2022-06-10 09:00: joe: fix bug
+2022-06-10 09:15: email
+",
+        );
+        let entries: Vec<Entry> = tl.get_all().cloned().collect();
+        let daily = Activities::new_per_day_where(
+            &entries,
+            |e| e.task.starts_with("joe"),
+            &Options::default(),
+        );
+        assert_eq!(daily.days.len(), 2);
+        assert_eq!(daily.total.total_work(), Duration::hours(3));
+        assert_eq!(
+            daily.format_totals(&EN, "%Y-%m-%d"),
+            "1 h 0 min: Thursday, 2022-06-09\n2 h 0 min: Friday, 2022-06-10\n"
+        );
+        assert_eq!(
+            daily.format_totals(&DE, "%x"),
+            "1 h 0 min: Donnerstag, 09.06.2022\n2 h 0 min: Freitag, 10.06.2022\n"
+        );
+    }
+
+    #[test]
+    fn test_ignored() {
+        let tl = Timelog::new_from_string(
+            "
+2022-06-10 07:00: arrived
+2022-06-10 08:00: code
+2022-06-10 09:00: *** doctor appointment
+2022-06-10 09:30: ** tea
+",
+        );
+        let entries: Vec<Entry> = tl.get_all().cloned().collect();
+        let a = Activities::new_from_entries(&entries, &Options::default());
+        assert_eq!(a.total_work(), Duration::hours(1));
+        assert_eq!(a.total_slack(), Duration::minutes(30));
+        assert_eq!(
+            a.iter().map(|a| a.name()).collect::<Vec<_>>(),
+            vec!["code", "** tea"]
+        );
+        assert_eq!(a.billable_total(), Duration::zero());
+
+        // other markers classify the same entries differently
+        let options = Options {
+            markers: Markers {
+                slack: "break".to_string(),
+                ignore: "*".to_string(),
+                billable: "co".to_string(),
+            },
+            ..Options::default()
+        };
+        let a = Activities::new_from_entries(&entries, &options);
+        assert_eq!(a.total_work(), Duration::hours(1));
+        assert_eq!(a.total_slack(), Duration::zero());
+        assert_eq!(a.iter().map(|a| a.name()).collect::<Vec<_>>(), vec!["code"]);
+        assert_eq!(a.billable_total(), Duration::hours(1));
+        assert_eq!(a.options(), &options);
+    }
+
+    #[test]
+    fn test_incremental() {
+        let tl = Timelog::new_from_string(
+            "
+2022-06-09 16:00: code
+2022-06-10 07:00: arrived
+2022-06-10 08:00: code
+2022-06-10 08:30: **tea
+2022-06-10 09:00: code
+",
+        );
+        let entries: Vec<Entry> = tl.get_all().cloned().collect();
+        let at = |h, m| {
+            NaiveDate::from_ymd_opt(2022, 6, 10)
+                .unwrap()
+                .and_hms_opt(h, m, 0)
+                .unwrap()
+        };
+
+        let mut a = Activities::new_from_entries(&entries[..3], &Options::default());
+        assert_eq!(a.total_work(), Duration::hours(1));
+        a.set_now(at(8, 20));
+        assert_eq!(a.pending(), Duration::minutes(20));
+        a.set_now(at(8, 25));
+        assert_eq!(a.pending(), Duration::minutes(25));
+
+        for e in &entries[3..] {
+            a.push(e);
+        }
+        assert_eq!(a.pending(), Duration::zero());
+        let all = Activities::new_from_entries(&entries, &Options::default());
+        assert_eq!(format!("{}", a), format!("{}", all));
+        assert_eq!(a.total_work(), Duration::minutes(90));
+
+        // next day
+        a.set_now(at(9, 10) + Duration::days(1));
+        assert_eq!(a.pending(), Duration::zero());
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_parallel() {
+        let mut log = String::new();
+        for day in 1..=28 {
+            for (hour, task) in [(8, "arrived"), (9, "code"), (10, "**tea"), (12, "review")] {
+                writeln!(log, "2022-02-{:02} {:02}:{:02}: {}", day, hour, day, task).unwrap();
+            }
+        }
+        let tl = Timelog::new_from_string(&log);
+        let entries: Vec<Entry> = tl.get_all().cloned().collect();
+        assert_eq!(
+            format!(
+                "{}",
+                Activities::new_from_entries_parallel(&entries, &Options::default())
+            ),
+            format!(
+                "{}",
+                Activities::new_from_entries(&entries, &Options::default())
+            )
+        );
+        assert_eq!(
+            Activities::new_from_entries_parallel(&[], &Options::default()).total_work(),
+            Duration::zero()
+        );
+    }
 }
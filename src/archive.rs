@@ -0,0 +1,142 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::compress;
+
+/**
+ * Yearly archive of a timelog file
+ *
+ * Archives are next to the timelog, named like `timelog-2019.txt`, optionally compressed as
+ * `timelog-2019.txt.gz` or `timelog-2019.txt.zst`.
+ */
+#[derive(Debug, PartialEq, Eq)]
+pub struct Archive {
+    pub year: i32,
+    pub path: PathBuf,
+}
+
+/**
+ * Year of an archive file name of timelog `stem` and `ext`, like "timelog", "txt"
+ */
+fn parse_name(name: &str, stem: &str, ext: &str) -> Option<i32> {
+    let rest = name.strip_prefix(stem)?.strip_prefix('-')?;
+    let (year, rest) = rest.split_at_checked(4)?;
+    if !year.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let rest = rest.strip_prefix('.')?.strip_prefix(ext)?;
+    match rest {
+        "" | ".gz" | ".zst" => year.parse().ok(),
+        _ => None,
+    }
+}
+
+/**
+ * All archives of a timelog file, sorted by year
+ */
+pub fn find(timelog_file: &Path) -> Vec<Archive> {
+    let stem = timelog_file
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("");
+    let ext = timelog_file
+        .extension()
+        .and_then(|s| s.to_str())
+        .unwrap_or("");
+    let dir = match timelog_file.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut archives: Vec<Archive> = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let year = parse_name(e.file_name().to_str()?, stem, ext)?;
+            Some(Archive {
+                year,
+                path: e.path(),
+            })
+        })
+        .collect();
+    archives.sort_by(|a, b| (a.year, &a.path).cmp(&(b.year, &b.path)));
+    archives
+}
+
+/**
+ * Contents of an archive, decompressed
+ */
+pub fn read(archive: &Archive) -> io::Result<String> {
+    let data = fs::read(&archive.path)?;
+    let data = compress::decompress_for(&archive.path, data)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", archive.path.display(), e)))?;
+    Ok(String::from_utf8_lossy(&data).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_name() {
+        assert_eq!(parse_name("timelog-2019.txt", "timelog", "txt"), Some(2019));
+        assert_eq!(
+            parse_name("timelog-2019.txt.gz", "timelog", "txt"),
+            Some(2019)
+        );
+        assert_eq!(
+            parse_name("timelog-2019.txt.zst", "timelog", "txt"),
+            Some(2019)
+        );
+        assert_eq!(parse_name("work-2020.log", "work", "log"), Some(2020));
+
+        assert_eq!(parse_name("timelog.txt", "timelog", "txt"), None);
+        assert_eq!(parse_name("timelog-19.txt", "timelog", "txt"), None);
+        assert_eq!(parse_name("timelog-2019.txt.bz2", "timelog", "txt"), None);
+        assert_eq!(parse_name("timelog-2019.txt~", "timelog", "txt"), None);
+        assert_eq!(parse_name("tasks-2019.txt", "timelog", "txt"), None);
+        assert_eq!(parse_name("timelog-20ä9.txt", "timelog", "txt"), None);
+    }
+
+    #[test]
+    fn test_find_read() {
+        let dir =
+            std::env::temp_dir().join(format!("rtimelog-archive-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let entry = "2019-06-10 07:00: arrived\n";
+        fs::write(dir.join("timelog.txt"), "").unwrap();
+        fs::write(dir.join("timelog-2020.txt"), "2020-01-01 07:00: arrived\n").unwrap();
+        fs::write(
+            dir.join("timelog-2019.txt.gz"),
+            compress::gzip(entry.as_bytes()).unwrap(),
+        )
+        .unwrap();
+        fs::write(dir.join("notes-2018.txt"), "").unwrap();
+
+        let archives = find(&dir.join("timelog.txt"));
+        assert_eq!(
+            archives.iter().map(|a| a.year).collect::<Vec<_>>(),
+            vec![2019, 2020]
+        );
+        assert_eq!(read(&archives[0]).unwrap(), entry);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,329 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate chrono;
+
+use std::process;
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+
+use crate::store::{Entry, Options, Timelog};
+
+const TIME_FMT: &str = "%Y-%m-%d %H:%M";
+
+/**
+ * Unlogged time of a day
+ *
+ * For a day without any entries, start and end are None. Otherwise this is the interval between
+ * an entry and a following placeholder entry.
+ */
+#[derive(Debug, PartialEq, Eq)]
+pub struct Gap {
+    pub start: Option<NaiveDateTime>,
+    pub end: Option<NaiveDateTime>,
+}
+
+/**
+ * Find gaps in the entries of a single day
+ *
+ * A gap ends at a placeholder entry, which the `options`' markers define.
+ */
+pub fn find_gaps(entries: &[Entry], options: &Options) -> Vec<Gap> {
+    let placeholder = options.markers.placeholder();
+    if entries.is_empty() {
+        return vec![Gap {
+            start: None,
+            end: None,
+        }];
+    }
+
+    entries
+        .windows(2)
+        .filter(|w| w[1].task == placeholder && options.day(&w[0]) == options.day(&w[1]))
+        .map(|w| Gap {
+            start: Some(w[0].stop),
+            end: Some(w[1].stop),
+        })
+        .collect()
+}
+
+/**
+ * Most recently used distinct tasks, newest first; skips placeholders and day starts
+ */
+pub fn recent_tasks(entries: &[Entry], count: usize, options: &Options) -> Vec<String> {
+    let placeholder = options.markers.placeholder();
+    let mut tasks: Vec<String> = Vec::new();
+    for e in entries.iter().rev() {
+        if tasks.len() >= count {
+            break;
+        }
+        if e.task == placeholder || e.task == "arrived" || tasks.iter().any(|t| e.task == **t) {
+            continue;
+        }
+        tasks.push(e.task.to_string());
+    }
+    tasks
+}
+
+/**
+ * Subjects of your git commits in the current directory's repository during the given time
+ *
+ * This is best-effort: outside of a git repository or without git, this returns nothing.
+ */
+pub fn git_suggestions(since: NaiveDateTime, until: NaiveDateTime) -> Vec<String> {
+    let author = process::Command::new("git")
+        .args(["config", "user.email"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .unwrap_or_default();
+
+    process::Command::new("git")
+        .arg("log")
+        .arg("--all")
+        .arg(format!("--author={}", author))
+        .arg(format!("--since={}", since.format(TIME_FMT)))
+        .arg(format!("--until={}", until.format(TIME_FMT)))
+        .arg("--format=%s")
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| {
+            String::from_utf8_lossy(&o.stdout)
+                .lines()
+                .map(|l| l.to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/**
+ * Answer to a gap prompt
+ */
+#[derive(Debug, PartialEq, Eq)]
+pub enum Answer {
+    // task which stopped at a given time inside the gap
+    Entry(Entry),
+    // task which fills the rest of the gap
+    Fill(String),
+}
+
+/**
+ * Parse a wizard answer "HH:MM task", or just "task" to fill the rest of a bounded gap
+ *
+ * The task can be given as the number of one of the `suggestions` (counting from 1). `after` is
+ * the time of the previous entry within the gap, if any.
+ */
+pub fn parse_answer(
+    line: &str,
+    day: NaiveDate,
+    gap: &Gap,
+    after: Option<NaiveDateTime>,
+    suggestions: &[String],
+) -> Result<Answer, String> {
+    let line = line.trim();
+    let (time, task) = match line.split_once(' ') {
+        Some((t, rest)) => match NaiveTime::parse_from_str(t, "%H:%M") {
+            Ok(time) => (Some(time), rest.trim()),
+            Err(_) => (None, line),
+        },
+        None => (None, line),
+    };
+
+    let task = match task.parse::<usize>() {
+        Ok(n) if n >= 1 && n <= suggestions.len() => suggestions[n - 1].clone(),
+        Ok(n) => return Err(format!("no suggestion number {}", n)),
+        Err(_) => task.to_string(),
+    };
+    if task.is_empty() {
+        return Err("missing task".to_string());
+    }
+
+    let Some(time) = time else {
+        if gap.end.is_none() {
+            return Err("please give the time when the task ended, as HH:MM task".to_string());
+        }
+        return Ok(Answer::Fill(task));
+    };
+
+    let stop = day.and_time(time);
+    if let Some(after) = after.or(gap.start) {
+        if stop <= after {
+            return Err(format!("{} is not after {}", time, after.format("%H:%M")));
+        }
+    }
+    if let Some(end) = gap.end {
+        if stop >= end {
+            return Err(format!(
+                "{} is not before the end of the gap {}",
+                time,
+                end.format("%H:%M")
+            ));
+        }
+    }
+    Ok(Answer::Entry(Entry {
+        stop,
+        task: task.into(),
+    }))
+}
+
+/**
+ * Apply the answer to a gap to the timelog
+ */
+pub fn apply_answer(timelog: &mut Timelog, gap: &Gap, answer: Answer) {
+    match answer {
+        Answer::Entry(e) => timelog.insert_at(e.stop, e.task.to_string()),
+        Answer::Fill(task) => {
+            let end = gap.end.expect("Fill answers need a bounded gap");
+            timelog.remove(end);
+            timelog.insert_at(end, task);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2022, 6, 10).unwrap()
+    }
+
+    fn at(h: u32, m: u32) -> NaiveDateTime {
+        day().and_hms_opt(h, m, 0).unwrap()
+    }
+
+    const DAY: &str = "
+2022-06-10 07:00: arrived
+2022-06-10 08:45: code
+2022-06-10 10:00: **
+2022-06-10 12:05: review
+2022-06-10 13:00: **
+";
+
+    #[test]
+    fn test_find_gaps() {
+        assert_eq!(
+            find_gaps(&[], &Options::default()),
+            vec![Gap {
+                start: None,
+                end: None
+            }]
+        );
+
+        let tl = Timelog::new_from_string(DAY);
+        assert_eq!(
+            find_gaps(tl.get_day(&day()), tl.options()),
+            vec![
+                Gap {
+                    start: Some(at(8, 45)),
+                    end: Some(at(10, 0))
+                },
+                Gap {
+                    start: Some(at(12, 5)),
+                    end: Some(at(13, 0))
+                },
+            ]
+        );
+
+        let tl = Timelog::new_from_string("2022-06-10 07:00: arrived\n2022-06-10 08:00: x\n");
+        assert!(find_gaps(tl.get_day(&day()), tl.options()).is_empty());
+    }
+
+    #[test]
+    fn test_recent_tasks() {
+        let tl = Timelog::new_from_string(DAY);
+        let entries: Vec<Entry> = tl.get_day(&day()).to_vec();
+        assert_eq!(
+            recent_tasks(&entries, 5, tl.options()),
+            vec!["review", "code"]
+        );
+        assert_eq!(recent_tasks(&entries, 1, tl.options()), vec!["review"]);
+        assert!(recent_tasks(&[], 5, tl.options()).is_empty());
+    }
+
+    #[test]
+    fn test_parse_answer() {
+        let gap = Gap {
+            start: Some(at(8, 45)),
+            end: Some(at(10, 0)),
+        };
+        let suggestions = vec!["code".to_string(), "review".to_string()];
+
+        assert_eq!(
+            parse_answer("09:15 email", day(), &gap, None, &suggestions),
+            Ok(Answer::Entry(Entry {
+                stop: at(9, 15),
+                task: "email".into()
+            }))
+        );
+        assert_eq!(
+            parse_answer("9:30 2", day(), &gap, Some(at(9, 15)), &suggestions),
+            Ok(Answer::Entry(Entry {
+                stop: at(9, 30),
+                task: "review".into()
+            }))
+        );
+        assert_eq!(
+            parse_answer("1", day(), &gap, None, &suggestions),
+            Ok(Answer::Fill("code".to_string()))
+        );
+        assert_eq!(
+            parse_answer("meeting prep", day(), &gap, None, &suggestions),
+            Ok(Answer::Fill("meeting prep".to_string()))
+        );
+
+        assert!(parse_answer("08:00 email", day(), &gap, None, &suggestions).is_err());
+        assert!(parse_answer("09:00 email", day(), &gap, Some(at(9, 15)), &suggestions).is_err());
+        assert!(parse_answer("10:00 email", day(), &gap, None, &suggestions).is_err());
+        assert!(parse_answer("3", day(), &gap, None, &suggestions).is_err());
+        assert!(parse_answer("", day(), &gap, None, &suggestions).is_err());
+    }
+
+    #[test]
+    fn test_parse_answer_empty_day() {
+        let gap = Gap {
+            start: None,
+            end: None,
+        };
+        assert_eq!(
+            parse_answer("08:00 arrived", day(), &gap, None, &[]),
+            Ok(Answer::Entry(Entry {
+                stop: at(8, 0),
+                task: "arrived".into()
+            }))
+        );
+        // unbounded gaps need a time
+        assert!(parse_answer("email", day(), &gap, None, &[]).is_err());
+    }
+
+    #[test]
+    fn test_apply_answer() {
+        let mut tl = Timelog::new_from_string(DAY);
+        let gaps = find_gaps(tl.get_day(&day()), tl.options());
+
+        let a = parse_answer("09:15 email", day(), &gaps[0], None, &[]).unwrap();
+        apply_answer(&mut tl, &gaps[0], a);
+        let a = parse_answer("code", day(), &gaps[0], Some(at(9, 15)), &[]).unwrap();
+        apply_answer(&mut tl, &gaps[0], a);
+
+        let entries = tl.get_day(&day());
+        assert_eq!(entries.len(), 6);
+        assert_eq!(&format!("{}", entries[2]), "2022-06-10 09:15: email");
+        assert_eq!(&format!("{}", entries[3]), "2022-06-10 10:00: code");
+        assert_eq!(&format!("{}", entries[5]), "2022-06-10 13:00: **");
+    }
+}
@@ -0,0 +1,177 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate chrono;
+
+use std::fmt;
+
+use chrono::{Datelike, Duration, NaiveDate};
+
+use crate::activity::{format_duration, Activities};
+use crate::config::Config;
+use crate::i18n::tr;
+use crate::store::Timelog;
+use crate::toil::Ledger;
+
+/**
+ * Work compared to the targets of a day and its week, and the running flex time balance
+ */
+#[derive(Debug, PartialEq, Eq)]
+pub struct Balance {
+    // work minus hours_per_day; negative while there is time left
+    pub day: Duration,
+    // work from Monday up to the day, minus the week's target
+    pub week: Duration,
+    // overtime minus missing time of all days from flex_start up to the day, like the TOIL ledger
+    pub flex: Duration,
+}
+
+impl Balance {
+    pub fn new(timelog: &Timelog, day: NaiveDate, config: &Config) -> Balance {
+        let work = |from: NaiveDate| {
+            Activities::new_from_entries(timelog.get_date_range(&from, &day), timelog.options())
+                .weighted_work(&config.oncall)
+        };
+        let monday = day - Duration::days(day.weekday().num_days_from_monday().into());
+        let start = config
+            .flex_start
+            .or_else(|| timelog.get_all().next().map(|e| timelog.options().day(e)))
+            .unwrap_or(day);
+        Balance {
+            day: work(day) - config.hours_per_day,
+            week: work(monday) - config.week_target(),
+            flex: Ledger::new(timelog, start, day, config).balance(),
+        }
+    }
+
+    /**
+     * Labels and durations, for extra report totals
+     */
+    pub fn lines(&self) -> Vec<(String, Duration)> {
+        let left_or_over = |d: Duration, left: &'static str, over: &'static str| {
+            if d < Duration::zero() {
+                (tr(left).to_string(), -d)
+            } else {
+                (tr(over).to_string(), d)
+            }
+        };
+        vec![
+            left_or_over(self.day, "Time left at work", "Overtime today"),
+            left_or_over(self.week, "Time left this week", "Overtime this week"),
+            (tr("Flex balance").to_string(), self.flex),
+        ]
+    }
+}
+
+impl fmt::Display for Balance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines = self.lines();
+        for (label, d) in &lines[..2] {
+            writeln!(f, "{}: {}", label, format_duration(*d))?;
+        }
+        let sign = if self.flex > Duration::zero() {
+            "+"
+        } else {
+            ""
+        };
+        writeln!(
+            f,
+            "{}: {}{}",
+            tr("Flex balance"),
+            sign,
+            format_duration(self.flex)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "
+2022-06-06 08:00: arrived
+2022-06-06 18:00: code
+
+2022-06-07 08:00: arrived
+2022-06-07 15:00: code
+
+2022-06-08 08:00: arrived
+2022-06-08 14:40: code
+";
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2022, 6, d).unwrap()
+    }
+
+    #[test]
+    fn test_balance() {
+        let tl = Timelog::new_from_string(LOG);
+        let config = Config::default();
+        let b = Balance::new(&tl, day(8), &config);
+        assert_eq!(
+            b,
+            Balance {
+                day: Duration::minutes(-80),
+                week: Duration::minutes(600 + 420 + 400 - 40 * 60),
+                flex: Duration::minutes(120 - 60 - 80),
+            }
+        );
+        assert_eq!(
+            format!("{}", b),
+            "Time left at work: 1 h 20 min
+Time left this week: 16 h 20 min
+Flex balance: -0 h 20 min
+"
+        );
+
+        let b = Balance::new(&tl, day(6), &config);
+        assert_eq!(
+            format!("{}", b),
+            "Overtime today: 2 h 0 min
+Time left this week: 30 h 0 min
+Flex balance: +2 h 0 min
+"
+        );
+
+        // the flex balance starts later, and with a smaller weekly target
+        let config = Config::new_from_string("hours_per_week = 20\nflex_start = \"2022-06-08\"");
+        let b = Balance::new(&tl, day(8), &config);
+        assert_eq!(b.week, Duration::minutes(600 + 420 + 400 - 20 * 60));
+        assert_eq!(b.flex, Duration::minutes(-80));
+    }
+
+    #[test]
+    fn test_balance_oncall() {
+        let tl = Timelog::new_from_string(&format!(
+            "{}
+2022-06-09 08:00: arrived
+2022-06-09 14:00: code
+2022-06-09 16:00: callout: server down
+",
+            LOG
+        ));
+        let config = Config::new_from_string("[oncall]\ncallout = 1.5\n");
+        let b = Balance::new(&tl, day(9), &config);
+        // 6 hours of code and 2 hours of callout, which count as 3
+        assert_eq!(b.day, Duration::hours(1));
+        assert_eq!(b.week, Duration::minutes(600 + 420 + 400 + 540 - 40 * 60));
+        assert_eq!(b.flex, Duration::minutes(120 - 60 - 80 + 60));
+        // without multipliers, the callout counts once
+        assert_eq!(
+            Balance::new(&tl, day(9), &Config::default()).day,
+            Duration::zero()
+        );
+    }
+}
@@ -0,0 +1,191 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate chrono;
+
+use std::fmt;
+
+use chrono::{Duration, NaiveDate};
+
+use crate::activity::{format_duration, Activities};
+use crate::store::Timelog;
+
+/**
+ * Burn-down of a category's hour budget over a period
+ */
+pub struct BurnDown {
+    category: String,
+    budget: Duration,
+    // (day, time spent on that day, budget remaining at the end of that day)
+    days: Vec<(NaiveDate, Duration, Duration)>,
+    projection: Option<NaiveDate>,
+}
+
+impl BurnDown {
+    /**
+     * Compute the burn-down of `category` from `from` to `to`
+     *
+     * Days after `today` are not counted; the exhaustion date gets projected from the average
+     * daily spending so far.
+     */
+    pub fn new(
+        timelog: &Timelog,
+        category: &str,
+        budget: Duration,
+        from: NaiveDate,
+        to: NaiveDate,
+        today: NaiveDate,
+    ) -> BurnDown {
+        let mut days = Vec::new();
+        let mut remaining = budget;
+        let last = to.min(today);
+
+        for day in from.iter_days().take_while(|d| *d <= last) {
+            let spent = Activities::new_from_entries(timelog.get_day(&day), timelog.options())
+                .category_total(category);
+            remaining -= spent;
+            days.push((day, spent, remaining));
+        }
+
+        let projection = match days.iter().find(|(_, _, r)| *r <= Duration::zero()) {
+            Some((day, _, _)) => Some(*day),
+            None if days.is_empty() => None,
+            None => {
+                let spent = budget - remaining;
+                let per_day = spent.num_seconds() / days.len() as i64;
+                if per_day > 0 {
+                    // round up to full days
+                    let more_days = (remaining.num_seconds() + per_day - 1) / per_day;
+                    Some(last + Duration::days(more_days))
+                } else {
+                    None
+                }
+            }
+        };
+
+        BurnDown {
+            category: category.to_string(),
+            budget,
+            days,
+            projection,
+        }
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.days.last().map(|d| d.2).unwrap_or(self.budget)
+    }
+
+    /**
+     * Day on which the budget was or is projected to be used up
+     */
+    pub fn projected_exhaustion(&self) -> Option<NaiveDate> {
+        self.projection
+    }
+}
+
+impl fmt::Display for BurnDown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Budget {}: {}",
+            self.category,
+            format_duration(self.budget)
+        )?;
+        for (day, spent, remaining) in &self.days {
+            writeln!(
+                f,
+                "{}: {} spent, {} remaining",
+                day,
+                format_duration(*spent),
+                format_duration(*remaining)
+            )?;
+        }
+        writeln!(f, "-------")?;
+        writeln!(f, "Remaining: {}", format_duration(self.remaining()))?;
+        match self.projection {
+            Some(day) if self.remaining() <= Duration::zero() => {
+                writeln!(f, "Exhausted on: {}", day)
+            }
+            Some(day) => writeln!(f, "Projected exhaustion: {}", day),
+            None => writeln!(f, "Projected exhaustion: never"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "
+2022-06-06 08:00: arrived
+2022-06-06 12:00: acme: code
+2022-06-06 13:00: internal: meeting
+
+2022-06-08 08:00: arrived
+2022-06-08 10:00: acme: review
+";
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2022, 6, d).unwrap()
+    }
+
+    #[test]
+    fn test_burndown() {
+        let tl = Timelog::new_from_string(LOG);
+        let b = BurnDown::new(&tl, "acme", Duration::hours(10), day(6), day(10), day(8));
+        assert_eq!(b.days.len(), 3);
+        assert_eq!(b.days[0], (day(6), Duration::hours(4), Duration::hours(6)));
+        assert_eq!(b.days[1], (day(7), Duration::zero(), Duration::hours(6)));
+        assert_eq!(b.days[2], (day(8), Duration::hours(2), Duration::hours(4)));
+        assert_eq!(b.remaining(), Duration::hours(4));
+        // 6 h in 3 days = 2 h per day, so 2 more days
+        assert_eq!(b.projected_exhaustion(), Some(day(10)));
+
+        assert_eq!(
+            format!("{}", b),
+            "Budget acme: 10 h 0 min
+2022-06-06: 4 h 0 min spent, 6 h 0 min remaining
+2022-06-07: 0 h 0 min spent, 6 h 0 min remaining
+2022-06-08: 2 h 0 min spent, 4 h 0 min remaining
+-------
+Remaining: 4 h 0 min
+Projected exhaustion: 2022-06-10
+"
+        );
+    }
+
+    #[test]
+    fn test_burndown_exhausted() {
+        let tl = Timelog::new_from_string(LOG);
+        let b = BurnDown::new(&tl, "acme", Duration::hours(5), day(6), day(30), day(30));
+        assert_eq!(b.remaining(), Duration::hours(-1));
+        assert_eq!(b.projected_exhaustion(), Some(day(8)));
+        assert!(format!("{}", b).ends_with("Remaining: -1 h 0 min\nExhausted on: 2022-06-08\n"));
+    }
+
+    #[test]
+    fn test_burndown_unused() {
+        let tl = Timelog::new_from_string(LOG);
+        let b = BurnDown::new(&tl, "other", Duration::hours(5), day(6), day(8), day(8));
+        assert_eq!(b.remaining(), Duration::hours(5));
+        assert_eq!(b.projected_exhaustion(), None);
+
+        // period in the future
+        let b = BurnDown::new(&tl, "acme", Duration::hours(5), day(9), day(12), day(8));
+        assert!(b.days.is_empty());
+        assert_eq!(b.remaining(), Duration::hours(5));
+        assert_eq!(b.projected_exhaustion(), None);
+    }
+}
@@ -0,0 +1,126 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate chrono;
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use chrono::NaiveDate;
+
+use crate::activity::Activities;
+use crate::store::{Entry, Options, Timelog};
+
+fn hash_entries(entries: &[Entry]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    entries.hash(&mut hasher);
+    hasher.finish()
+}
+
+/**
+ * Cache of per-day Activities
+ *
+ * Each day is keyed by a hash of its entries, so that only changed days get recomputed.
+ */
+#[derive(Default)]
+pub struct DayCache {
+    days: HashMap<NaiveDate, (u64, Activities)>,
+    // number of computed days, for testing
+    misses: usize,
+}
+
+impl DayCache {
+    pub fn new() -> DayCache {
+        DayCache::default()
+    }
+
+    /**
+     * Activities of a day with the given entries, classified with `options`
+     */
+    pub fn day(&mut self, day: NaiveDate, entries: &[Entry], options: &Options) -> &Activities {
+        let hash = hash_entries(entries);
+        if !matches!(self.days.get(&day), Some((h, a)) if *h == hash && a.options() == options) {
+            self.misses += 1;
+            self.days
+                .insert(day, (hash, Activities::new_from_entries(entries, options)));
+        }
+        &self.days[&day].1
+    }
+
+    /**
+     * Activities of all days from `from` to `to` (inclusive)
+     */
+    pub fn range(&mut self, timelog: &Timelog, from: NaiveDate, to: NaiveDate) -> Activities {
+        for day in from.iter_days().take_while(|d| *d <= to) {
+            self.day(day, timelog.get_day(&day), timelog.options());
+        }
+        Activities::merge(
+            from.iter_days()
+                .take_while(|d| *d <= to)
+                .map(|day| &self.days[&day].1),
+            timelog.options(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "
+2022-06-09 08:00: arrived
+2022-06-09 09:00: code
+2022-06-09 10:00: **tea
+
+2022-06-10 07:00: arrived
+2022-06-10 08:00: review
+2022-06-10 09:00: code
+";
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2022, 6, d).unwrap()
+    }
+
+    #[test]
+    fn test_cache() {
+        let mut tl = Timelog::new_from_string(LOG);
+        let mut cache = DayCache::new();
+
+        let a = cache.range(&tl, day(9), day(10));
+        assert_eq!(cache.misses, 2);
+        assert_eq!(
+            format!("{}", a),
+            format!(
+                "{}",
+                Activities::new_from_entries(tl.get_date_range(&day(9), &day(10)), tl.options())
+            )
+        );
+
+        // unchanged
+        cache.range(&tl, day(9), day(10));
+        assert_eq!(cache.misses, 2);
+
+        // only the changed day gets recomputed
+        tl.add_since(
+            "email".to_string(),
+            day(10).and_hms_opt(9, 0, 0).unwrap(),
+            day(10).and_hms_opt(9, 30, 0).unwrap(),
+        );
+        let a = cache.range(&tl, day(9), day(10));
+        assert_eq!(cache.misses, 3);
+        assert!(format!("{}", a).contains(" 0 h 30 min: email"));
+    }
+}
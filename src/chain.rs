@@ -0,0 +1,161 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::fmt::Write as _; // import without risk of name clashing
+
+use crate::sha256::{hex, sha256};
+use crate::store::{parse_entry, Entry};
+
+/**
+ * Hashes of the entries are recorded in this file next to the timelog
+ *
+ * Each line has the hash of the previous line's hash and the entry, so that changing, removing,
+ * or inserting any entry breaks the chain from there on.
+ */
+pub const FILE: &str = "chain.txt";
+
+/// "previous hash" of the first link
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+fn link(prev: &str, entry: &Entry) -> String {
+    hex(&sha256(format!("{}\n{}", prev, entry).as_bytes()))
+}
+
+/**
+ * Hash of the last link in the chain file contents
+ */
+pub fn last_hash(contents: &str) -> &str {
+    contents
+        .lines()
+        .rev()
+        .find_map(|line| line.split_once('\t'))
+        .map_or(GENESIS, |(hash, _)| hash)
+}
+
+/**
+ * Chain file lines for new entries, after the chain file `contents`
+ */
+pub fn extend(contents: &str, entries: &[Entry]) -> String {
+    let mut prev = last_hash(contents).to_string();
+    let mut output = String::new();
+    for entry in entries {
+        prev = link(&prev, entry);
+        writeln!(output, "{}\t{}", prev, entry).unwrap();
+    }
+    output
+}
+
+/**
+ * Check the chain file contents, and that the timelog has exactly the chained entries
+ *
+ * Entries before the first chained one are the ones before the chain was started. Entries after
+ * the last chained one were added while the chain was not kept up to date. Returns the number of
+ * chained entries, or a description of the first problem.
+ */
+pub fn verify(contents: &str, entries: &[Entry]) -> Result<usize, String> {
+    let mut chained = Vec::new();
+    let mut prev = GENESIS.to_string();
+    for (i, line) in contents.lines().enumerate() {
+        let (hash, entry) = line
+            .split_once('\t')
+            .ok_or_else(|| format!("{} line {}: invalid format", FILE, i + 1))?;
+        let entry =
+            parse_entry(entry).ok_or_else(|| format!("{} line {}: invalid entry", FILE, i + 1))?;
+        prev = link(&prev, &entry);
+        if hash != prev {
+            return Err(format!(
+                "{} line {}: hash mismatch, the chain was changed",
+                FILE,
+                i + 1
+            ));
+        }
+        chained.push(entry);
+    }
+    let Some(first) = chained.first() else {
+        return Err(format!("{} has no entries", FILE));
+    };
+
+    let start = entries
+        .iter()
+        .position(|e| e == first)
+        .ok_or_else(|| format!("first chained entry {} is not in the timelog", first))?;
+    let logged = &entries[start..];
+    for (i, entry) in chained.iter().enumerate() {
+        match logged.get(i) {
+            Some(e) if e == entry => (),
+            Some(e) => return Err(format!("timelog has {} where the chain has {}", e, entry)),
+            None => return Err(format!("chained entry {} is not in the timelog", entry)),
+        }
+    }
+    if let Some(e) = logged.get(chained.len()) {
+        return Err(format!("entry {} is not chained", e));
+    }
+    Ok(chained.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::parse_entries;
+
+    #[test]
+    fn test_extend_verify() {
+        let (entries, _) = parse_entries(
+            "2022-06-09 06:02: arrived
+2022-06-10 07:00: arrived
+2022-06-10 12:05: rtimelog: code
+2022-06-10 12:30: **lunch
+",
+        );
+        // chain started after the first entry
+        let chain = extend("", &entries[1..3]);
+        assert_eq!(chain.lines().count(), 2);
+        assert_eq!(verify(&chain, &entries[..3]), Ok(2));
+        assert_eq!(
+            verify(&chain, &entries),
+            Err("entry 2022-06-10 12:30: **lunch is not chained".to_string())
+        );
+        let chain = chain.clone() + &extend(&chain, &entries[3..]);
+        assert_eq!(verify(&chain, &entries), Ok(3));
+        assert_eq!(
+            last_hash(&chain),
+            chain.lines().last().unwrap()[..64].to_string()
+        );
+
+        // changed entry
+        let (mut changed, _) = parse_entries(
+            "2022-06-09 06:02: arrived
+2022-06-10 07:00: arrived
+2022-06-10 12:05: rtimelog: review
+2022-06-10 12:30: **lunch
+",
+        );
+        assert_eq!(
+            verify(&chain, &changed),
+            Err("timelog has 2022-06-10 12:05: rtimelog: review where the chain has 2022-06-10 12:05: rtimelog: code".to_string())
+        );
+        // removed entry
+        changed.remove(2);
+        assert!(verify(&chain, &changed).is_err());
+
+        // changed chain
+        let forged = chain.replace("rtimelog: code", "rtimelog: review");
+        assert_eq!(
+            verify(&forged, &changed),
+            Err("chain.txt line 2: hash mismatch, the chain was changed".to_string())
+        );
+        assert!(verify("", &entries).is_err());
+    }
+}
@@ -0,0 +1,295 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::{Error, ErrorKind};
+
+fn usage_error(message: String) -> Error {
+    Error::new(ErrorKind::Usage, message)
+}
+
+/**
+ * Remove an option which applies to all commands, like "--format json", from `args`
+ */
+pub fn take_option(args: &mut Vec<String>, name: &str) -> Result<Option<String>, Error> {
+    let long = format!("--{}", name);
+    let end = args.iter().position(|a| a == "--").unwrap_or(args.len());
+    let Some(i) = args[..end]
+        .iter()
+        .position(|a| *a == long || a.starts_with(&format!("{}=", long)))
+    else {
+        return Ok(None);
+    };
+
+    let arg = args.remove(i);
+    match arg.split_once('=') {
+        Some((_, value)) => Ok(Some(value.to_string())),
+        None if i < end - 1 => Ok(Some(args.remove(i))),
+        None => Err(usage_error(format!("option {} needs a value", long))),
+    }
+}
+
+/**
+ * Remove a flag which applies to all commands, like "--dry-run", from `args`
+ */
+pub fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    let long = format!("--{}", name);
+    let end = args.iter().position(|a| a == "--").unwrap_or(args.len());
+    match args[..end].iter().position(|a| *a == long) {
+        Some(i) => {
+            args.remove(i);
+            true
+        }
+        None => false,
+    }
+}
+
+/**
+ * Remove -v/-vv/-vvv/--verbose options from `args`, and return how verbose to be
+ */
+pub fn take_verbosity(args: &mut Vec<String>) -> u8 {
+    let end = args.iter().position(|a| a == "--").unwrap_or(args.len());
+    let mut verbosity = 0;
+    let mut i = 0;
+    for _ in 0..end {
+        let arg = &args[i];
+        let count = match arg.strip_prefix('-') {
+            _ if arg == "--verbose" => 1,
+            Some(vs) if !vs.is_empty() && vs.chars().all(|c| c == 'v') => vs.len(),
+            _ => 0,
+        };
+        if count > 0 {
+            verbosity += count;
+            args.remove(i);
+        } else {
+            i += 1;
+        }
+    }
+    verbosity.min(u8::MAX.into()) as u8
+}
+
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+
+/**
+ * Answer all confirm() questions with yes, for --yes
+ */
+pub fn set_assume_yes(yes: bool) {
+    ASSUME_YES.store(yes, Ordering::Relaxed);
+}
+
+fn ask(question: &str, input: &mut impl BufRead) -> bool {
+    print!("{} [y/N] ", question);
+    let _ = io::stdout().flush();
+    let mut answer = String::new();
+    if input.read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+/**
+ * Ask before a destructive operation; `question` should summarize what will happen
+ *
+ * Without a terminal, this only succeeds with --yes.
+ */
+pub fn confirm(question: &str) -> bool {
+    if ASSUME_YES.load(Ordering::Relaxed) {
+        return true;
+    }
+    let stdin = io::stdin();
+    if !stdin.is_terminal() {
+        eprintln!("{}", question);
+        eprintln!("Not a terminal, use --yes to confirm");
+        return false;
+    }
+    ask(question, &mut stdin.lock())
+}
+
+/**
+ * Parsed command line arguments of a subcommand
+ *
+ * Options are `--flag`, `--name value`, or `--name=value`; everything else (including a lone `-`)
+ * is positional. `--` ends option parsing.
+ */
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Args {
+    pub positional: Vec<String>,
+    options: HashMap<String, Option<String>>,
+}
+
+impl Args {
+    /**
+     * Parse `args` with the given known boolean `flags` and value-taking `options` (without "--")
+     */
+    pub fn parse(args: &[String], flags: &[&str], options: &[&str]) -> Result<Args, Error> {
+        let mut result = Args::default();
+        let mut iter = args.iter();
+
+        while let Some(arg) = iter.next() {
+            let Some(opt) = arg.strip_prefix("--") else {
+                result.positional.push(arg.clone());
+                continue;
+            };
+            if opt.is_empty() {
+                result.positional.extend(iter.by_ref().cloned());
+                break;
+            }
+
+            let (name, inline_value) = match opt.split_once('=') {
+                Some((n, v)) => (n, Some(v.to_string())),
+                None => (opt, None),
+            };
+
+            if flags.contains(&name) {
+                if inline_value.is_some() {
+                    return Err(usage_error(format!(
+                        "option --{} does not take a value",
+                        name
+                    )));
+                }
+                result.options.insert(name.to_string(), None);
+            } else if options.contains(&name) {
+                let value = match inline_value {
+                    Some(v) => v,
+                    None => iter
+                        .next()
+                        .ok_or_else(|| usage_error(format!("option --{} needs a value", name)))?
+                        .clone(),
+                };
+                result.options.insert(name.to_string(), Some(value));
+            } else {
+                return Err(usage_error(format!("unknown option --{}", name)));
+            }
+        }
+
+        Ok(result)
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        self.options.contains_key(name)
+    }
+
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.options.get(name).and_then(|v| v.as_deref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_empty() {
+        let args = Args::parse(&[], &["week"], &["from"]).unwrap();
+        assert!(args.positional.is_empty());
+        assert!(!args.flag("week"));
+        assert_eq!(args.value("from"), None);
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let args = Args::parse(
+            &strings(&[
+                "--week",
+                "--from",
+                "last monday",
+                "a",
+                "--to=today",
+                "-",
+                "b",
+            ]),
+            &["week", "month"],
+            &["from", "to"],
+        )
+        .unwrap();
+        assert!(args.flag("week"));
+        assert!(!args.flag("month"));
+        assert_eq!(args.value("from"), Some("last monday"));
+        assert_eq!(args.value("to"), Some("today"));
+        assert_eq!(args.positional, vec!["a", "-", "b"]);
+    }
+
+    #[test]
+    fn test_parse_double_dash() {
+        let args = Args::parse(&strings(&["--week", "--", "--from"]), &["week"], &[]).unwrap();
+        assert!(args.flag("week"));
+        assert_eq!(args.positional, vec!["--from"]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = |args: &[&str]| Args::parse(&strings(args), &["week"], &["from"]).unwrap_err();
+        assert_eq!(err(&["--bogus"]).message, "unknown option --bogus");
+        assert_eq!(err(&["--bogus"]).kind, ErrorKind::Usage);
+        assert_eq!(err(&["--from"]).message, "option --from needs a value");
+        assert_eq!(
+            err(&["--week=1"]).message,
+            "option --week does not take a value"
+        );
+    }
+
+    #[test]
+    fn test_take_option() {
+        let mut args = strings(&["report", "--format", "json", "--today"]);
+        assert_eq!(
+            take_option(&mut args, "format"),
+            Ok(Some("json".to_string()))
+        );
+        assert_eq!(args, strings(&["report", "--today"]));
+        assert_eq!(take_option(&mut args, "format"), Ok(None));
+
+        let mut args = strings(&["--format=text", "add", "--", "--format", "x"]);
+        assert_eq!(
+            take_option(&mut args, "format"),
+            Ok(Some("text".to_string()))
+        );
+        assert_eq!(args, strings(&["add", "--", "--format", "x"]));
+
+        let mut args = strings(&["add", "--format", "--"]);
+        assert!(take_option(&mut args, "format").is_err());
+    }
+
+    #[test]
+    fn test_take_flag() {
+        let mut args = strings(&["add", "--dry-run", "x", "--", "--dry-run"]);
+        assert!(take_flag(&mut args, "dry-run"));
+        assert_eq!(args, strings(&["add", "x", "--", "--dry-run"]));
+        assert!(!take_flag(&mut args, "dry-run"));
+    }
+
+    #[test]
+    fn test_ask() {
+        assert!(ask("Really?", &mut "y\n".as_bytes()));
+        assert!(ask("Really?", &mut " YES \n".as_bytes()));
+        assert!(!ask("Really?", &mut "n\n".as_bytes()));
+        assert!(!ask("Really?", &mut "\n".as_bytes()));
+        assert!(!ask("Really?", &mut "".as_bytes()));
+    }
+
+    #[test]
+    fn test_take_verbosity() {
+        let mut args = strings(&["-vv", "report", "--verbose", "-", "--", "-v"]);
+        assert_eq!(take_verbosity(&mut args), 3);
+        assert_eq!(args, strings(&["report", "-", "--", "-v"]));
+        assert_eq!(take_verbosity(&mut args), 0);
+    }
+}
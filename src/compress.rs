@@ -0,0 +1,96 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{self, Stdio};
+use std::thread;
+
+/**
+ * Pipe data through an external (de)compression program
+ */
+fn pipe(program: &str, args: &[&str], data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut child = process::Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| io::Error::new(e.kind(), format!("cannot run {}: {}", program, e)))?;
+    // feed stdin from a thread, so that a full stdout pipe cannot block us
+    let mut stdin = child.stdin.take().unwrap();
+    let input = data.to_vec();
+    let writer = thread::spawn(move || stdin.write_all(&input));
+    let output = child.wait_with_output()?;
+    writer.join().unwrap()?;
+    if !output.status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} failed: {}",
+                program,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ));
+    }
+    Ok(output.stdout)
+}
+
+pub fn gzip(data: &[u8]) -> io::Result<Vec<u8>> {
+    pipe("gzip", &["-c", "-n"], data)
+}
+
+pub fn gunzip(data: &[u8]) -> io::Result<Vec<u8>> {
+    pipe("gzip", &["-d", "-c"], data)
+}
+
+pub fn unzstd(data: &[u8]) -> io::Result<Vec<u8>> {
+    pipe("zstd", &["-d", "-c", "-q"], data)
+}
+
+/**
+ * Decompress data according to the file name extension (.gz or .zst); other data is unchanged
+ */
+pub fn decompress_for(path: &Path, data: Vec<u8>) -> io::Result<Vec<u8>> {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("gz") => gunzip(&data),
+        Some("zst") => unzstd(&data),
+        _ => Ok(data),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gzip() {
+        let data = "2022-06-10 07:00: arrived\n".repeat(1000);
+        let compressed = gzip(data.as_bytes()).unwrap();
+        assert!(compressed.len() < data.len() / 10);
+        assert_eq!(gunzip(&compressed).unwrap(), data.as_bytes());
+
+        assert!(gunzip(b"not gzip").is_err());
+
+        assert_eq!(
+            decompress_for(Path::new("timelog-2019.txt.gz"), compressed).unwrap(),
+            data.as_bytes()
+        );
+        assert_eq!(
+            decompress_for(Path::new("timelog-2019.txt"), b"plain".to_vec()).unwrap(),
+            b"plain"
+        );
+    }
+}
@@ -0,0 +1,1322 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate dirs;
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use chrono::{Duration, NaiveDate, NaiveTime, Weekday};
+
+use crate::activity::RoundingMode;
+use crate::footer::Expression;
+use crate::markers::Markers;
+use crate::quickentry::parse_duration;
+use crate::regex::Regex;
+use crate::store;
+
+/**
+ * Parse a number of hours like "7.5", or a duration like "7h30m", of at most `max`
+ */
+fn parse_hours(value: &str, max: Duration) -> Option<Duration> {
+    let hours = match value.parse::<f64>() {
+        Ok(hours) if hours.is_finite() && hours >= 0.0 => {
+            Duration::try_minutes((hours * 60.0).round() as i64)?
+        }
+        Ok(_) => return None,
+        Err(_) => parse_duration(value)?,
+    };
+    Some(hours).filter(|h| *h <= max)
+}
+
+/**
+ * Split a comma separated list
+ */
+fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
+/**
+ * Expand a leading "~/" to the home directory
+ */
+fn expand_home(value: &str) -> PathBuf {
+    match (value.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(value),
+    }
+}
+
+/**
+ * Environment variable which overrides a configuration key, like RTIMELOG_SCHEDULE_TIME for
+ * "schedule.time"
+ */
+pub fn env_var(key: &str) -> String {
+    format!("RTIMELOG_{}", key.to_uppercase().replace(['.', '-'], "_"))
+}
+
+static FILE: OnceLock<PathBuf> = OnceLock::new();
+
+/**
+ * Read the configuration from `path` instead of $RTIMELOG_CONFIG or the default file
+ */
+pub fn set_file(path: PathBuf) {
+    FILE.set(path)
+        .expect("config::set_file() can only be called once");
+}
+
+/**
+ * Format a duration for the config file, like "7h30m"
+ */
+fn format_duration(d: Duration) -> String {
+    match (d.num_hours(), d.num_minutes() % 60) {
+        (h, 0) => format!("{}h", h),
+        (0, m) => format!("{}m", m),
+        (h, m) => format!("{}h{}m", h, m),
+    }
+}
+
+/**
+ * Format a value for the config file: numbers and booleans bare, everything else quoted
+ */
+pub fn format_value(value: &str) -> String {
+    if value.parse::<f64>().is_ok() || value == "true" || value == "false" {
+        return value.to_string();
+    }
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t");
+    format!("\"{}\"", escaped)
+}
+
+fn format_key(key: &str) -> String {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        key.to_string()
+    } else {
+        format!("\"{}\"", key)
+    }
+}
+
+/**
+ * Set `key` (like "leave.vacation_days") to `value` in the contents of a config file
+ *
+ * This keeps comments and all other settings. The key gets added to its section if it is not set
+ * yet, and a missing section gets appended.
+ */
+pub fn set_in_contents(contents: &str, key: &str, value: &str) -> String {
+    let (section, name) = key.split_once('.').unwrap_or(("", key));
+    let new_line = format!("{} = {}", format_key(name), format_value(value));
+    let mut lines: Vec<String> = contents.lines().map(str::to_string).collect();
+
+    let mut current = String::new();
+    // index after the last line of the wanted section
+    let mut insert_at = if section.is_empty() { Some(0) } else { None };
+    for (i, line) in lines.iter().enumerate() {
+        match Config::parse_line(line) {
+            Ok(Line::Section(s)) => {
+                if s == section {
+                    insert_at = Some(i + 1);
+                }
+                current = s;
+            }
+            Ok(Line::KeyValue(k, _)) if current == section && k == name => {
+                lines[i] = new_line;
+                return lines.join("\n") + "\n";
+            }
+            Ok(Line::KeyValue(..)) if current == section => insert_at = Some(i + 1),
+            _ => (),
+        }
+    }
+
+    match insert_at {
+        Some(i) => lines.insert(i, new_line),
+        None => {
+            if lines.last().is_some_and(|l| !l.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push(format!("[{}]", section));
+            lines.push(new_line);
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+enum Line {
+    Empty,
+    Section(String),
+    KeyValue(String, String),
+}
+
+/**
+ * Parse another file in the rtimelogrc format into (section, key, value), in file order
+ *
+ * Keys outside of a section have an empty section. Fails on the first invalid line.
+ */
+pub fn parse_settings(contents: &str) -> Result<Vec<(String, String, String)>, String> {
+    let mut settings = Vec::new();
+    let mut section = String::new();
+    for (lineno, line) in contents.lines().enumerate() {
+        match Config::parse_line(line).map_err(|e| format!("line {}: {}", lineno + 1, e))? {
+            Line::KeyValue(key, value) => settings.push((section.clone(), key, value)),
+            Line::Section(name) => section = name,
+            Line::Empty => (),
+        }
+    }
+    Ok(settings)
+}
+
+/**
+ * How entries are grouped into days for the daily view and reports
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DayGrouping {
+    // calendar days
+    Calendar,
+    // blocks of entries without a long break, which may cross midnight
+    Shift,
+}
+
+/**
+ * User configuration from rtimelogrc
+ *
+ * The file uses a TOML subset: `key = value` lines, where value is a "quoted string", a number,
+ * or a boolean, and `[section]` headers. Empty lines and `#` comments are ignored.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// the timelog, instead of the default ~/.gtimelog/timelog.txt
+    pub file: Option<PathBuf>,
+    /// your name and email address, as sender of reports
+    pub name: Option<String>,
+    pub email: Option<String>,
+    /// strftime format for dates in reports; %x is the locale's date format
+    pub date_format: String,
+    /// strftime format for times of day
+    pub time_format: String,
+    /// "calendar" or "shift"
+    pub day_grouping: DayGrouping,
+    /// with shift grouping, a break longer than this starts a new shift
+    pub shift_break: Duration,
+    /// split concurrent tasks like "code / mentoring" at this separator
+    pub task_separator: Option<String>,
+    /// hour budgets of categories, from the [budgets] section
+    pub budgets: Vec<(String, Duration)>,
+    /// on-call multipliers of categories, from the [oncall] section
+    pub oncall: Vec<(String, f64)>,
+    /// expected work time per day
+    pub hours_per_day: Duration,
+    /// weekly target for the balance; without it, five times hours_per_day
+    pub hours_per_week: Option<Duration>,
+    /// first day of the flex time balance; without it, the first day in the timelog
+    pub flex_start: Option<NaiveDate>,
+    /// yearly vacation allowance in days
+    pub vacation_days: u32,
+    /// tasks starting with one of these mark a vacation day
+    pub vacation_markers: Vec<String>,
+    /// tasks starting with one of these mark a sick day
+    pub sick_markers: Vec<String>,
+    /// tasks starting with one of these mark time off in lieu of overtime
+    pub toil_markers: Vec<String>,
+    /// URL of a shared task list, like gtimelog's remote task list
+    pub task_list_url: Option<String>,
+    /// fetch the task list again when the cached copy is older than this
+    pub task_list_expiry: Duration,
+    /// never write the timelog
+    pub read_only: bool,
+    /// record hashes of appended entries, and refuse changing existing ones
+    pub hash_chain: bool,
+    /// round the times of new entries to this many minutes; 0 keeps them exact
+    pub snap_minutes: u32,
+    /// round the durations of activities in reports, like "nearest:15" or "up:5"
+    pub rounding: RoundingMode,
+    /// entries before this time of day count for the previous day, like in gtimelog
+    pub virtual_midnight: NaiveTime,
+    /// when `rtimelog schedule` sends the report of the previous week, from the [schedule] section
+    pub schedule_day: Weekday,
+    pub schedule_time: NaiveTime,
+    /// where to send it: an email address, and/or a URL for POSTing the report as JSON
+    pub schedule_email: Option<String>,
+    pub schedule_webhook: Option<String>,
+    /// where `rtimelog send-report` mails reports to, from the [mail] section; they go out with
+    /// the sendmail command, or through an SMTP server URL like "smtps://mail.example.com"
+    pub mail_to: Option<String>,
+    pub mail_sendmail: String,
+    pub mail_smtp: Option<String>,
+    pub mail_smtp_user: Option<String>,
+    /// task prefixes for slack, ignored entries, and billable work, from the [markers] section
+    pub markers: Markers,
+    /// conventions for new entries, from the [validation] section
+    pub task_pattern: Option<Regex>,
+    pub require_category: bool,
+    pub max_backdate: Option<Duration>,
+    /// extra report lines computed from report values, from the [footer] section
+    pub footer: Vec<(String, Expression)>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            file: None,
+            name: None,
+            email: None,
+            date_format: "%x".to_string(),
+            time_format: "%H:%M".to_string(),
+            day_grouping: DayGrouping::Calendar,
+            shift_break: Duration::hours(6),
+            task_separator: None,
+            budgets: Vec::new(),
+            oncall: Vec::new(),
+            hours_per_day: Duration::hours(8),
+            hours_per_week: None,
+            flex_start: None,
+            vacation_days: 0,
+            vacation_markers: vec!["*** vacation".to_string(), "*** PTO".to_string()],
+            sick_markers: vec!["*** sick".to_string()],
+            toil_markers: vec!["*** TOIL".to_string()],
+            task_list_url: None,
+            task_list_expiry: Duration::hours(24),
+            read_only: false,
+            hash_chain: false,
+            snap_minutes: 0,
+            rounding: RoundingMode::None,
+            virtual_midnight: NaiveTime::MIN,
+            schedule_day: Weekday::Mon,
+            schedule_time: NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            schedule_email: None,
+            schedule_webhook: None,
+            mail_to: None,
+            mail_sendmail: "sendmail -t".to_string(),
+            mail_smtp: None,
+            mail_smtp_user: None,
+            markers: Markers::default(),
+            task_pattern: None,
+            require_category: false,
+            max_backdate: None,
+            footer: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn get_default_file() -> PathBuf {
+        let mut path = match env::var_os("XDG_CONFIG_HOME") {
+            Some(val) => PathBuf::from(val),
+            None => dirs::config_dir().unwrap(),
+        };
+        path.push("rtimelog");
+        path.push("rtimelogrc");
+        path
+    }
+
+    /**
+     * The file given with set_file(), otherwise $RTIMELOG_CONFIG, otherwise the default file
+     */
+    pub fn get_file() -> PathBuf {
+        FILE.get()
+            .cloned()
+            .or_else(|| env::var_os("RTIMELOG_CONFIG").map(PathBuf::from))
+            .unwrap_or_else(Config::get_default_file)
+    }
+
+    /**
+     * Configuration from get_file(), with the overrides from the environment (see env_var())
+     */
+    pub fn new_from_default_file() -> Result<Config, String> {
+        let mut config = Config::new_from_file(&Config::get_file())?;
+        for p in config.apply_env(&env::vars().collect::<Vec<_>>()) {
+            eprintln!("WARNING: {}", p);
+        }
+        Ok(config)
+    }
+
+    /**
+     * Override keys with the environment variables `vars` (see env_var()); returns the problems
+     * with invalid values
+     */
+    pub fn apply_env(&mut self, vars: &[(String, String)]) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (key, _) in self.to_pairs() {
+            let name = env_var(&key);
+            if let Some((_, value)) = vars.iter().find(|(n, _)| *n == name) {
+                if let Err(e) = self.set(&key, value) {
+                    problems.push(format!("${}: {}", name, e));
+                }
+            }
+        }
+        problems
+    }
+
+    /**
+     * Override a key with a "key=value" setting, like from the --set option
+     */
+    pub fn apply_setting(&mut self, setting: &str) -> Result<(), String> {
+        let (key, value) = setting
+            .split_once('=')
+            .ok_or_else(|| format!("invalid setting {}, expected KEY=VALUE", setting))?;
+        self.set(key.trim(), value.trim())
+    }
+
+    /**
+     * Configuration from the file `path`, or the defaults if it does not exist
+     */
+    pub fn new_from_file(path: &PathBuf) -> Result<Config, String> {
+        match fs::read_to_string(path) {
+            Ok(contents) => {
+                crate::info!("reading configuration {}", path.display());
+                Ok(Config::new_from_string(&contents))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                crate::debug!("no configuration file {}", path.display());
+                Ok(Config::default())
+            }
+            Err(e) => Err(format!("could not read {}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn new_from_string(contents: &str) -> Config {
+        let (config, problems) = Config::parse(contents);
+        for p in problems {
+            eprintln!("WARNING: rtimelogrc {}", p);
+        }
+        config
+    }
+
+    /**
+     * Parse the contents of a config file; returns the config and the problems with invalid lines
+     */
+    pub fn parse(contents: &str) -> (Config, Vec<String>) {
+        let mut config = Config::default();
+        let mut problems = Vec::new();
+        let mut section = String::new();
+        for (lineno, line) in contents.lines().enumerate() {
+            let result = match Config::parse_line(line) {
+                Ok(Line::KeyValue(key, value)) if section.is_empty() => config.set(&key, &value),
+                Ok(Line::KeyValue(key, value)) => {
+                    config.set(&format!("{}.{}", section, key), &value)
+                }
+                Ok(Line::Section(name)) => {
+                    section = name;
+                    Ok(())
+                }
+                Ok(Line::Empty) => Ok(()),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                problems.push(format!("line {}: {}", lineno + 1, e));
+            }
+        }
+        (config, problems)
+    }
+
+    /**
+     * All settings as (key, value), with keys in sections as "section.key"
+     */
+    pub fn to_pairs(&self) -> Vec<(String, String)> {
+        let grouping = match self.day_grouping {
+            DayGrouping::Calendar => "calendar",
+            DayGrouping::Shift => "shift",
+        };
+        let mut pairs = vec![
+            (
+                "file".to_string(),
+                self.file
+                    .as_ref()
+                    .map(|f| f.display().to_string())
+                    .unwrap_or_default(),
+            ),
+            ("name".to_string(), self.name.clone().unwrap_or_default()),
+            ("email".to_string(), self.email.clone().unwrap_or_default()),
+            ("date_format".to_string(), self.date_format.clone()),
+            ("time_format".to_string(), self.time_format.clone()),
+            ("day_grouping".to_string(), grouping.to_string()),
+            ("shift_break".to_string(), format_duration(self.shift_break)),
+            (
+                "task_separator".to_string(),
+                self.task_separator.clone().unwrap_or_default(),
+            ),
+            (
+                "hours_per_day".to_string(),
+                format_duration(self.hours_per_day),
+            ),
+            (
+                "hours_per_week".to_string(),
+                self.hours_per_week.map(format_duration).unwrap_or_default(),
+            ),
+            (
+                "flex_start".to_string(),
+                self.flex_start.map(|d| d.to_string()).unwrap_or_default(),
+            ),
+            (
+                "task_list_url".to_string(),
+                self.task_list_url.clone().unwrap_or_default(),
+            ),
+            (
+                "task_list_expiry".to_string(),
+                format_duration(self.task_list_expiry),
+            ),
+            ("read_only".to_string(), self.read_only.to_string()),
+            ("hash_chain".to_string(), self.hash_chain.to_string()),
+            ("snap_minutes".to_string(), self.snap_minutes.to_string()),
+            ("rounding".to_string(), self.rounding.to_string()),
+            (
+                "virtual_midnight".to_string(),
+                self.virtual_midnight.format("%H:%M").to_string(),
+            ),
+        ];
+        for (category, budget) in &self.budgets {
+            pairs.push((format!("budgets.{}", category), format_duration(*budget)));
+        }
+        for (category, factor) in &self.oncall {
+            pairs.push((format!("oncall.{}", category), factor.to_string()));
+        }
+        for (label, expression) in &self.footer {
+            pairs.push((format!("footer.{}", label), expression.as_str().to_string()));
+        }
+        pairs.push((
+            "leave.vacation_days".to_string(),
+            self.vacation_days.to_string(),
+        ));
+        for (name, markers) in [
+            ("vacation_markers", &self.vacation_markers),
+            ("sick_markers", &self.sick_markers),
+            ("toil_markers", &self.toil_markers),
+        ] {
+            pairs.push((format!("leave.{}", name), markers.join(", ")));
+        }
+        for (name, marker) in [
+            ("slack", &self.markers.slack),
+            ("ignore", &self.markers.ignore),
+            ("billable", &self.markers.billable),
+        ] {
+            pairs.push((format!("markers.{}", name), marker.clone()));
+        }
+        pairs.push(("schedule.day".to_string(), self.schedule_day.to_string()));
+        pairs.push((
+            "schedule.time".to_string(),
+            self.schedule_time.format("%H:%M").to_string(),
+        ));
+        pairs.push((
+            "schedule.email".to_string(),
+            self.schedule_email.clone().unwrap_or_default(),
+        ));
+        pairs.push((
+            "schedule.webhook".to_string(),
+            self.schedule_webhook.clone().unwrap_or_default(),
+        ));
+        pairs.push((
+            "mail.to".to_string(),
+            self.mail_to.clone().unwrap_or_default(),
+        ));
+        pairs.push(("mail.sendmail".to_string(), self.mail_sendmail.clone()));
+        pairs.push((
+            "mail.smtp".to_string(),
+            self.mail_smtp.clone().unwrap_or_default(),
+        ));
+        pairs.push((
+            "mail.smtp_user".to_string(),
+            self.mail_smtp_user.clone().unwrap_or_default(),
+        ));
+        pairs.push((
+            "validation.task_pattern".to_string(),
+            self.task_pattern
+                .as_ref()
+                .map(|p| p.as_str().to_string())
+                .unwrap_or_default(),
+        ));
+        pairs.push((
+            "validation.require_category".to_string(),
+            self.require_category.to_string(),
+        ));
+        pairs.push((
+            "validation.max_backdate".to_string(),
+            self.max_backdate.map(format_duration).unwrap_or_default(),
+        ));
+        pairs
+    }
+
+    /**
+     * Target work time of a week
+     */
+    pub fn week_target(&self) -> Duration {
+        self.hours_per_week.unwrap_or(self.hours_per_day * 5)
+    }
+
+    /**
+     * Where the timelog is, and how to read and write it
+     */
+    pub fn store_options(&self) -> store::Options {
+        store::Options {
+            file: self.file.clone(),
+            virtual_midnight: self.virtual_midnight,
+            markers: self.markers.clone(),
+            snap_minutes: self.snap_minutes,
+            hash_chain: self.hash_chain,
+            read_only: self.read_only,
+        }
+    }
+
+    /**
+     * Sender address from name and email, like "Joe <joe@example.com>"
+     */
+    pub fn sender(&self) -> Option<String> {
+        match (&self.name, &self.email) {
+            (Some(name), Some(email)) => Some(format!("{} <{}>", name, email)),
+            (None, Some(email)) => Some(email.clone()),
+            (_, None) => None,
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.to_pairs()
+            .into_iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v)
+    }
+
+    /**
+     * Set a configuration key from its string value
+     */
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "date_format" => self.date_format = value.to_string(),
+            "time_format" => self.time_format = value.to_string(),
+            "day_grouping" => {
+                self.day_grouping = match value {
+                    "calendar" => DayGrouping::Calendar,
+                    "shift" => DayGrouping::Shift,
+                    _ => return Err(format!("invalid day_grouping {}", value)),
+                }
+            }
+            "task_separator" => {
+                self.task_separator = Some(value.to_string()).filter(|v| !v.is_empty())
+            }
+            "markers.slack" => self.markers.slack = value.trim().to_string(),
+            "markers.ignore" => self.markers.ignore = value.trim().to_string(),
+            "markers.billable" => self.markers.billable = value.trim().to_string(),
+            "schedule.day" => {
+                self.schedule_day = value
+                    .parse()
+                    .map_err(|_| format!("invalid schedule day {}, expected a weekday", value))?
+            }
+            "schedule.time" => {
+                self.schedule_time = NaiveTime::parse_from_str(value, "%H:%M")
+                    .map_err(|_| format!("invalid schedule time {}, expected HH:MM", value))?
+            }
+            "schedule.email" => {
+                self.schedule_email = Some(value.to_string()).filter(|v| !v.is_empty())
+            }
+            "schedule.webhook" => {
+                self.schedule_webhook = Some(value.to_string()).filter(|v| !v.is_empty())
+            }
+            "mail.to" => self.mail_to = Some(value.to_string()).filter(|v| !v.is_empty()),
+            "mail.sendmail" => {
+                if value.trim().is_empty() {
+                    return Err("mail.sendmail needs a command".to_string());
+                }
+                self.mail_sendmail = value.to_string()
+            }
+            "mail.smtp" => {
+                if !(value.is_empty()
+                    || value.starts_with("smtp://")
+                    || value.starts_with("smtps://"))
+                {
+                    return Err(format!(
+                        "invalid SMTP server {}, expected smtp://HOST or smtps://HOST",
+                        value
+                    ));
+                }
+                self.mail_smtp = Some(value.to_string()).filter(|v| !v.is_empty())
+            }
+            "mail.smtp_user" => {
+                self.mail_smtp_user = Some(value.to_string()).filter(|v| !v.is_empty())
+            }
+            "validation.task_pattern" if value.is_empty() => self.task_pattern = None,
+            "validation.task_pattern" => {
+                self.task_pattern = Some(
+                    Regex::new(value)
+                        .map_err(|e| format!("invalid task_pattern {}: {}", value, e))?,
+                )
+            }
+            "validation.require_category" => {
+                self.require_category = value.parse().map_err(|_| {
+                    format!("invalid require_category {}, expected true or false", value)
+                })?
+            }
+            "validation.max_backdate" if value.is_empty() => self.max_backdate = None,
+            "validation.max_backdate" => {
+                self.max_backdate = Some(
+                    parse_duration(value)
+                        .ok_or_else(|| format!("invalid max_backdate {}", value))?,
+                )
+            }
+            "leave.vacation_days" => {
+                self.vacation_days = value
+                    .parse()
+                    .map_err(|_| format!("invalid vacation_days {}", value))?
+            }
+            "leave.vacation_markers" => self.vacation_markers = parse_list(value),
+            "leave.sick_markers" => self.sick_markers = parse_list(value),
+            "leave.toil_markers" => self.toil_markers = parse_list(value),
+            "hours_per_day" => {
+                self.hours_per_day = parse_hours(value, Duration::hours(24))
+                    .ok_or_else(|| format!("invalid hours_per_day {}", value))?
+            }
+            "hours_per_week" if value.is_empty() => self.hours_per_week = None,
+            "hours_per_week" => {
+                self.hours_per_week = Some(
+                    parse_hours(value, Duration::days(7))
+                        .ok_or_else(|| format!("invalid hours_per_week {}", value))?,
+                )
+            }
+            "flex_start" if value.is_empty() => self.flex_start = None,
+            "flex_start" => {
+                self.flex_start =
+                    Some(NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| {
+                        format!("invalid flex_start {}, expected YYYY-MM-DD", value)
+                    })?)
+            }
+            "shift_break" => {
+                self.shift_break =
+                    parse_duration(value).ok_or_else(|| format!("invalid shift_break {}", value))?
+            }
+            "file" => self.file = Some(value).filter(|v| !v.is_empty()).map(expand_home),
+            "name" => self.name = Some(value.to_string()).filter(|v| !v.is_empty()),
+            "email" => self.email = Some(value.to_string()).filter(|v| !v.is_empty()),
+            "task_list_url" => {
+                self.task_list_url = Some(value.to_string()).filter(|v| !v.is_empty())
+            }
+            "read_only" => {
+                self.read_only = value
+                    .parse()
+                    .map_err(|_| format!("invalid read_only {}, expected true or false", value))?
+            }
+            "hash_chain" => {
+                self.hash_chain = value
+                    .parse()
+                    .map_err(|_| format!("invalid hash_chain {}, expected true or false", value))?
+            }
+            "snap_minutes" => {
+                self.snap_minutes = value
+                    .parse()
+                    .ok()
+                    .filter(|m| (0..=60).contains(m))
+                    .ok_or_else(|| format!("invalid snap_minutes {}, expected 0 to 60", value))?
+            }
+            "rounding" => self.rounding = RoundingMode::parse(value)?,
+            "virtual_midnight" => {
+                self.virtual_midnight = NaiveTime::parse_from_str(value, "%H:%M")
+                    .ok()
+                    .filter(|t| *t < NaiveTime::from_hms_opt(12, 0, 0).unwrap())
+                    .ok_or_else(|| {
+                        format!(
+                            "invalid virtual_midnight {}, expected HH:MM before noon",
+                            value
+                        )
+                    })?
+            }
+            "task_list_expiry" => {
+                self.task_list_expiry = parse_duration(value)
+                    .ok_or_else(|| format!("invalid task_list_expiry {}", value))?
+            }
+            _ => {
+                if let Some(category) = key.strip_prefix("budgets.") {
+                    let budget =
+                        parse_duration(value).ok_or_else(|| format!("invalid budget {}", value))?;
+                    match self.budgets.iter_mut().find(|(c, _)| c == category) {
+                        Some(entry) => entry.1 = budget,
+                        None => self.budgets.push((category.to_string(), budget)),
+                    }
+                } else if let Some(category) = key.strip_prefix("oncall.") {
+                    let factor = value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|f| *f >= 0.0)
+                        .ok_or_else(|| format!("invalid on-call multiplier {}", value))?;
+                    match self.oncall.iter_mut().find(|(c, _)| c == category) {
+                        Some(entry) => entry.1 = factor,
+                        None => self.oncall.push((category.to_string(), factor)),
+                    }
+                } else if let Some(label) = key.strip_prefix("footer.") {
+                    let expression = Expression::parse(value)
+                        .map_err(|e| format!("invalid footer expression {}: {}", value, e))?;
+                    match self.footer.iter_mut().find(|(l, _)| l == label) {
+                        Some(entry) => entry.1 = expression,
+                        None => self.footer.push((label.to_string(), expression)),
+                    }
+                } else {
+                    return Err(format!("unknown key {}", key));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * Parse a `key = value` or `[section]` line
+     */
+    fn parse_line(line: &str) -> Result<Line, String> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(Line::Empty);
+        }
+        if let Some(section) = line.strip_prefix('[') {
+            let section = section
+                .split_once(']')
+                .map(|(s, _)| s.trim())
+                .filter(|s| !s.is_empty())
+                .ok_or_else(|| format!("invalid section: {}", line))?;
+            return Ok(Line::Section(section.to_string()));
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("expected key = value: {}", line))?;
+        let key = key.trim();
+        // quoted keys allow spaces, like "customer joe" = "20h"
+        let key = key
+            .strip_prefix('"')
+            .and_then(|k| k.strip_suffix('"'))
+            .unwrap_or(key);
+        if key.is_empty() {
+            return Err(format!("missing key: {}", line));
+        }
+        Ok(Line::KeyValue(
+            key.to_string(),
+            Config::parse_value(value.trim())?,
+        ))
+    }
+
+    fn parse_value(value: &str) -> Result<String, String> {
+        let Some(quoted) = value.strip_prefix('"') else {
+            // bare number or boolean; drop trailing comment
+            let bare = value.split('#').next().unwrap_or("").trim();
+            if bare.is_empty() {
+                return Err("missing value".to_string());
+            }
+            return Ok(bare.to_string());
+        };
+
+        let mut out = String::new();
+        let mut chars = quoted.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => {
+                    let rest = chars.as_str().trim();
+                    if !rest.is_empty() && !rest.starts_with('#') {
+                        return Err(format!("trailing garbage after string: {}", rest));
+                    }
+                    return Ok(out);
+                }
+                '\\' => match chars.next() {
+                    Some('n') => out.push('\n'),
+                    Some('t') => out.push('\t'),
+                    Some(c @ ('"' | '\\')) => out.push(c),
+                    _ => return Err(format!("invalid escape in {}", value)),
+                },
+                c => out.push(c),
+            }
+        }
+        Err(format!("unterminated string: {}", value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults() {
+        assert_eq!(Config::new_from_string(""), Config::default());
+        assert_eq!(
+            Config::new_from_file(&PathBuf::from("/nonexisting")),
+            Ok(Config::default())
+        );
+        assert!(Config::new_from_file(&env::temp_dir()).is_err());
+        assert_eq!(Config::default().date_format, "%x");
+        assert_eq!(Config::default().time_format, "%H:%M");
+    }
+
+    #[test]
+    fn test_parse() {
+        let config = Config::new_from_string(
+            r#"
+# my settings
+date_format = "%d.%m."  # German style
+time_format="%I:%M %p"
+"#,
+        );
+        assert_eq!(config.date_format, "%d.%m.");
+        assert_eq!(config.time_format, "%I:%M %p");
+        assert_eq!(config.day_grouping, DayGrouping::Calendar);
+
+        let config = Config::new_from_string(
+            r#"
+day_grouping = "shift"
+shift_break = "4h30m"
+"#,
+        );
+        assert_eq!(config.day_grouping, DayGrouping::Shift);
+        assert_eq!(config.shift_break, Duration::minutes(270));
+        assert_eq!(config.task_separator, None);
+
+        let config = Config::new_from_string(r#"task_separator = " / ""#);
+        assert_eq!(config.task_separator, Some(" / ".to_string()));
+        let config = Config::new_from_string(r#"task_separator = """#);
+        assert_eq!(config.task_separator, None);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        // invalid lines get ignored, valid ones still apply
+        let config = Config::new_from_string(
+            r#"
+nonsense
+unknown_key = 1
+day_grouping = "weekly"
+shift_break = "long"
+date_format = "unterminated
+time_format = "%H.%M"
+"#,
+        );
+        assert_eq!(config.date_format, "%x");
+        assert_eq!(config.time_format, "%H.%M");
+        assert_eq!(config.day_grouping, DayGrouping::Calendar);
+        assert_eq!(config.shift_break, Duration::hours(6));
+    }
+
+    #[test]
+    fn test_parse_sections() {
+        let config = Config::new_from_string(
+            r#"
+time_format = "%H.%M"
+
+[budgets]
+acme = "40h"
+"customer joe" = "12h30m"
+acme = "50h"
+[ other ]
+"#,
+        );
+        assert_eq!(config.time_format, "%H.%M");
+        assert_eq!(
+            config.budgets,
+            vec![
+                ("acme".to_string(), Duration::hours(50)),
+                ("customer joe".to_string(), Duration::minutes(750)),
+            ]
+        );
+
+        // keys in sections don't leak into the top level
+        let config = Config::new_from_string("[budgets]\ntime_format = \"%H\"\n");
+        assert_eq!(config.time_format, "%H:%M");
+        assert!(Config::parse_line("[]").is_err());
+        assert!(Config::parse_line("[budgets").is_err());
+    }
+
+    #[test]
+    fn test_parse_leave() {
+        let config = Config::default();
+        assert_eq!(config.vacation_days, 0);
+        assert_eq!(config.vacation_markers, vec!["*** vacation", "*** PTO"]);
+
+        let config = Config::new_from_string(
+            r#"
+[leave]
+vacation_days = 28
+vacation_markers = "*** Urlaub, *** holiday,"
+"#,
+        );
+        assert_eq!(config.vacation_days, 28);
+        assert_eq!(config.vacation_markers, vec!["*** Urlaub", "*** holiday"]);
+
+        let config = Config::new_from_string("[leave]\nvacation_days = many\n");
+        assert_eq!(config.vacation_days, 0);
+        assert_eq!(config.sick_markers, vec!["*** sick"]);
+        assert_eq!(config.toil_markers, vec!["*** TOIL"]);
+    }
+
+    #[test]
+    fn test_parse_oncall() {
+        let config = Config::new_from_string(
+            "[oncall]\nstandby = 0.25\n\"night callout\" = 1.5\nbogus = -1\nworse = x\n",
+        );
+        assert_eq!(
+            config.oncall,
+            vec![
+                ("standby".to_string(), 0.25),
+                ("night callout".to_string(), 1.5)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_markers() {
+        assert_eq!(Config::default().markers.slack, "**");
+        let config =
+            Config::new_from_string("[markers]\nslack = \"~\"\nignore = \"\"\nbillable = \"!\"\n");
+        assert_eq!(
+            config.markers,
+            Markers {
+                slack: "~".to_string(),
+                ignore: String::new(),
+                billable: "!".to_string()
+            }
+        );
+        assert_eq!(config.get("markers.billable"), Some("!".to_string()));
+    }
+
+    #[test]
+    fn test_parse_footer() {
+        let (config, problems) = Config::parse(
+            r#"
+[footer]
+Billable = "work - internal"
+"Remaining this week" = "target_week - work_week"
+Broken = "work -"
+"#,
+        );
+        assert_eq!(
+            config
+                .footer
+                .iter()
+                .map(|(l, e)| (l.as_str(), e.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("Billable", "work - internal"),
+                ("Remaining this week", "target_week - work_week")
+            ]
+        );
+        assert_eq!(
+            problems,
+            vec!["line 5: invalid footer expression work -: unexpected end".to_string()]
+        );
+        assert_eq!(
+            config.get("footer.Billable"),
+            Some("work - internal".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_snap_minutes() {
+        assert_eq!(Config::default().snap_minutes, 0);
+        assert_eq!(
+            Config::new_from_string("snap_minutes = 15").snap_minutes,
+            15
+        );
+        let (config, problems) = Config::parse("snap_minutes = 90");
+        assert_eq!(config.snap_minutes, 0);
+        assert_eq!(problems.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_rounding() {
+        assert_eq!(Config::default().rounding, RoundingMode::None);
+        assert_eq!(
+            Config::new_from_string("rounding = up:6").rounding,
+            RoundingMode::Up(6)
+        );
+        let (config, problems) = Config::parse("rounding = sometimes");
+        assert_eq!(config.rounding, RoundingMode::None);
+        assert_eq!(problems.len(), 1);
+    }
+
+    #[test]
+    fn test_overrides() {
+        assert_eq!(env_var("hours_per_day"), "RTIMELOG_HOURS_PER_DAY");
+        assert_eq!(env_var("schedule.time"), "RTIMELOG_SCHEDULE_TIME");
+
+        let mut config = Config::new_from_string("hours_per_day = 7\nname = \"Joe\"");
+        let vars = vec![
+            ("RTIMELOG_HOURS_PER_DAY".to_string(), "6h".to_string()),
+            ("RTIMELOG_SCHEDULE_DAY".to_string(), "someday".to_string()),
+            ("RTIMELOG_LOG".to_string(), "debug".to_string()),
+            ("HOME".to_string(), "/home/joe".to_string()),
+        ];
+        let problems = config.apply_env(&vars);
+        assert_eq!(config.hours_per_day, Duration::hours(6));
+        assert_eq!(config.schedule_day, Weekday::Mon);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("$RTIMELOG_SCHEDULE_DAY: "));
+
+        assert_eq!(config.apply_setting("email = joe@example.com"), Ok(()));
+        assert_eq!(config.email.as_deref(), Some("joe@example.com"));
+        assert_eq!(config.name.as_deref(), Some("Joe"));
+        assert_eq!(config.apply_setting("file=/tmp/t.txt"), Ok(()));
+        assert_eq!(config.file, Some(PathBuf::from("/tmp/t.txt")));
+        assert!(config.apply_setting("no_such_key=1").is_err());
+        assert!(config.apply_setting("hours_per_day").is_err());
+        assert!(config.apply_setting("snap_minutes=99").is_err());
+
+        assert_eq!(config.sender().as_deref(), Some("Joe <joe@example.com>"));
+        assert_eq!(Config::default().sender(), None);
+
+        let config = Config::new_from_string("file = \"~/work/timelog.txt\"");
+        assert_eq!(
+            config.file,
+            Some(dirs::home_dir().unwrap().join("work/timelog.txt"))
+        );
+    }
+
+    #[test]
+    fn test_parse_targets() {
+        let config = Config::default();
+        assert_eq!(config.week_target(), Duration::hours(40));
+        assert_eq!(config.flex_start, None);
+
+        let config = Config::new_from_string(
+            "hours_per_day = 7.5\nhours_per_week = 30\nflex_start = \"2022-01-01\"",
+        );
+        assert_eq!(config.week_target(), Duration::hours(30));
+        assert_eq!(config.flex_start, NaiveDate::from_ymd_opt(2022, 1, 1));
+        assert_eq!(
+            Config::new_from_string("hours_per_day = 6").week_target(),
+            Duration::hours(30)
+        );
+
+        let (config, problems) = Config::parse("hours_per_week = lots\nflex_start = 2022-13-01");
+        assert_eq!(config.hours_per_week, None);
+        assert_eq!(config.flex_start, None);
+        assert_eq!(problems.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_virtual_midnight() {
+        assert_eq!(Config::default().virtual_midnight, NaiveTime::MIN);
+        assert_eq!(
+            Config::new_from_string("virtual_midnight = \"04:30\"").virtual_midnight,
+            NaiveTime::from_hms_opt(4, 30, 0).unwrap()
+        );
+        for invalid in ["25:00", "13:00", "early"] {
+            let (config, problems) = Config::parse(&format!("virtual_midnight = {}", invalid));
+            assert_eq!(config.virtual_midnight, NaiveTime::MIN);
+            assert_eq!(problems.len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_parse_validation() {
+        let config = Config::default();
+        assert_eq!(config.task_pattern, None);
+        assert!(!config.require_category);
+
+        let config = Config::new_from_string(
+            "[validation]\ntask_pattern = \"^[a-z]+: \"\nrequire_category = true\nmax_backdate = \"48h\"\n",
+        );
+        assert!(config.task_pattern.unwrap().is_match("acme: code"));
+        assert!(config.require_category);
+        assert_eq!(config.max_backdate, Some(Duration::hours(48)));
+
+        let (config, problems) = Config::parse("[validation]\ntask_pattern = \"(acme\"\n");
+        assert_eq!(config.task_pattern, None);
+        assert_eq!(
+            problems,
+            vec!["line 2: invalid task_pattern (acme: missing )".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_parse_schedule() {
+        let config = Config::default();
+        assert_eq!(config.schedule_day, Weekday::Mon);
+        assert_eq!(config.schedule_email, None);
+
+        let config = Config::new_from_string(
+            r#"
+[schedule]
+day = "friday"
+time = "17:30"
+email = "me@example.com"
+webhook = "https://example.com/hook"
+"#,
+        );
+        assert_eq!(config.schedule_day, Weekday::Fri);
+        assert_eq!(
+            config.schedule_time,
+            NaiveTime::from_hms_opt(17, 30, 0).unwrap()
+        );
+        assert_eq!(config.schedule_email.as_deref(), Some("me@example.com"));
+        assert_eq!(
+            config.schedule_webhook.as_deref(),
+            Some("https://example.com/hook")
+        );
+
+        let config = Config::new_from_string(
+            "[schedule]
+day = someday
+time = 25:00
+",
+        );
+        assert_eq!(config.schedule_day, Weekday::Mon);
+        assert_eq!(
+            config.schedule_time,
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_mail() {
+        let config = Config::default();
+        assert_eq!(config.mail_to, None);
+        assert_eq!(config.mail_sendmail, "sendmail -t");
+
+        let config = Config::new_from_string(
+            r#"
+[mail]
+to = "activity@example.com"
+sendmail = "/usr/sbin/sendmail -t -oi"
+smtp = "smtps://mail.example.com"
+smtp_user = "joe"
+"#,
+        );
+        assert_eq!(config.mail_to.as_deref(), Some("activity@example.com"));
+        assert_eq!(config.mail_sendmail, "/usr/sbin/sendmail -t -oi");
+        assert_eq!(
+            config.mail_smtp.as_deref(),
+            Some("smtps://mail.example.com")
+        );
+        assert_eq!(config.mail_smtp_user.as_deref(), Some("joe"));
+        assert_eq!(
+            config.get("mail.to").as_deref(),
+            Some("activity@example.com")
+        );
+
+        let (config, problems) =
+            Config::parse("[mail]\nsmtp = mail.example.com\nsendmail = \"\"\n");
+        assert_eq!(config.mail_smtp, None);
+        assert_eq!(config.mail_sendmail, "sendmail -t");
+        assert_eq!(problems.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_hours() {
+        assert_eq!(Config::default().hours_per_day, Duration::hours(8));
+        assert_eq!(
+            Config::new_from_string("hours_per_day = 7.5").hours_per_day,
+            Duration::minutes(450)
+        );
+        assert_eq!(
+            Config::new_from_string("hours_per_day = \"6h15m\"").hours_per_day,
+            Duration::minutes(375)
+        );
+        let day = Duration::hours(24);
+        assert_eq!(parse_hours("24", day), Some(day));
+        assert_eq!(parse_hours("-1", day), None);
+        assert_eq!(parse_hours("lots", day), None);
+        assert_eq!(parse_hours("25", day), None);
+        assert_eq!(parse_hours("inf", day), None);
+        assert_eq!(parse_hours("NaN", day), None);
+        assert_eq!(parse_hours("1e300", day), None);
+        assert!(Config::default().set("hours_per_day", "1e300").is_err());
+        assert!(Config::default().set("hours_per_week", "40").is_ok());
+    }
+
+    #[test]
+    fn test_get() {
+        let config =
+            Config::new_from_string("hours_per_day = 7.5\n[budgets]\n\"customer joe\" = \"2h\"\n");
+        assert_eq!(config.get("hours_per_day"), Some("7h30m".to_string()));
+        assert_eq!(config.get("shift_break"), Some("6h".to_string()));
+        assert_eq!(config.get("budgets.customer joe"), Some("2h".to_string()));
+        assert_eq!(
+            config.get("leave.vacation_markers"),
+            Some("*** vacation, *** PTO".to_string())
+        );
+        assert_eq!(config.get("bogus"), None);
+
+        // list round-trips through set()
+        let mut copy = Config::default();
+        for (key, value) in config.to_pairs() {
+            copy.set(&key, &value).unwrap();
+        }
+        assert_eq!(copy, config);
+    }
+
+    #[test]
+    fn test_set_in_contents() {
+        assert_eq!(
+            set_in_contents("", "hours_per_day", "7.5"),
+            "hours_per_day = 7.5\n"
+        );
+        assert_eq!(
+            set_in_contents("", "budgets.customer joe", "2h"),
+            "[budgets]\n\"customer joe\" = \"2h\"\n"
+        );
+
+        let contents = "# my config
+date_format = \"%d.%m.\"
+
+[leave]
+vacation_days = 25 # per year
+
+[budgets]
+acme = \"40h\"
+";
+        assert_eq!(
+            set_in_contents(contents, "leave.vacation_days", "30"),
+            contents.replace("25 # per year", "30")
+        );
+        assert_eq!(
+            set_in_contents(contents, "time_format", "%H.%M"),
+            contents.replace(
+                "date_format = \"%d.%m.\"\n",
+                "date_format = \"%d.%m.\"\ntime_format = \"%H.%M\"\n"
+            )
+        );
+        assert_eq!(
+            set_in_contents(contents, "leave.sick_markers", "*** sick, *** ill"),
+            contents.replace(
+                "25 # per year\n",
+                "25 # per year\nsick_markers = \"*** sick, *** ill\"\n"
+            )
+        );
+        assert_eq!(
+            set_in_contents(contents, "oncall.standby", "0.25"),
+            format!("{}\n[oncall]\nstandby = 0.25\n", contents)
+        );
+        assert_eq!(
+            set_in_contents("[oncall]\n# none yet\n", "oncall.standby", "0.25"),
+            "[oncall]\nstandby = 0.25\n# none yet\n"
+        );
+        assert_eq!(
+            set_in_contents("", "task_separator", " / \"x\""),
+            "task_separator = \" / \\\"x\\\"\"\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_value() {
+        assert_eq!(
+            Config::parse_value(r#""a \"b\" \\ c""#).unwrap(),
+            r#"a "b" \ c"#
+        );
+        assert_eq!(Config::parse_value("42 # comment").unwrap(), "42");
+        assert_eq!(Config::parse_value("true").unwrap(), "true");
+        assert!(Config::parse_value("").is_err());
+        assert!(Config::parse_value(r#""abc" def"#).is_err());
+        assert!(Config::parse_value(r#""a\qb""#).is_err());
+    }
+}
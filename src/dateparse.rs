@@ -0,0 +1,308 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate chrono;
+
+use chrono::{Datelike, Duration, Months, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+
+/**
+ * Parse a date given as ISO "2022-06-10" or as a human phrase relative to `today`
+ *
+ * Supported phrases: "today", "yesterday", "tomorrow", "monday" (most recent Monday, possibly
+ * today), "last monday" (strictly before today), "next monday", "3 days ago", "2 weeks ago",
+ * "1 month ago", "beginning of [this|last|next] week|month|year", "end of ...".
+ */
+pub fn parse_date(s: &str, today: NaiveDate) -> Result<NaiveDate, String> {
+    let s = s.trim().to_lowercase();
+    if let Ok(d) = NaiveDate::parse_from_str(&s, "%Y-%m-%d") {
+        return Ok(d);
+    }
+
+    let words: Vec<&str> = s.split_whitespace().collect();
+    let invalid = || format!("cannot parse date '{}'", s);
+    let result = match words.as_slice() {
+        ["today"] => Some(today),
+        ["yesterday"] => today.pred_opt(),
+        ["tomorrow"] => today.succ_opt(),
+        [day] => parse_weekday(day).map(|wd| weekday_on_or_before(today, wd)),
+        ["last", day] => {
+            parse_weekday(day).map(|wd| weekday_on_or_before(today - Duration::days(1), wd))
+        }
+        ["next", day] => {
+            parse_weekday(day).map(|wd| weekday_on_or_before(today + Duration::days(7), wd))
+        }
+        [n, unit, "ago"] => {
+            let n: u32 = n.parse().map_err(|_| invalid())?;
+            shift(today, n, unit, false)
+        }
+        ["beginning" | "start", "of", rest @ ..] => period_bound(today, rest, true),
+        ["end", "of", rest @ ..] => period_bound(today, rest, false),
+        _ => None,
+    };
+    result.ok_or_else(invalid)
+}
+
+/**
+ * Parse a time of day "HH:MM" on `today`, or preceded by a date like "2022-06-10 14:30" or
+ * "yesterday 14:30" (see parse_date())
+ *
+ * Like in the timelog, a time without a date which is before the virtual `midnight` is on the
+ * calendar day after `today`.
+ */
+pub fn parse_datetime(
+    s: &str,
+    today: NaiveDate,
+    midnight: NaiveTime,
+) -> Result<NaiveDateTime, String> {
+    let s = s.trim();
+    let (date, time) = match s.rsplit_once(char::is_whitespace) {
+        Some((date, time)) => (Some(date), time),
+        None => (None, s),
+    };
+    let time = NaiveTime::parse_from_str(time, "%H:%M")
+        .map_err(|_| format!("cannot parse time '{}', expected [DATE] HH:MM", s))?;
+    Ok(match date {
+        Some(date) => parse_date(date, today)?.and_time(time),
+        None if time < midnight => today.and_time(time) + Duration::days(1),
+        None => today.and_time(time),
+    })
+}
+
+fn parse_weekday(s: &str) -> Option<Weekday> {
+    // this also accepts abbreviations like "mon"
+    s.parse().ok()
+}
+
+fn weekday_on_or_before(day: NaiveDate, weekday: Weekday) -> NaiveDate {
+    let diff = (7 + day.weekday().num_days_from_monday() - weekday.num_days_from_monday()) % 7;
+    day - Duration::days(diff.into())
+}
+
+/**
+ * Move `day` by n days/weeks/months/years, backwards or forwards
+ */
+fn shift(day: NaiveDate, n: u32, unit: &str, forward: bool) -> Option<NaiveDate> {
+    let unit = unit.strip_suffix('s').unwrap_or(unit);
+    let days = |d: i64| {
+        if forward {
+            day.checked_add_signed(Duration::days(d))
+        } else {
+            day.checked_sub_signed(Duration::days(d))
+        }
+    };
+    let months = |m: u32| {
+        if forward {
+            day.checked_add_months(Months::new(m))
+        } else {
+            day.checked_sub_months(Months::new(m))
+        }
+    };
+    match unit {
+        "day" => days(n.into()),
+        "week" => days(i64::from(n) * 7),
+        "month" => months(n),
+        "year" => months(n.checked_mul(12)?),
+        _ => None,
+    }
+}
+
+/**
+ * First (begin = true) or last day of "[this|last|next] week|month|year" around `today`
+ */
+fn period_bound(today: NaiveDate, words: &[&str], begin: bool) -> Option<NaiveDate> {
+    let (offset, unit) = match words {
+        [unit] | ["this", unit] => (None, *unit),
+        ["last", unit] => (Some(false), *unit),
+        ["next", unit] => (Some(true), *unit),
+        _ => return None,
+    };
+    let day = match offset {
+        Some(forward) => shift(today, 1, unit, forward)?,
+        None => today,
+    };
+
+    match unit {
+        "week" => {
+            let monday = weekday_on_or_before(day, Weekday::Mon);
+            Some(if begin {
+                monday
+            } else {
+                monday + Duration::days(6)
+            })
+        }
+        "month" => {
+            let first = day.with_day(1)?;
+            if begin {
+                Some(first)
+            } else {
+                first.checked_add_months(Months::new(1))?.pred_opt()
+            }
+        }
+        "year" => {
+            if begin {
+                NaiveDate::from_ymd_opt(day.year(), 1, 1)
+            } else {
+                NaiveDate::from_ymd_opt(day.year(), 12, 31)
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_iso() {
+        let today = date(2022, 6, 10);
+        assert_eq!(parse_date("2022-06-01", today), Ok(date(2022, 6, 1)));
+        assert_eq!(parse_date(" 2021-12-31 ", today), Ok(date(2021, 12, 31)));
+    }
+
+    #[test]
+    fn test_relative_days() {
+        // a Friday
+        let today = date(2022, 6, 10);
+        assert_eq!(parse_date("today", today), Ok(today));
+        assert_eq!(parse_date("Yesterday", today), Ok(date(2022, 6, 9)));
+        assert_eq!(parse_date("tomorrow", today), Ok(date(2022, 6, 11)));
+        assert_eq!(parse_date("3 days ago", today), Ok(date(2022, 6, 7)));
+        assert_eq!(parse_date("1 day ago", today), Ok(date(2022, 6, 9)));
+        assert_eq!(parse_date("2 weeks ago", today), Ok(date(2022, 5, 27)));
+        assert_eq!(parse_date("1 month ago", today), Ok(date(2022, 5, 10)));
+        assert_eq!(parse_date("2 years ago", today), Ok(date(2020, 6, 10)));
+    }
+
+    #[test]
+    fn test_weekdays() {
+        // a Friday
+        let today = date(2022, 6, 10);
+        assert_eq!(parse_date("monday", today), Ok(date(2022, 6, 6)));
+        assert_eq!(parse_date("friday", today), Ok(today));
+        assert_eq!(parse_date("fri", today), Ok(today));
+        assert_eq!(parse_date("last friday", today), Ok(date(2022, 6, 3)));
+        assert_eq!(parse_date("last monday", today), Ok(date(2022, 6, 6)));
+        assert_eq!(parse_date("last saturday", today), Ok(date(2022, 6, 4)));
+        assert_eq!(parse_date("next monday", today), Ok(date(2022, 6, 13)));
+        assert_eq!(parse_date("next friday", today), Ok(date(2022, 6, 17)));
+    }
+
+    #[test]
+    fn test_period_bounds() {
+        let today = date(2022, 6, 10);
+        assert_eq!(parse_date("beginning of week", today), Ok(date(2022, 6, 6)));
+        assert_eq!(parse_date("end of week", today), Ok(date(2022, 6, 12)));
+        assert_eq!(
+            parse_date("beginning of month", today),
+            Ok(date(2022, 6, 1))
+        );
+        assert_eq!(
+            parse_date("start of this month", today),
+            Ok(date(2022, 6, 1))
+        );
+        assert_eq!(parse_date("end of month", today), Ok(date(2022, 6, 30)));
+        assert_eq!(
+            parse_date("beginning of last month", today),
+            Ok(date(2022, 5, 1))
+        );
+        assert_eq!(
+            parse_date("end of last month", today),
+            Ok(date(2022, 5, 31))
+        );
+        assert_eq!(
+            parse_date("end of next month", today),
+            Ok(date(2022, 7, 31))
+        );
+        assert_eq!(
+            parse_date("beginning of last week", today),
+            Ok(date(2022, 5, 30))
+        );
+        assert_eq!(parse_date("beginning of year", today), Ok(date(2022, 1, 1)));
+        assert_eq!(
+            parse_date("end of last year", today),
+            Ok(date(2021, 12, 31))
+        );
+        // February in a leap year
+        assert_eq!(
+            parse_date("end of month", date(2024, 2, 10)),
+            Ok(date(2024, 2, 29))
+        );
+    }
+
+    #[test]
+    fn test_datetime() {
+        let today = NaiveDate::from_ymd_opt(2022, 6, 10).unwrap();
+        let at = |d: u32, h: u32, m: u32| {
+            NaiveDate::from_ymd_opt(2022, 6, d)
+                .unwrap()
+                .and_hms_opt(h, m, 0)
+                .unwrap()
+        };
+        assert_eq!(
+            parse_datetime("14:30", today, NaiveTime::MIN),
+            Ok(at(10, 14, 30))
+        );
+        assert_eq!(
+            parse_datetime(" 9:05 ", today, NaiveTime::MIN),
+            Ok(at(10, 9, 5))
+        );
+        assert_eq!(
+            parse_datetime("2022-06-08 14:30", today, NaiveTime::MIN),
+            Ok(at(8, 14, 30))
+        );
+        assert_eq!(
+            parse_datetime("yesterday 23:59", today, NaiveTime::MIN),
+            Ok(at(9, 23, 59))
+        );
+        assert_eq!(
+            parse_datetime("last monday 08:00", today, NaiveTime::MIN),
+            Ok(at(6, 8, 0))
+        );
+        assert!(parse_datetime("", today, NaiveTime::MIN).is_err());
+        assert!(parse_datetime("14:30 today", today, NaiveTime::MIN).is_err());
+        assert!(parse_datetime("someday 14:30", today, NaiveTime::MIN).is_err());
+        assert!(parse_datetime("2022-06-08", today, NaiveTime::MIN).is_err());
+
+        // the night belongs to the day before the virtual midnight
+        let midnight = NaiveTime::from_hms_opt(4, 0, 0).unwrap();
+        assert_eq!(parse_datetime("02:30", today, midnight), Ok(at(11, 2, 30)));
+        assert_eq!(parse_datetime("04:00", today, midnight), Ok(at(10, 4, 0)));
+        assert_eq!(
+            parse_datetime("yesterday 02:30", today, midnight),
+            Ok(at(9, 2, 30))
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        let today = date(2022, 6, 10);
+        assert!(parse_date("", today).is_err());
+        assert!(parse_date("someday", today).is_err());
+        assert!(parse_date("2022-13-01", today).is_err());
+        assert!(parse_date("x days ago", today).is_err());
+        assert!(parse_date("3 fortnights ago", today).is_err());
+        assert!(parse_date("beginning of decade", today).is_err());
+        assert!(parse_date("last", today).is_err());
+        assert_eq!(
+            parse_date("someday", today),
+            Err("cannot parse date 'someday'".to_string())
+        );
+    }
+}
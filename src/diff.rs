@@ -0,0 +1,149 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::fmt::Write as _; // import without risk of name clashing
+
+// above this many line pairs, don't search the longest common subsequence
+const MAX_LCS: usize = 10_000_000;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/**
+ * Edit script to turn a into b, through their longest common subsequence
+ */
+fn edit_script(a: &[&str], b: &[&str]) -> Vec<Op> {
+    if a.len().saturating_mul(b.len()) > MAX_LCS {
+        let mut ops = vec![Op::Delete; a.len()];
+        ops.extend(vec![Op::Insert; b.len()]);
+        return ops;
+    }
+
+    // lcs[i][j]: length of the LCS of a[i..] and b[j..]
+    let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut ops = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            ops.push(Op::Equal);
+            i += 1;
+            j += 1;
+        } else if j < b.len() && (i == a.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            ops.push(Op::Insert);
+            j += 1;
+        } else {
+            ops.push(Op::Delete);
+            i += 1;
+        }
+    }
+    ops
+}
+
+/**
+ * Line based diff of two texts in unified format, without context lines
+ *
+ * Returns an empty string if the texts have the same lines.
+ */
+pub fn unified(old: &str, new: &str) -> String {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+
+    // only search the differing middle part
+    let prefix = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let ops = edit_script(&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    let mut output = String::new();
+    let (mut i, mut j) = (prefix, prefix);
+    let mut k = 0;
+    while k < ops.len() {
+        if ops[k] == Op::Equal {
+            i += 1;
+            j += 1;
+            k += 1;
+            continue;
+        }
+        let hunk_len = ops[k..].iter().take_while(|op| **op != Op::Equal).count();
+        let hunk = &ops[k..k + hunk_len];
+        let deleted = hunk.iter().filter(|op| **op == Op::Delete).count();
+        let inserted = hunk_len - deleted;
+        // empty ranges refer to the line before, like in diff -u
+        writeln!(
+            output,
+            "@@ -{},{} +{},{} @@",
+            if deleted == 0 { i } else { i + 1 },
+            deleted,
+            if inserted == 0 { j } else { j + 1 },
+            inserted
+        )
+        .unwrap();
+        for line in &a[i..i + deleted] {
+            writeln!(output, "-{}", line).unwrap();
+        }
+        for line in &b[j..j + inserted] {
+            writeln!(output, "+{}", line).unwrap();
+        }
+        i += deleted;
+        j += inserted;
+        k += hunk_len;
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified() {
+        assert_eq!(unified("", ""), "");
+        assert_eq!(unified("a\nb\n", "a\nb"), "");
+        assert_eq!(unified("a\nb\n", "a\nb\nc\n"), "@@ -2,0 +3,1 @@\n+c\n");
+        assert_eq!(unified("a\nb\nc\n", "a\nc\n"), "@@ -2,1 +1,0 @@\n-b\n");
+        assert_eq!(
+            unified("a\nb\nc\nd\ne\n", "a\nB\nc\nd\nE\nf\n"),
+            "@@ -2,1 +2,1 @@\n-b\n+B\n@@ -5,1 +5,2 @@\n-e\n+E\n+f\n"
+        );
+    }
+
+    #[test]
+    fn test_edit_script() {
+        use Op::*;
+        assert_eq!(
+            edit_script(&["a", "b", "c"], &["b", "c", "d"]),
+            vec![Delete, Equal, Equal, Insert]
+        );
+        assert_eq!(edit_script(&[], &["x"]), vec![Insert]);
+    }
+}
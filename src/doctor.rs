@@ -0,0 +1,295 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate chrono;
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use chrono::{Duration, Local, NaiveDateTime};
+
+use crate::config::Config;
+use crate::store::{parse_entries, Options};
+
+// report at most this many problems of the same kind
+const MAX_DETAILS: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    Warning,
+    Problem,
+}
+
+/**
+ * Result of a check, with a hint how to fix it if necessary
+ */
+#[derive(Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub status: Status,
+    pub message: String,
+}
+
+impl Finding {
+    fn new(status: Status, message: impl Into<String>) -> Finding {
+        Finding {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self.status {
+            Status::Ok => "ok",
+            Status::Warning => "warning",
+            Status::Problem => "PROBLEM",
+        };
+        write!(f, "[{}] {}", label, self.message)
+    }
+}
+
+/**
+ * Check that the config file is valid
+ */
+pub fn check_config(path: &Path) -> Vec<Finding> {
+    let contents = match fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return vec![Finding::new(
+                Status::Ok,
+                format!("no configuration file {}, using defaults", path.display()),
+            )]
+        }
+        Err(e) => {
+            return vec![Finding::new(
+                Status::Problem,
+                format!("cannot read configuration {}: {}", path.display(), e),
+            )]
+        }
+    };
+
+    let (_, problems) = Config::parse(&contents);
+    if problems.is_empty() {
+        return vec![Finding::new(
+            Status::Ok,
+            format!("configuration {} is valid", path.display()),
+        )];
+    }
+    problems
+        .into_iter()
+        .map(|p| {
+            Finding::new(
+                Status::Warning,
+                format!(
+                    "{} {}; fix it with \"rtimelog config edit\"",
+                    path.display(),
+                    p
+                ),
+            )
+        })
+        .collect()
+}
+
+/**
+ * Check that the timelog file and its directory can be written
+ */
+pub fn check_timelog_file(path: &Path) -> Vec<Finding> {
+    let mut findings = vec![Finding::new(
+        Status::Ok,
+        format!("timelog file is {}", path.display()),
+    )];
+    match fs::metadata(path) {
+        Ok(m) if m.permissions().readonly() => findings.push(Finding::new(
+            Status::Problem,
+            format!(
+                "{} is read-only, new entries cannot be saved; fix with \"chmod u+w {}\"",
+                path.display(),
+                path.display()
+            ),
+        )),
+        Ok(_) => (),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => findings.push(Finding::new(
+            Status::Warning,
+            "timelog file does not exist yet, it gets created with the first entry",
+        )),
+        Err(e) => findings.push(Finding::new(
+            Status::Problem,
+            format!("cannot access {}: {}", path.display(), e),
+        )),
+    }
+
+    if let Some(dir) = path.parent() {
+        if let Ok(m) = fs::metadata(dir) {
+            if m.permissions().readonly() {
+                findings.push(Finding::new(
+                    Status::Problem,
+                    format!("directory {} is read-only", dir.display()),
+                ));
+            }
+        }
+    }
+    findings
+}
+
+/**
+ * Check that $TZ names a known time zone; otherwise all times are in UTC
+ */
+pub fn check_timezone() -> Finding {
+    let offset = Local::now().format("%:z");
+    match env::var("TZ") {
+        Ok(tz) if !tz.is_empty() => {
+            let name = tz.trim_start_matches(':');
+            let known = name == "UTC"
+                || Path::new(name).is_absolute() && Path::new(name).exists()
+                || Path::new("/usr/share/zoneinfo").join(name).exists();
+            if known {
+                Finding::new(Status::Ok, format!("time zone TZ={} (UTC{})", tz, offset))
+            } else {
+                Finding::new(
+                    Status::Warning,
+                    format!(
+                        "TZ={} is not a known time zone, times are in UTC{}; use a name like Europe/Berlin",
+                        tz, offset
+                    ),
+                )
+            }
+        }
+        _ => Finding::new(Status::Ok, format!("system time zone (UTC{})", offset)),
+    }
+}
+
+/**
+ * Check the timelog contents for invalid lines and implausible entries
+ *
+ * Entries get split into days at the virtual midnight of `options`.
+ */
+pub fn check_data(raw: &str, now: NaiveDateTime, options: &Options) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let (entries, errors) = parse_entries(raw);
+
+    let days = entries
+        .windows(2)
+        .filter(|w| options.day(&w[0]) != options.day(&w[1]))
+        .count()
+        + usize::from(!entries.is_empty());
+    match (entries.first(), entries.last()) {
+        (Some(first), Some(last)) => findings.push(Finding::new(
+            Status::Ok,
+            format!(
+                "{} entries on {} days from {} to {}",
+                entries.len(),
+                days,
+                options.day(first),
+                options.day(last)
+            ),
+        )),
+        _ => findings.push(Finding::new(Status::Ok, "no entries yet")),
+    }
+
+    for e in errors.iter().take(MAX_DETAILS) {
+        findings.push(Finding::new(
+            Status::Warning,
+            format!("timelog {}; these lines are ignored", e),
+        ));
+    }
+    if errors.len() > MAX_DETAILS {
+        findings.push(Finding::new(
+            Status::Warning,
+            format!("{} more invalid lines", errors.len() - MAX_DETAILS),
+        ));
+    }
+
+    let future: Vec<_> = entries.iter().filter(|e| e.stop > now).collect();
+    if let Some(first) = future.first() {
+        findings.push(Finding::new(
+            Status::Warning,
+            format!(
+                "{} entries are in the future, the first is \"{}\"; is your clock right?",
+                future.len(),
+                first
+            ),
+        ));
+    }
+
+    let long: Vec<_> = entries
+        .windows(2)
+        .filter(|w| {
+            options.day(&w[0]) == options.day(&w[1]) && w[1].stop - w[0].stop > Duration::hours(12)
+        })
+        .collect();
+    for w in long.iter().take(MAX_DETAILS) {
+        findings.push(Finding::new(
+            Status::Warning,
+            format!(
+                "\"{}\" took {} hours since the previous entry; forgot to log something?",
+                w[1],
+                (w[1].stop - w[0].stop).num_hours()
+            ),
+        ));
+    }
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_check_data() {
+        let now = NaiveDate::from_ymd_opt(2022, 6, 10)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        assert_eq!(
+            check_data("", now, &Options::default()),
+            vec![Finding::new(Status::Ok, "no entries yet")]
+        );
+
+        let findings = check_data(
+            "
+2022-06-09 06:00: arrived
+2022-06-09 19:00: code
+garbage
+2022-06-10 07:00: arrived
+2022-06-10 14:00: review
+",
+            now,
+            &Options::default(),
+        );
+        let messages: Vec<String> = findings.iter().map(|f| f.to_string()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "[ok] 4 entries on 2 days from 2022-06-09 to 2022-06-10",
+                "[warning] timelog line 4, column 1: expected \"YYYY-MM-DD HH:MM: task\"; these lines are ignored",
+                "[warning] 1 entries are in the future, the first is \"2022-06-10 14:00: review\"; is your clock right?",
+                "[warning] \"2022-06-09 19:00: code\" took 13 hours since the previous entry; forgot to log something?",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_config() {
+        let findings = check_config(Path::new("/nonexistent/rtimelogrc"));
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].status, Status::Ok);
+    }
+}
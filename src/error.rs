@@ -0,0 +1,128 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::fmt;
+
+use crate::json;
+
+/**
+ * Kind of failure, which determines the exit code
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    // anything else, like I/O errors
+    Other,
+    // invalid command line
+    Usage,
+    // invalid input like a task text or duration
+    Parse,
+    // the timelog is locked by another process
+    Lock,
+    // missing or invalid configuration
+    Config,
+    // invalid date or date range
+    InvalidRange,
+    // attempt to change the timelog in read-only mode
+    ReadOnly,
+}
+
+impl ErrorKind {
+    /**
+     * Exit code of the program; these are stable, so that scripts can rely on them
+     */
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::Other => 1,
+            ErrorKind::Usage => 2,
+            ErrorKind::Parse => 3,
+            ErrorKind::Lock => 4,
+            ErrorKind::Config => 5,
+            ErrorKind::InvalidRange => 6,
+            ErrorKind::ReadOnly => 7,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorKind::Other => "error",
+            ErrorKind::Usage => "usage",
+            ErrorKind::Parse => "parse",
+            ErrorKind::Lock => "lock",
+            ErrorKind::Config => "config",
+            ErrorKind::InvalidRange => "invalid_range",
+            ErrorKind::ReadOnly => "read_only",
+        }
+    }
+}
+
+/**
+ * Error with a kind and a human readable message
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Error {
+        Error {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    /**
+     * JSON envelope like {"error": {"kind": "usage", "code": 2, "message": "..."}}
+     */
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"error\": {{\"kind\": {}, \"code\": {}, \"message\": {}}}}}",
+            json::string(self.kind.name()),
+            self.kind.exit_code(),
+            json::string(&self.message)
+        )
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error() {
+        let e = Error::new(
+            ErrorKind::InvalidRange,
+            "--from 2022-06-10 is after --to \"x\"",
+        );
+        assert_eq!(e.to_string(), "--from 2022-06-10 is after --to \"x\"");
+        assert_eq!(e.kind.exit_code(), 6);
+        assert_eq!(
+            e.to_json(),
+            r#"{"error": {"kind": "invalid_range", "code": 6, "message": "--from 2022-06-10 is after --to \"x\""}}"#
+        );
+        assert_eq!(Error::new(ErrorKind::Other, "").kind.exit_code(), 1);
+        assert_eq!(ErrorKind::ReadOnly.exit_code(), 7);
+        assert_eq!(ErrorKind::ReadOnly.name(), "read_only");
+    }
+}
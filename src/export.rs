@@ -0,0 +1,446 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate chrono;
+
+use std::fmt::Write as _; // import without risk of name clashing
+
+use chrono::{Duration, NaiveDate, NaiveTime};
+
+use crate::activity::{category, Activities};
+use crate::config::parse_settings;
+use crate::json;
+use crate::markers::Kind;
+use crate::output::csv_field;
+use crate::store::{Entry, Options};
+
+pub mod ics;
+
+// placeholders which every mapping can use in its columns
+const BUILTIN: &[&str] = &[
+    "date",
+    "start",
+    "end",
+    "minutes",
+    "hours",
+    "task",
+    "category",
+    "description",
+];
+
+/**
+ * One exported row: the work on a task during a day, or of a single entry
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Row {
+    pub date: NaiveDate,
+    // only for rows of single entries
+    pub start: Option<NaiveTime>,
+    pub end: Option<NaiveTime>,
+    pub task: String,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Granularity {
+    // one row per task and day
+    Day,
+    // one row per entry
+    Entry,
+}
+
+/**
+ * How to export work to an external system, read from a mapping file like
+ *
+ * ```text
+ * format = "csv"
+ * rows = "day"
+ * date_format = "%d.%m.%Y"
+ * decimal_separator = ","
+ *
+ * [columns]
+ * Date = "{date}"
+ * Project = "{project}"
+ * Hours = "{hours}"
+ * Notes = "{description}"
+ *
+ * [project]
+ * acme = "P-1001"
+ * "*" = "P-0000"
+ * ```
+ *
+ * Columns are templates with placeholders: the built-in ones in BUILTIN, and any other section,
+ * which maps tasks to values.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mapping {
+    pub json: bool,
+    pub granularity: Granularity,
+    pub date_format: String,
+    pub decimal_separator: char,
+    pub columns: Vec<(String, String)>,
+    pub tables: Vec<(String, Vec<(String, String)>)>,
+}
+
+/**
+ * Names of the placeholders in a column template like "{category}-{project}"
+ */
+fn placeholders(template: &str) -> Result<Vec<&str>, String> {
+    let mut names = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("unterminated placeholder in {}", template))?;
+        names.push(&rest[start + 1..start + end]);
+        rest = &rest[start + end + 1..];
+    }
+    Ok(names)
+}
+
+/**
+ * Value of a mapping table for a task; the longest key which is the task, its category, or a
+ * prefix of it wins, "*" is the fallback
+ */
+fn lookup<'a>(table: &'a [(String, String)], task: &str) -> Option<&'a str> {
+    let cat = category(task);
+    table
+        .iter()
+        .filter(|(key, _)| task.starts_with(key.as_str()) || cat == Some(key.as_str()))
+        .max_by_key(|(key, _)| key.len())
+        .or_else(|| table.iter().find(|(key, _)| key == "*"))
+        .map(|(_, value)| value.as_str())
+}
+
+impl Mapping {
+    pub fn parse(contents: &str) -> Result<Mapping, String> {
+        let mut mapping = Mapping {
+            json: false,
+            granularity: Granularity::Day,
+            date_format: "%Y-%m-%d".to_string(),
+            decimal_separator: '.',
+            columns: Vec::new(),
+            tables: Vec::new(),
+        };
+        for (section, key, value) in parse_settings(contents)? {
+            match (section.as_str(), key.as_str()) {
+                ("", "format") => {
+                    mapping.json = match value.as_str() {
+                        "csv" => false,
+                        "json" => true,
+                        _ => return Err(format!("invalid format {}, expected csv or json", value)),
+                    }
+                }
+                ("", "rows") => {
+                    mapping.granularity = match value.as_str() {
+                        "day" => Granularity::Day,
+                        "entry" => Granularity::Entry,
+                        _ => return Err(format!("invalid rows {}, expected day or entry", value)),
+                    }
+                }
+                ("", "date_format") => mapping.date_format = value,
+                ("", "decimal_separator") => {
+                    mapping.decimal_separator = match value.as_str() {
+                        "." => '.',
+                        "," => ',',
+                        _ => return Err(format!("invalid decimal_separator {}", value)),
+                    }
+                }
+                ("", _) => return Err(format!("unknown key {}", key)),
+                ("columns", _) => mapping.columns.push((key, value)),
+                (table, _) => match mapping.tables.iter_mut().find(|(t, _)| t == table) {
+                    Some((_, entries)) => entries.push((key, value)),
+                    None => mapping.tables.push((table.to_string(), vec![(key, value)])),
+                },
+            }
+        }
+
+        if mapping.columns.is_empty() {
+            return Err("no [columns] defined".to_string());
+        }
+        for (_, template) in &mapping.columns {
+            for name in placeholders(template)? {
+                if !BUILTIN.contains(&name) && !mapping.tables.iter().any(|(t, _)| t == name) {
+                    return Err(format!("unknown placeholder {{{}}} in {}", name, template));
+                }
+            }
+        }
+        Ok(mapping)
+    }
+
+    fn value(&self, name: &str, row: &Row) -> Result<String, String> {
+        let time = |t: Option<NaiveTime>| t.map(|t| t.format("%H:%M").to_string());
+        Ok(match name {
+            "date" => row.date.format(&self.date_format).to_string(),
+            "start" => time(row.start).unwrap_or_default(),
+            "end" => time(row.end).unwrap_or_default(),
+            "minutes" => row.duration.num_minutes().to_string(),
+            "hours" => format!("{:.2}", row.duration.num_minutes() as f64 / 60.0)
+                .replace('.', &self.decimal_separator.to_string()),
+            "task" => row.task.clone(),
+            "category" => category(&row.task).unwrap_or_default().to_string(),
+            "description" => match row.task.split_once(':') {
+                Some((_, d)) if category(&row.task).is_some() => d.trim().to_string(),
+                _ => row.task.clone(),
+            },
+            table => {
+                let entries = &self.tables.iter().find(|(t, _)| t == table).unwrap().1;
+                lookup(entries, &row.task)
+                    .ok_or_else(|| format!("no {} for \"{}\"", table, row.task))?
+                    .to_string()
+            }
+        })
+    }
+
+    fn fill(&self, template: &str, row: &Row) -> Result<String, String> {
+        let mut out = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            let end = start + rest[start..].find('}').unwrap();
+            out.push_str(&rest[..start]);
+            out.push_str(&self.value(&rest[start + 1..end], row)?);
+            rest = &rest[end + 1..];
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+
+    /**
+     * Render the rows in the target format; fails with all rows whose tasks are not mapped
+     */
+    pub fn render(&self, rows: &[Row]) -> Result<String, String> {
+        let mut lines = Vec::new();
+        let mut problems: Vec<String> = Vec::new();
+        for row in rows {
+            match self
+                .columns
+                .iter()
+                .map(|(_, template)| self.fill(template, row))
+                .collect::<Result<Vec<String>, String>>()
+            {
+                Ok(values) => lines.push(values),
+                Err(e) if !problems.contains(&e) => problems.push(e),
+                Err(_) => (),
+            }
+        }
+        if !problems.is_empty() {
+            return Err(problems.join("\n"));
+        }
+
+        let mut out = String::new();
+        if self.json {
+            let objects: Vec<String> = lines
+                .iter()
+                .map(|values| {
+                    let fields: Vec<String> = self
+                        .columns
+                        .iter()
+                        .zip(values)
+                        .map(|((name, template), value)| {
+                            // a lone number placeholder stays a JSON number
+                            let number = ["{minutes}", "{hours}"].contains(&template.as_str())
+                                && self.decimal_separator == '.';
+                            let value = if number {
+                                value.clone()
+                            } else {
+                                json::string(value)
+                            };
+                            format!("{}: {}", json::string(name), value)
+                        })
+                        .collect();
+                    format!("{{{}}}", fields.join(", "))
+                })
+                .collect();
+            writeln!(out, "[{}]", objects.join(",\n ")).unwrap();
+        } else {
+            let header: Vec<String> = self.columns.iter().map(|(n, _)| csv_field(n)).collect();
+            writeln!(out, "{}", header.join(",")).unwrap();
+            for values in lines {
+                let fields: Vec<String> = values.iter().map(|v| csv_field(v)).collect();
+                writeln!(out, "{}", fields.join(",")).unwrap();
+            }
+        }
+        Ok(out)
+    }
+}
+
+/**
+ * Work rows of the entries; slack and ignored entries are left out
+ */
+pub fn rows(entries: &[Entry], granularity: Granularity, options: &Options) -> Vec<Row> {
+    let work = |a: &Activities| -> Vec<(String, Duration)> {
+        a.iter()
+            .filter(|a| options.markers.kind(a.name()) == Kind::Work)
+            .map(|a| (a.name().to_string(), a.duration()))
+            .collect()
+    };
+
+    let mut rows = Vec::new();
+    match granularity {
+        Granularity::Day => {
+            let mut rest = entries;
+            while let Some(first) = rest.first() {
+                let date = options.day(first);
+                let (day, tail) = rest.split_at(rest.partition_point(|e| options.day(e) == date));
+                for (task, duration) in work(&Activities::new_from_entries(day, options)) {
+                    rows.push(Row {
+                        date,
+                        start: None,
+                        end: None,
+                        task,
+                        duration,
+                    });
+                }
+                rest = tail;
+            }
+        }
+        Granularity::Entry => {
+            for pair in entries.windows(2) {
+                // splits percentage allocations like a report does
+                for (task, duration) in work(&Activities::new_from_entries(pair, options)) {
+                    rows.push(Row {
+                        date: options.day(&pair[1]),
+                        start: Some(pair[0].stop.time()),
+                        end: Some(pair[1].stop.time()),
+                        task,
+                        duration,
+                    });
+                }
+            }
+        }
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::parse_entries;
+
+    const ENTRIES: &str = "
+2022-06-09 08:00: arrived
+2022-06-09 09:30: acme: code
+2022-06-09 10:00: ** coffee
+2022-06-09 11:00: acme: code
+2022-06-09 12:00: 50% acme: review, 50% internal: meeting
+2022-06-10 08:00: arrived
+2022-06-10 08:45: email
+";
+
+    const MAPPING: &str = r#"
+format = "csv"
+date_format = "%d.%m.%Y"
+decimal_separator = ","
+
+[columns]
+Date = "{date}"
+Project = "{project}"
+Activity = "{activity}"
+Hours = "{hours}"
+Notes = "{description}"
+
+[project]
+acme = "P-1001"
+internal = "P-0001"
+"*" = "P-0000"
+
+[activity]
+"acme: code" = "DEV"
+acme = "CONS"
+"*" = "GEN"
+"#;
+
+    #[test]
+    fn test_rows() {
+        let entries = parse_entries(ENTRIES).0;
+        let by_day = rows(&entries, Granularity::Day, &Options::default());
+        assert_eq!(
+            by_day
+                .iter()
+                .map(|r| (
+                    r.date.to_string(),
+                    r.task.as_str(),
+                    r.duration.num_minutes()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("2022-06-09".to_string(), "acme: code", 150),
+                ("2022-06-09".to_string(), "acme: review", 30),
+                ("2022-06-09".to_string(), "internal: meeting", 30),
+                ("2022-06-10".to_string(), "email", 45),
+            ]
+        );
+
+        let by_entry = rows(&entries, Granularity::Entry, &Options::default());
+        assert_eq!(by_entry.len(), 5);
+        assert_eq!(
+            by_entry[1],
+            Row {
+                date: NaiveDate::from_ymd_opt(2022, 6, 9).unwrap(),
+                start: NaiveTime::from_hms_opt(10, 0, 0),
+                end: NaiveTime::from_hms_opt(11, 0, 0),
+                task: "acme: code".to_string(),
+                duration: Duration::hours(1),
+            }
+        );
+    }
+
+    #[test]
+    fn test_render_csv() {
+        let mapping = Mapping::parse(MAPPING).unwrap();
+        let entries = parse_entries(ENTRIES).0;
+        assert_eq!(
+            mapping.render(&rows(&entries, Granularity::Day, &Options::default())),
+            Ok("Date,Project,Activity,Hours,Notes
+09.06.2022,P-1001,DEV,\"2,50\",code
+09.06.2022,P-1001,CONS,\"0,50\",review
+09.06.2022,P-0001,GEN,\"0,50\",meeting
+10.06.2022,P-0000,GEN,\"0,75\",email
+"
+            .to_string())
+        );
+    }
+
+    #[test]
+    fn test_render_json() {
+        let mapping = Mapping::parse(
+            "format = \"json\"\nrows = \"entry\"\n[columns]\nproject = \"{code}\"\nfrom = \"{start}\"\nminutes = \"{minutes}\"\n[code]\nacme = \"A\"\n",
+        )
+        .unwrap();
+        let entries = parse_entries("2022-06-09 08:00: arrived\n2022-06-09 09:30: acme: code\n").0;
+        assert_eq!(
+            mapping.render(&rows(&entries, Granularity::Entry, &Options::default())),
+            Ok("[{\"project\": \"A\", \"from\": \"08:00\", \"minutes\": 90}]\n".to_string())
+        );
+
+        // unmapped tasks fail
+        let entries = parse_entries("2022-06-09 08:00: arrived\n2022-06-09 09:30: other\n").0;
+        assert_eq!(
+            mapping.render(&rows(&entries, Granularity::Entry, &Options::default())),
+            Err("no code for \"other\"".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(Mapping::parse("format = \"xml\"\n[columns]\na = \"{date}\"\n").is_err());
+        assert!(Mapping::parse("format = \"csv\"\n").is_err());
+        assert_eq!(
+            Mapping::parse("[columns]\na = \"{project}\"\n"),
+            Err("unknown placeholder {project} in {project}".to_string())
+        );
+        assert!(Mapping::parse("[columns]\na = \"{date\"\n").is_err());
+    }
+}
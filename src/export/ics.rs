@@ -0,0 +1,204 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate chrono;
+
+use chrono::NaiveDateTime;
+
+use crate::activity::category;
+use crate::markers::Kind;
+use crate::store::{Entry, Options};
+
+const ICS_TIME: &str = "%Y%m%dT%H%M%S";
+
+/**
+ * What happens with slack entries
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slack {
+    // like work entries
+    Keep,
+    // with the "slack" category, and free instead of busy
+    Tag,
+    Skip,
+}
+
+impl Slack {
+    pub fn from_name(name: &str) -> Result<Slack, String> {
+        match name {
+            "keep" => Ok(Slack::Keep),
+            "tag" => Ok(Slack::Tag),
+            "skip" => Ok(Slack::Skip),
+            _ => Err(format!(
+                "invalid slack mode {}, expected keep, tag, or skip",
+                name
+            )),
+        }
+    }
+}
+
+/**
+ * Calendar with an event for each entry, from the previous entry's stop to its own
+ *
+ * The first entry of each day only marks the start, and ignored entries are left out. Times are
+ * "floating" local times, like in the timelog. `now` (in UTC) is the time stamp of the events.
+ */
+pub fn calendar(entries: &[Entry], slack: Slack, now: NaiveDateTime, options: &Options) -> String {
+    let mut out = String::new();
+    line(&mut out, "BEGIN:VCALENDAR");
+    line(&mut out, "VERSION:2.0");
+    line(&mut out, "PRODID:-//rtimelog//rtimelog//EN");
+    line(&mut out, "CALSCALE:GREGORIAN");
+    for pair in entries.windows(2) {
+        let (start, entry) = (&pair[0], &pair[1]);
+        if options.day(start) != options.day(entry) || start.stop >= entry.stop {
+            continue;
+        }
+        let kind = options.markers.kind(&entry.task);
+        let tagged = match (kind, slack) {
+            (Kind::Ignored, _) | (Kind::Slack, Slack::Skip) => continue,
+            (Kind::Slack, Slack::Tag) => true,
+            _ => false,
+        };
+        line(&mut out, "BEGIN:VEVENT");
+        line(
+            &mut out,
+            &format!(
+                "UID:{}-{}@rtimelog",
+                start.stop.format(ICS_TIME),
+                entry.stop.format(ICS_TIME)
+            ),
+        );
+        line(&mut out, &format!("DTSTAMP:{}Z", now.format(ICS_TIME)));
+        line(
+            &mut out,
+            &format!("DTSTART:{}", start.stop.format(ICS_TIME)),
+        );
+        line(&mut out, &format!("DTEND:{}", entry.stop.format(ICS_TIME)));
+        line(&mut out, &format!("SUMMARY:{}", escape(&entry.task)));
+        if tagged {
+            line(&mut out, "CATEGORIES:slack");
+            line(&mut out, "TRANSP:TRANSPARENT");
+        } else if let Some(cat) = category(&entry.task) {
+            line(&mut out, &format!("CATEGORIES:{}", escape(cat)));
+        }
+        line(&mut out, "END:VEVENT");
+    }
+    line(&mut out, "END:VCALENDAR");
+    out
+}
+
+/**
+ * Escape a TEXT value
+ */
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/**
+ * Append a content line, folded after 75 octets, with CRLF
+ */
+fn line(out: &mut String, content: &str) {
+    let mut width = 0;
+    for c in content.chars() {
+        // don't split UTF-8 sequences
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            // the space counts as well
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::parse_entries;
+
+    fn now() -> NaiveDateTime {
+        "2022-06-11T09:00:00".parse().unwrap()
+    }
+
+    const ENTRIES: &str = "
+2022-06-10 08:00: arrived
+2022-06-10 09:30: acme: code, tests
+2022-06-10 10:00: ** coffee
+2022-06-10 10:00: *** zero length
+2022-06-10 11:00: *** ignored
+2022-06-11 07:00: arrived
+2022-06-11 08:00: email
+";
+
+    #[test]
+    fn test_calendar() {
+        let (entries, _) = parse_entries(ENTRIES);
+        let ics = calendar(&entries, Slack::Tag, now(), &Options::default());
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.ends_with("END:VEVENT\r\nEND:VCALENDAR\r\n"));
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 3);
+        assert!(ics.contains(
+            "BEGIN:VEVENT\r
+UID:20220610T080000-20220610T093000@rtimelog\r
+DTSTAMP:20220611T090000Z\r
+DTSTART:20220610T080000\r
+DTEND:20220610T093000\r
+SUMMARY:acme: code\\, tests\r
+CATEGORIES:acme\r
+END:VEVENT\r\n"
+        ));
+        assert!(ics.contains("SUMMARY:** coffee\r\nCATEGORIES:slack\r\nTRANSP:TRANSPARENT\r\n"));
+        assert!(ics.contains(
+            "DTSTART:20220611T070000\r\nDTEND:20220611T080000\r\nSUMMARY:email\r\nEND:VEVENT"
+        ));
+
+        let ics = calendar(&entries, Slack::Skip, now(), &Options::default());
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 2);
+        assert!(!ics.contains("coffee"));
+        let ics = calendar(&entries, Slack::Keep, now(), &Options::default());
+        assert!(ics.contains("SUMMARY:** coffee\r\nEND:VEVENT"));
+
+        assert_eq!(
+            calendar(&[], Slack::Tag, now(), &Options::default())
+                .matches("VCALENDAR")
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_slack_from_name() {
+        assert_eq!(Slack::from_name("skip"), Ok(Slack::Skip));
+        assert!(Slack::from_name("drop").is_err());
+    }
+
+    #[test]
+    fn test_line() {
+        let mut out = String::new();
+        line(&mut out, &"x".repeat(80));
+        assert_eq!(out, format!("{}\r\n {}\r\n", "x".repeat(75), "x".repeat(5)));
+
+        // multi-byte characters stay in one piece
+        let mut out = String::new();
+        line(&mut out, &"ü".repeat(40));
+        assert_eq!(out, format!("{}\r\n {}\r\n", "ü".repeat(37), "ü".repeat(3)));
+        assert_eq!(escape("a;b\\c\nd"), "a\\;b\\\\c\\nd");
+    }
+}
@@ -0,0 +1,263 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate chrono;
+
+use chrono::Duration;
+
+use crate::quickentry::parse_duration;
+
+/**
+ * Arithmetic expression for a report footer line, like "work - internal" or "target_week - 2h"
+ *
+ * Operands are variables, durations like "2h" or "1h30m", and plain numbers, combined with
+ * `+ - * /` and parentheses. The result must be a duration.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    source: String,
+    tree: Node,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Duration(Duration),
+    Number(f64),
+    Variable(String),
+    Negate(Box<Node>),
+    Binary(char, Box<Node>, Box<Node>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Value {
+    // minutes
+    Duration(f64),
+    Number(f64),
+}
+
+fn tokenize(source: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if "+-*/()".contains(c) {
+            tokens.push(c.to_string());
+            chars.next();
+        } else if c.is_alphanumeric() || c == '_' || c == '.' {
+            let mut token = String::new();
+            while let Some(&c) = chars
+                .peek()
+                .filter(|c| c.is_alphanumeric() || **c == '_' || **c == '.')
+            {
+                token.push(c);
+                chars.next();
+            }
+            tokens.push(token);
+        } else {
+            return Err(format!("unexpected character {}", c));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<String>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(String::as_str)
+    }
+
+    fn next(&mut self) -> Option<String> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    // sum := product (("+" | "-") product)*
+    fn sum(&mut self) -> Result<Node, String> {
+        let mut node = self.product()?;
+        while let Some(op @ ("+" | "-")) = self.peek() {
+            let op = op.chars().next().unwrap();
+            self.pos += 1;
+            node = Node::Binary(op, Box::new(node), Box::new(self.product()?));
+        }
+        Ok(node)
+    }
+
+    // product := unary (("*" | "/") unary)*
+    fn product(&mut self) -> Result<Node, String> {
+        let mut node = self.unary()?;
+        while let Some(op @ ("*" | "/")) = self.peek() {
+            let op = op.chars().next().unwrap();
+            self.pos += 1;
+            node = Node::Binary(op, Box::new(node), Box::new(self.unary()?));
+        }
+        Ok(node)
+    }
+
+    fn unary(&mut self) -> Result<Node, String> {
+        let token = self.next().ok_or("unexpected end")?;
+        match token.as_str() {
+            "-" => Ok(Node::Negate(Box::new(self.unary()?))),
+            "(" => {
+                let node = self.sum()?;
+                match self.next().as_deref() {
+                    Some(")") => Ok(node),
+                    _ => Err("missing )".to_string()),
+                }
+            }
+            t if t.starts_with(|c: char| c.is_ascii_digit() || c == '.') => {
+                if let Ok(n) = t.parse() {
+                    Ok(Node::Number(n))
+                } else {
+                    parse_duration(t)
+                        .map(Node::Duration)
+                        .ok_or_else(|| format!("invalid number {}", t))
+                }
+            }
+            t if t.chars().all(|c| c.is_alphanumeric() || c == '_') => {
+                Ok(Node::Variable(t.to_string()))
+            }
+            t => Err(format!("unexpected {}", t)),
+        }
+    }
+}
+
+fn eval(node: &Node, vars: &dyn Fn(&str) -> Option<Duration>) -> Result<Value, String> {
+    Ok(match node {
+        Node::Duration(d) => Value::Duration(d.num_minutes() as f64),
+        Node::Number(n) => Value::Number(*n),
+        Node::Variable(name) => Value::Duration(
+            vars(name)
+                .ok_or_else(|| format!("unknown variable {}", name))?
+                .num_minutes() as f64,
+        ),
+        Node::Negate(node) => match eval(node, vars)? {
+            Value::Duration(d) => Value::Duration(-d),
+            Value::Number(n) => Value::Number(-n),
+        },
+        Node::Binary(op, left, right) => {
+            use Value::*;
+            match (op, eval(left, vars)?, eval(right, vars)?) {
+                ('+', Duration(a), Duration(b)) => Duration(a + b),
+                ('-', Duration(a), Duration(b)) => Duration(a - b),
+                ('+', Number(a), Number(b)) => Number(a + b),
+                ('-', Number(a), Number(b)) => Number(a - b),
+                ('*', Duration(a), Number(b)) | ('*', Number(b), Duration(a)) => Duration(a * b),
+                ('*', Number(a), Number(b)) => Number(a * b),
+                ('/', _, Number(b)) | ('/', _, Duration(b)) if b == 0.0 => {
+                    return Err("division by zero".to_string())
+                }
+                ('/', Duration(a), Number(b)) => Duration(a / b),
+                ('/', Duration(a), Duration(b)) => Number(a / b),
+                ('/', Number(a), Number(b)) => Number(a / b),
+                (op, _, _) => {
+                    return Err(format!("cannot apply {} to a number and a duration", op))
+                }
+            }
+        }
+    })
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Expression, String> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            pos: 0,
+        };
+        let tree = parser.sum()?;
+        if let Some(t) = parser.peek() {
+            return Err(format!("unexpected {}", t));
+        }
+        Ok(Expression {
+            source: source.to_string(),
+            tree,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /**
+     * Compute the duration, looking up variables with `vars`
+     */
+    pub fn eval(&self, vars: &dyn Fn(&str) -> Option<Duration>) -> Result<Duration, String> {
+        match eval(&self.tree, vars)? {
+            Value::Duration(minutes) => Ok(Duration::minutes(minutes.round() as i64)),
+            Value::Number(_) => Err(format!("{} is a number, not a duration", self.source)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(name: &str) -> Option<Duration> {
+        match name {
+            "work" => Some(Duration::minutes(600)),
+            "internal" => Some(Duration::minutes(90)),
+            "target_week" => Some(Duration::hours(40)),
+            _ => None,
+        }
+    }
+
+    fn eval(source: &str) -> Result<Duration, String> {
+        Expression::parse(source).unwrap().eval(&vars)
+    }
+
+    #[test]
+    fn test_eval() {
+        assert_eq!(eval("work"), Ok(Duration::minutes(600)));
+        assert_eq!(eval("work - internal"), Ok(Duration::minutes(510)));
+        assert_eq!(eval("target_week - work"), Ok(Duration::minutes(1800)));
+        assert_eq!(eval("work - 1h30m"), Ok(Duration::minutes(510)));
+        assert_eq!(eval("work * 0.9"), Ok(Duration::minutes(540)));
+        assert_eq!(
+            eval("2 * (work - internal) / 3"),
+            Ok(Duration::minutes(340))
+        );
+        assert_eq!(eval("-internal + 2h"), Ok(Duration::minutes(30)));
+        assert_eq!(eval("work - internal - 30m"), Ok(Duration::minutes(480)));
+
+        assert_eq!(eval("nope"), Err("unknown variable nope".to_string()));
+        assert_eq!(
+            eval("work / internal"),
+            Err("work / internal is a number, not a duration".to_string())
+        );
+        assert!(eval("work / 0").is_err());
+        assert!(eval("2 - work").is_err());
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for source in [
+            "",
+            "work -",
+            "(work",
+            "work)",
+            "work internal",
+            "3x",
+            "work % 2",
+        ] {
+            assert!(Expression::parse(source).is_err(), "{}", source);
+        }
+    }
+}
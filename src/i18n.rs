@@ -0,0 +1,195 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::env;
+use std::sync::OnceLock;
+
+/**
+ * Message catalog: gettext style mapping of English msgid to translation
+ */
+pub struct Catalog {
+    pub language: &'static str,
+    messages: &'static [(&'static str, &'static str)],
+}
+
+pub const DE: Catalog = Catalog {
+    language: "de",
+    messages: &[
+        ("Total work done", "Gesamte Arbeitszeit"),
+        ("Total slacking", "Gesamte Pausenzeit"),
+        (
+            "Total with on-call multipliers",
+            "Gesamt mit Bereitschaftsfaktoren",
+        ),
+        ("Total billable", "Abrechenbar gesamt"),
+        ("Skipped", "Übersprungen"),
+        ("Not added", "Nicht hinzugefügt"),
+        ("Grand total", "Gesamtsumme"),
+        ("Time left at work", "Verbleibende Arbeitszeit"),
+        (
+            "Time left this week",
+            "Verbleibende Arbeitszeit diese Woche",
+        ),
+        ("Overtime today", "Überstunden heute"),
+        ("Overtime this week", "Überstunden diese Woche"),
+        ("Flex balance", "Gleitzeitsaldo"),
+        ("Total of matching tasks", "Summe der passenden Aufgaben"),
+        ("Matched days", "Passende Tage"),
+        ("Work done today", "Heute erledigt"),
+        ("Work done this week", "Diese Woche erledigt"),
+        ("Work done this shift", "In dieser Schicht erledigt"),
+        ("Work done on", "Erledigt am"),
+        ("Work done from", "Erledigt vom"),
+        ("%A, %x (week %U)", "%A, %x (KW %U)"),
+        ("%Y, week %U", "%Y, KW %U"),
+        ("no entries yet today", "heute noch keine Einträge"),
+        ("since last entry", "seit dem letzten Eintrag"),
+        (
+            "type command (:h for help) or entry",
+            "Befehl (:h für Hilfe) oder Eintrag eingeben",
+        ),
+        (
+            "No existing file, starting new log",
+            "Keine Datei vorhanden, beginne neues Protokoll",
+        ),
+        ("WARNING: timelog", "WARNUNG: Zeitprotokoll"),
+        (
+            "WARNING: cannot read archive",
+            "WARNUNG: Kann Archiv nicht lesen",
+        ),
+        ("Dry run: no changes", "Probelauf: keine Änderungen"),
+        ("dry run", "Probelauf"),
+        (
+            "Read-only mode, not adding entries",
+            "Nur-Lese-Modus, füge keine Einträge hinzu",
+        ),
+        (
+            "Read-only mode, not editing the timelog",
+            "Nur-Lese-Modus, bearbeite das Zeitprotokoll nicht",
+        ),
+        (
+            "WARNING: more invalid lines in timelog",
+            "WARNUNG: weitere ungültige Zeilen im Zeitprotokoll",
+        ),
+        (
+            "WARNING: replacing invalid UTF-8 in timelog",
+            "WARNUNG: ersetze ungültiges UTF-8 im Zeitprotokoll",
+        ),
+        ("invalid date or time", "ungültiges Datum oder Uhrzeit"),
+        ("missing task", "Aufgabe fehlt"),
+        ("goes back in time", "geht in der Zeit zurück"),
+        ("NUL character", "NUL-Zeichen"),
+        (
+            "
+:w - switch to weekly mode
+:d - switch to daily mode
+:c - switch to chronological list of today's entries
+:q - quit
+:h - show this help
+:e - open timelog.txt in $EDITOR
+^r - history search (like in bash) through currently shown activities
+
+Any other input is the description of a task that you just finished.",
+            "
+:w - zur Wochenansicht wechseln
+:d - zur Tagesansicht wechseln
+:c - zur chronologischen Liste der heutigen Einträge wechseln
+:q - beenden
+:h - diese Hilfe anzeigen
+:e - timelog.txt in $EDITOR öffnen
+^r - Verlaufssuche (wie in bash) in den angezeigten Tätigkeiten
+
+Jede andere Eingabe ist die Beschreibung einer gerade beendeten Aufgabe.",
+        ),
+    ],
+};
+
+const CATALOGS: [&Catalog; 1] = [&DE];
+
+static ACTIVE: OnceLock<Option<&'static Catalog>> = OnceLock::new();
+
+impl Catalog {
+    /**
+     * Catalog for a POSIX locale name like "de_DE.UTF-8"; None for untranslated (English)
+     */
+    pub fn for_locale(name: &str) -> Option<&'static Catalog> {
+        let lang = name.split(['_', '.', '@']).next().unwrap_or("");
+        CATALOGS.iter().find(|c| c.language == lang).copied()
+    }
+
+    pub fn lookup(&self, msgid: &'static str) -> &'static str {
+        self.messages
+            .iter()
+            .find(|(id, _)| *id == msgid)
+            .map(|(_, msg)| *msg)
+            .unwrap_or(msgid)
+    }
+}
+
+/**
+ * Select the message language from LC_ALL > LC_MESSAGES > LANG
+ *
+ * This must be called once at program start; without it, all messages stay English.
+ */
+pub fn init_from_env() {
+    let name = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|v| env::var(v).ok())
+        .find(|v| !v.is_empty())
+        .unwrap_or_default();
+    init(&name);
+}
+
+pub fn init(locale: &str) {
+    // first initialization wins
+    let _ = ACTIVE.set(Catalog::for_locale(locale));
+}
+
+/**
+ * Translate a message into the active language
+ */
+pub fn tr(msgid: &'static str) -> &'static str {
+    match ACTIVE.get() {
+        Some(Some(catalog)) => catalog.lookup(msgid),
+        _ => msgid,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_for_locale() {
+        assert_eq!(Catalog::for_locale("de_DE.UTF-8").unwrap().language, "de");
+        assert!(Catalog::for_locale("en_US.UTF-8").is_none());
+        assert!(Catalog::for_locale("C").is_none());
+        assert!(Catalog::for_locale("").is_none());
+    }
+
+    #[test]
+    fn test_lookup() {
+        assert_eq!(DE.lookup("Total work done"), "Gesamte Arbeitszeit");
+        assert_eq!(DE.lookup("%A, %x (week %U)"), "%A, %x (KW %U)");
+        // untranslated messages fall back to the msgid
+        assert_eq!(DE.lookup("no such message"), "no such message");
+    }
+
+    #[test]
+    fn test_tr_uninitialized() {
+        // tests never call init(), so output stays English
+        assert_eq!(tr("Total work done"), "Total work done");
+    }
+}
@@ -0,0 +1,390 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate chrono;
+
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+
+use crate::locale::Locale;
+use crate::quickentry::parse_duration;
+use crate::store::Timelog;
+
+/**
+ * Time interval of a task, from a row of an imported file
+ */
+#[derive(Debug, PartialEq, Eq)]
+pub struct Row {
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub task: String,
+}
+
+// accepted column names, in English, German, and French
+const DATE: &[&str] = &["date", "day", "datum", "tag", "jour"];
+const START: &[&str] = &["start", "begin", "from", "beginn", "von", "début", "debut"];
+const END: &[&str] = &["end", "stop", "to", "ende", "bis", "fin"];
+const DURATION: &[&str] = &["duration", "hours", "dauer", "stunden", "durée", "heures"];
+const TASK: &[&str] = &[
+    "task",
+    "description",
+    "activity",
+    "aufgabe",
+    "beschreibung",
+    "tätigkeit",
+    "tâche",
+    "activité",
+];
+
+/**
+ * Split a CSV line into fields; fields can be quoted with "", with "" for a literal quote
+ */
+fn split_line(line: &str, delimiter: char) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            c if c == delimiter && !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+/**
+ * Exports with decimal commas usually separate fields with semicolons
+ */
+fn detect_delimiter(header: &str) -> char {
+    [',', ';', '\t']
+        .into_iter()
+        .max_by_key(|d| header.matches(*d).count())
+        .unwrap()
+}
+
+fn parse_time(s: &str) -> Option<NaiveTime> {
+    ["%H:%M", "%H:%M:%S", "%H.%M", "%I:%M %p"]
+        .iter()
+        .find_map(|fmt| NaiveTime::parse_from_str(s.trim(), fmt).ok())
+}
+
+/**
+ * Parse a time of day, or a date and time separated by a space
+ */
+fn parse_datetime(s: &str, date: Option<NaiveDate>, locale: &Locale) -> Option<NaiveDateTime> {
+    let s = s.trim();
+    if let Some(time) = parse_time(s) {
+        return Some(date?.and_time(time));
+    }
+    // the time can have two words, like "9:00 AM"
+    let words: Vec<&str> = s.split_whitespace().collect();
+    (1..=2.min(words.len().saturating_sub(1))).find_map(|n| {
+        let (d, t) = words.split_at(words.len() - n);
+        Some(
+            locale
+                .parse_date(&d.join(" "))?
+                .and_time(parse_time(&t.join(" "))?),
+        )
+    })
+}
+
+/**
+ * Parse a duration like "1,5" (hours, with the locale's decimal separator), "1:30", or "90min"
+ */
+fn parse_localized_duration(s: &str, locale: &Locale) -> Option<Duration> {
+    let s = s.trim();
+    if let Some(hours) = locale.parse_number(s) {
+        return Some(Duration::seconds((hours * 3600.0).round() as i64))
+            .filter(|d| *d > Duration::zero());
+    }
+    if let Some((h, m)) = s.split_once(':') {
+        let (h, m): (i64, i64) = (h.parse().ok()?, m.parse().ok()?);
+        return Some(Duration::minutes(h * 60 + m)).filter(|d| *d > Duration::zero());
+    }
+    parse_duration(&s.replace(',', "."))
+}
+
+/**
+ * Parse a CSV file with a header line
+ *
+ * The columns are found by their names: a task, a start, and an end or duration. The start can
+ * be a time with a separate date column, or a date and time. Dates, times, and durations are
+ * parsed according to `locale`.
+ */
+pub fn parse_csv(contents: &str, locale: &Locale) -> Result<Vec<Row>, String> {
+    let mut lines = contents
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty());
+    let (_, header) = lines.next().ok_or("empty file")?;
+    let header = header.trim_start_matches('\u{feff}');
+    let delimiter = detect_delimiter(header);
+    let names: Vec<String> = split_line(header, delimiter)
+        .iter()
+        .map(|n| n.trim().to_lowercase())
+        .collect();
+    let column = |candidates: &[&str]| names.iter().position(|n| candidates.contains(&n.as_str()));
+    let date_col = column(DATE);
+    let start_col = column(START).ok_or("no start column")?;
+    let end_col = column(END);
+    let duration_col = column(DURATION);
+    let task_col = column(TASK).ok_or("no task column")?;
+    if end_col.is_none() && duration_col.is_none() {
+        return Err("no end or duration column".to_string());
+    }
+
+    let mut rows = Vec::new();
+    for (i, line) in lines {
+        let err = |what: &str, value: &str| format!("line {}: invalid {} {:?}", i + 1, what, value);
+        let fields = split_line(line, delimiter);
+        let field = |col: usize| fields.get(col).map_or("", |f| f.trim());
+
+        let date = match date_col {
+            Some(col) => Some(
+                locale
+                    .parse_date(field(col))
+                    .ok_or_else(|| err("date", field(col)))?,
+            ),
+            None => None,
+        };
+        let start = parse_datetime(field(start_col), date, locale)
+            .ok_or_else(|| err("start", field(start_col)))?;
+        let end = match (end_col, duration_col) {
+            (Some(col), _) if !field(col).is_empty() => {
+                let mut end = parse_datetime(field(col), Some(start.date()), locale)
+                    .ok_or_else(|| err("end", field(col)))?;
+                // like 22:00 to 01:00
+                if end < start && field(col).len() <= 8 {
+                    end += Duration::days(1);
+                }
+                end
+            }
+            (_, Some(col)) => {
+                start
+                    + parse_localized_duration(field(col), locale)
+                        .ok_or_else(|| err("duration", field(col)))?
+            }
+            (Some(col), None) => return Err(err("end", field(col))),
+            (None, None) => unreachable!(),
+        };
+        let task = field(task_col);
+        if task.is_empty() {
+            return Err(format!("line {}: missing task", i + 1));
+        }
+        if end <= start {
+            return Err(format!("line {}: end is not after the start", i + 1));
+        }
+        rows.push(Row {
+            start,
+            end,
+            task: task.to_string(),
+        });
+    }
+    rows.sort_by_key(|r| r.start);
+    Ok(rows)
+}
+
+/**
+ * Add rows to the timelog where their time is not logged yet
+ *
+ * Each row becomes an entry at its end, after an "arrived" entry at its start for the first entry
+ * of a day, or an unnamed slack entry for a gap. Rows may fill unnamed slack entries, but must not
+ * overlap other entries. Returns the number of added rows, and why the others were skipped.
+ */
+pub fn apply(timelog: &mut Timelog, rows: &[Row]) -> (usize, Vec<String>) {
+    let placeholder = timelog.options().markers.placeholder().to_string();
+    let mut added = 0;
+    let mut skipped = Vec::new();
+    for row in rows {
+        let describe = || {
+            format!(
+                "{} - {} {}",
+                row.start.format("%Y-%m-%d %H:%M"),
+                row.end.format("%H:%M"),
+                row.task
+            )
+        };
+        if row.start.date() != row.end.date() {
+            skipped.push(format!("{}: crosses midnight", describe()));
+            continue;
+        }
+        let day = timelog.get_day(&row.start.date());
+        if day.iter().any(|e| e.stop == row.end && *e.task == row.task) {
+            skipped.push(format!("{}: already logged", describe()));
+            continue;
+        }
+        let prev = day.iter().rev().find(|e| e.stop <= row.start).cloned();
+        let next = day.iter().find(|e| e.stop > row.start).cloned();
+        let fills_gap = next
+            .as_ref()
+            .is_some_and(|n| prev.is_some() && *n.task == placeholder && n.stop >= row.end);
+        if next.is_some() && !fills_gap {
+            skipped.push(format!("{}: overlaps logged time", describe()));
+            continue;
+        }
+
+        match prev {
+            None => timelog.insert_at(row.start, "arrived".to_string()),
+            Some(p) if p.stop < row.start => timelog.insert_at(row.start, placeholder.clone()),
+            Some(_) => (),
+        }
+        // a gap which ends at the same time is completely filled
+        if let Some(n) = next.filter(|n| n.stop == row.end) {
+            timelog.remove_entry(&n);
+        }
+        timelog.insert_at(row.end, row.task.clone());
+        added += 1;
+    }
+    (added, skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::locale::{DE, EN, FR};
+
+    fn at(d: u32, h: u32, m: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2022, 6, d)
+            .unwrap()
+            .and_hms_opt(h, m, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_split_line() {
+        assert_eq!(split_line("a,b,,c", ','), vec!["a", "b", "", "c"]);
+        assert_eq!(
+            split_line("\"a;b\";\"say \"\"hi\"\"\";1,5", ';'),
+            vec!["a;b", "say \"hi\"", "1,5"]
+        );
+        assert_eq!(detect_delimiter("Datum;Beginn;Dauer;Tätigkeit"), ';');
+        assert_eq!(detect_delimiter("date,start,end,task"), ',');
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let rows = parse_csv(
+            "\u{feff}Datum;Beginn;Dauer;Tätigkeit
+Freitag, 10. Juni 2022;09:00;1,5;acme: code
+
+10.06.2022;14.00;0:45;\"meeting; planning\"
+",
+            &DE,
+        )
+        .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                Row {
+                    start: at(10, 9, 0),
+                    end: at(10, 10, 30),
+                    task: "acme: code".to_string()
+                },
+                Row {
+                    start: at(10, 14, 0),
+                    end: at(10, 14, 45),
+                    task: "meeting; planning".to_string()
+                },
+            ]
+        );
+
+        let rows = parse_csv(
+            "Début,Fin,Tâche\nven. 10 juin 2022 08:00,ven. 10 juin 2022 08:20,email\n",
+            &FR,
+        )
+        .unwrap();
+        assert_eq!(rows[0].start, at(10, 8, 0));
+        assert_eq!(rows[0].end, at(10, 8, 20));
+
+        let rows = parse_csv(
+            "Task,Date,Start,End\nsupport,\"June 9, 2022\",9:30 PM,11:00 PM\n",
+            &EN,
+        )
+        .unwrap();
+        assert_eq!(rows[0].start, at(9, 21, 30));
+        assert_eq!(rows[0].end, at(9, 23, 0));
+
+        // English doesn't know decimal commas and German dates
+        assert_eq!(
+            parse_csv("date,start,hours,task\n2022-06-10,09:00,\"1,5\",x\n", &EN),
+            Err("line 2: invalid duration \"1,5\"".to_string())
+        );
+        assert_eq!(
+            parse_csv("date;start;hours;task\n10. Juni 2022;09:00;1;x\n", &EN),
+            Err("line 2: invalid date \"10. Juni 2022\"".to_string())
+        );
+        assert_eq!(
+            parse_csv("date,start,task\n", &EN),
+            Err("no end or duration column".to_string())
+        );
+        assert!(parse_csv("", &EN).is_err());
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut tl = Timelog::new_from_string(
+            "
+2022-06-10 07:00: arrived
+2022-06-10 08:00: code
+2022-06-10 12:00: **
+2022-06-10 13:00: review
+",
+        );
+        let row = |start, end, task: &str| Row {
+            start,
+            end,
+            task: task.to_string(),
+        };
+        let rows = vec![
+            row(at(9, 9, 0), at(9, 10, 30), "new day"),
+            row(at(10, 7, 30), at(10, 8, 30), "overlap"),
+            row(at(10, 8, 30), at(10, 9, 0), "in gap"),
+            row(at(10, 11, 0), at(10, 12, 0), "gap end"),
+            row(at(10, 14, 0), at(10, 15, 0), "after"),
+            row(at(10, 7, 0), at(10, 8, 0), "code"),
+        ];
+        let (added, skipped) = apply(&mut tl, &rows);
+        assert_eq!(added, 4);
+        assert_eq!(
+            skipped,
+            vec![
+                "2022-06-10 07:30 - 08:30 overlap: overlaps logged time",
+                "2022-06-10 07:00 - 08:00 code: already logged"
+            ]
+        );
+        assert_eq!(
+            tl.get_all().map(|e| e.to_string()).collect::<Vec<_>>(),
+            vec![
+                "2022-06-09 09:00: arrived",
+                "2022-06-09 10:30: new day",
+                "2022-06-10 07:00: arrived",
+                "2022-06-10 08:00: code",
+                "2022-06-10 08:30: **",
+                "2022-06-10 09:00: in gap",
+                "2022-06-10 11:00: **",
+                "2022-06-10 12:00: gap end",
+                "2022-06-10 13:00: review",
+                "2022-06-10 14:00: **",
+                "2022-06-10 15:00: after",
+            ]
+        );
+    }
+}
@@ -0,0 +1,136 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::ptr;
+use std::sync::{Mutex, OnceLock};
+
+// all interned strings; only needed for interning, symbols point to their string directly
+static INTERNER: OnceLock<Mutex<HashSet<&'static str>>> = OnceLock::new();
+
+fn interner() -> &'static Mutex<HashSet<&'static str>> {
+    INTERNER.get_or_init(Default::default)
+}
+
+/**
+ * Interned string, like a task name
+ *
+ * Logs repeat the same few tasks over and over again, so each distinct string is only stored once
+ * (for the lifetime of the program), and comparing symbols is just comparing their addresses.
+ */
+#[derive(Clone, Copy)]
+pub struct Symbol(&'static str);
+
+impl Symbol {
+    pub fn intern(s: &str) -> Symbol {
+        let mut interner = interner().lock().unwrap();
+        if let Some(interned) = interner.get(s) {
+            return Symbol(interned);
+        }
+        let s: &'static str = Box::leak(s.to_string().into_boxed_str());
+        interner.insert(s);
+        Symbol(s)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        self.0
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Symbol) -> bool {
+        ptr::eq(self.0, other.0)
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.as_ptr().hash(state)
+    }
+}
+
+impl Default for Symbol {
+    fn default() -> Symbol {
+        Symbol::intern("")
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(s: &str) -> Symbol {
+        Symbol::intern(s)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(s: String) -> Symbol {
+        Symbol::intern(&s)
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern() {
+        let a = Symbol::intern("code review");
+        let b = Symbol::from("code review".to_string());
+        let c = Symbol::from("email");
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(a, "code review");
+        assert_eq!(&*c, "email");
+        assert!(a.starts_with("code"));
+        assert_eq!(format!("{} {:?}", a, c), "code review \"email\"");
+        assert_eq!(Symbol::default(), "");
+    }
+}
@@ -0,0 +1,68 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::fmt::Write as _; // import without risk of name clashing
+
+use chrono::NaiveDateTime;
+
+/**
+ * Format a string as a quoted JSON string
+ */
+pub fn string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/**
+ * Format a time as a quoted ISO-8601 JSON string like "2022-06-10T08:00:00"
+ */
+pub fn datetime(t: &NaiveDateTime) -> String {
+    format!("\"{}\"", t.format("%Y-%m-%dT%H:%M:%S"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_string() {
+        assert_eq!(string(""), "\"\"");
+        assert_eq!(string("code review"), "\"code review\"");
+        assert_eq!(string("say \"hi\"\\"), "\"say \\\"hi\\\"\\\\\"");
+        assert_eq!(string("a\nb\tc\0ä"), "\"a\\nb\\tc\\u0000ä\"");
+    }
+
+    #[test]
+    fn test_datetime() {
+        let t = chrono::NaiveDate::from_ymd_opt(2022, 6, 10)
+            .unwrap()
+            .and_hms_opt(8, 5, 0)
+            .unwrap();
+        assert_eq!(datetime(&t), "\"2022-06-10T08:05:00\"");
+    }
+}
@@ -0,0 +1,373 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate chrono;
+
+use std::fmt;
+
+use chrono::{Datelike, Duration, NaiveDate};
+
+use crate::activity::Activities;
+use crate::dateparse::parse_date;
+use crate::store::{Entry, Options};
+
+// longest period covered by a single marker, against typos in the year
+const MAX_MARKED_DAYS: usize = 366;
+
+/**
+ * Does the task start with one of the markers (case insensitive)?
+ */
+pub fn is_marked(task: &str, markers: &[String]) -> bool {
+    let task = task.trim().to_lowercase();
+    markers
+        .iter()
+        .any(|m| !m.is_empty() && task.starts_with(&m.to_lowercase()))
+}
+
+/**
+ * Last day covered by a marker entry on `day` like "*** vacation until 2022-08-16"
+ *
+ * The end can also be a phrase like "until friday" (see dateparse::parse_date).
+ */
+pub fn marked_until(task: &str, day: NaiveDate) -> Option<NaiveDate> {
+    let task = task.to_lowercase();
+    let (_, until) = task.rsplit_once(" until ")?;
+    parse_date(until, day)
+        .ok()
+        .filter(|end| *end > day)
+        // "until friday" means the coming one
+        .or_else(|| parse_date(&format!("next {}", until), day).ok())
+        .filter(|end| *end > day)
+}
+
+/**
+ * Days which have an entry with one of the markers, like "*** vacation"
+ *
+ * A marker with an end like "*** vacation until 2022-08-16" also covers the weekdays up to that.
+ */
+pub fn marked_days(entries: &[Entry], markers: &[String], options: &Options) -> Vec<NaiveDate> {
+    let mut days: Vec<NaiveDate> = Vec::new();
+    for e in entries {
+        if !is_marked(&e.task, markers) {
+            continue;
+        }
+        let day = options.day(e);
+        days.push(day);
+        if let Some(end) = marked_until(&e.task, day) {
+            days.extend(
+                day.iter_days()
+                    .skip(1)
+                    .take(MAX_MARKED_DAYS)
+                    .take_while(|d| *d <= end)
+                    .filter(|d| d.weekday().num_days_from_monday() < 5),
+            );
+        }
+    }
+    days.sort();
+    days.dedup();
+    days
+}
+
+/**
+ * Vacation balance of a year
+ */
+pub struct LeaveBalance {
+    pub year: i32,
+    pub allowance: u32,
+    pub taken: Vec<NaiveDate>,
+}
+
+impl LeaveBalance {
+    pub fn new(
+        entries: &[Entry],
+        markers: &[String],
+        year: i32,
+        allowance: u32,
+        options: &Options,
+    ) -> LeaveBalance {
+        LeaveBalance {
+            year,
+            allowance,
+            taken: marked_days(entries, markers, options)
+                .into_iter()
+                .filter(|d| d.year() == year)
+                .collect(),
+        }
+    }
+
+    pub fn remaining(&self) -> i64 {
+        i64::from(self.allowance) - self.taken.len() as i64
+    }
+}
+
+impl fmt::Display for LeaveBalance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Vacation {}:", self.year)?;
+        for day in &self.taken {
+            writeln!(f, "  {}", day.format("%Y-%m-%d %a"))?;
+        }
+        writeln!(f, "-------")?;
+        writeln!(
+            f,
+            "Taken: {} of {} days, remaining: {} days",
+            self.taken.len(),
+            self.allowance,
+            self.remaining()
+        )
+    }
+}
+
+/**
+ * Sick days of a year
+ *
+ * A sick day without any logged work counts as a full day. Otherwise it is a partial sick day,
+ * and counts as the fraction of the expected hours which were not worked.
+ */
+pub struct SickSummary {
+    pub year: i32,
+    expected: Duration,
+    // day and work done on it
+    days: Vec<(NaiveDate, Duration)>,
+}
+
+impl SickSummary {
+    pub fn new(
+        entries: &[Entry],
+        markers: &[String],
+        year: i32,
+        expected: Duration,
+        options: &Options,
+    ) -> SickSummary {
+        let days = marked_days(entries, markers, options)
+            .into_iter()
+            .filter(|d| d.year() == year)
+            .map(|day| {
+                let first = entries.partition_point(|e| options.day(e) < day);
+                let last = entries.partition_point(|e| options.day(e) <= day);
+                let worked =
+                    Activities::new_from_entries(&entries[first..last], options).total_work();
+                (day, worked)
+            })
+            .collect();
+        SickSummary {
+            year,
+            expected,
+            days,
+        }
+    }
+
+    fn fraction(&self, worked: Duration) -> f64 {
+        if worked <= Duration::zero() || self.expected <= Duration::zero() {
+            return 1.0;
+        }
+        let missing = (self.expected - worked).num_minutes().max(0);
+        missing as f64 / self.expected.num_minutes() as f64
+    }
+
+    pub fn full_days(&self) -> usize {
+        self.days
+            .iter()
+            .filter(|(_, w)| *w <= Duration::zero())
+            .count()
+    }
+
+    pub fn partial_days(&self) -> usize {
+        self.days.len() - self.full_days()
+    }
+
+    /**
+     * Sick time in days, with partial days counted fractionally
+     */
+    pub fn total(&self) -> f64 {
+        self.days.iter().map(|(_, w)| self.fraction(*w)).sum()
+    }
+}
+
+impl fmt::Display for SickSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Sick days {}:", self.year)?;
+        for (day, worked) in &self.days {
+            if *worked <= Duration::zero() {
+                writeln!(f, "  {}: full day", day.format("%Y-%m-%d %a"))?;
+            } else {
+                writeln!(
+                    f,
+                    "  {}: partial, {} h {} min worked ({:.2} days)",
+                    day.format("%Y-%m-%d %a"),
+                    worked.num_hours(),
+                    worked.num_minutes() % 60,
+                    self.fraction(*worked)
+                )?;
+            }
+        }
+        writeln!(f, "-------")?;
+        writeln!(
+            f,
+            "Full days: {}, partial days: {}, total: {:.2} days",
+            self.full_days(),
+            self.partial_days(),
+            self.total()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::Timelog;
+
+    const LOG: &str = "
+2021-12-31 09:00: *** vacation
+
+2022-01-03 08:00: arrived
+2022-01-03 12:00: code
+
+2022-01-04 09:00: *** Vacation
+2022-01-04 09:00: *** vacation
+
+2022-01-05 09:00: *** PTO: skiing
+
+2022-01-06 09:00: ** vacation planning
+";
+
+    fn markers() -> Vec<String> {
+        vec!["*** vacation".to_string(), "*** pto".to_string()]
+    }
+
+    #[test]
+    fn test_is_marked() {
+        assert!(is_marked("*** vacation", &markers()));
+        assert!(is_marked(" *** PTO ", &markers()));
+        assert!(!is_marked("** vacation", &markers()));
+        assert!(!is_marked("vacation", &markers()));
+        assert!(!is_marked("anything", &[String::new()]));
+    }
+
+    #[test]
+    fn test_marked_days() {
+        let tl = Timelog::new_from_string(LOG);
+        let entries: Vec<Entry> = tl.get_all().cloned().collect();
+        let days = marked_days(&entries, &markers(), &Options::default());
+        assert_eq!(
+            days,
+            vec![
+                NaiveDate::from_ymd_opt(2021, 12, 31).unwrap(),
+                NaiveDate::from_ymd_opt(2022, 1, 4).unwrap(),
+                NaiveDate::from_ymd_opt(2022, 1, 5).unwrap(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_marked_until() {
+        let tl = Timelog::new_from_string(
+            "
+2022-08-03 09:00: *** vacation until 2022-08-16
+2022-08-05 09:00: *** vacation
+2022-08-17 09:00: *** PTO until friday
+2022-08-22 09:00: *** vacation until last year
+",
+        );
+        let entries: Vec<Entry> = tl.get_all().cloned().collect();
+        let days: Vec<String> = marked_days(&entries, &markers(), &Options::default())
+            .iter()
+            .map(|d| d.format("%m-%d").to_string())
+            .collect();
+        // without weekends; "until last year" is invalid and just counts the day itself
+        assert_eq!(
+            days,
+            vec![
+                "08-03", "08-04", "08-05", "08-08", "08-09", "08-10", "08-11", "08-12", "08-15",
+                "08-16", "08-17", "08-18", "08-19", "08-22"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_balance() {
+        let tl = Timelog::new_from_string(LOG);
+        let entries: Vec<Entry> = tl.get_all().cloned().collect();
+
+        let b = LeaveBalance::new(&entries, &markers(), 2022, 25, &Options::default());
+        assert_eq!(b.taken.len(), 2);
+        assert_eq!(b.remaining(), 23);
+        assert_eq!(
+            format!("{}", b),
+            "Vacation 2022:
+  2022-01-04 Tue
+  2022-01-05 Wed
+-------
+Taken: 2 of 25 days, remaining: 23 days
+"
+        );
+
+        let b = LeaveBalance::new(&entries, &markers(), 2021, 0, &Options::default());
+        assert_eq!(b.remaining(), -1);
+        let b = LeaveBalance::new(&entries, &markers(), 2020, 30, &Options::default());
+        assert_eq!(b.remaining(), 30);
+    }
+
+    #[test]
+    fn test_sick() {
+        let tl = Timelog::new_from_string(
+            "
+2022-01-10 09:00: *** sick
+
+2022-01-11 08:00: arrived
+2022-01-11 10:00: code
+2022-01-11 10:00: *** sick: went home
+
+2022-01-12 08:00: arrived
+2022-01-12 17:00: code
+2022-01-12 17:00: *** sick
+
+2022-01-13 09:00: *** vacation
+",
+        );
+        let entries: Vec<Entry> = tl.get_all().cloned().collect();
+        let markers = vec!["*** sick".to_string()];
+
+        let s = SickSummary::new(
+            &entries,
+            &markers,
+            2022,
+            Duration::hours(8),
+            &Options::default(),
+        );
+        assert_eq!(s.full_days(), 1);
+        assert_eq!(s.partial_days(), 2);
+        // 1 + 6/8 + 0
+        assert_eq!(s.total(), 1.75);
+        assert_eq!(
+            format!("{}", s),
+            "Sick days 2022:
+  2022-01-10 Mon: full day
+  2022-01-11 Tue: partial, 2 h 0 min worked (0.75 days)
+  2022-01-12 Wed: partial, 9 h 0 min worked (0.00 days)
+-------
+Full days: 1, partial days: 2, total: 1.75 days
+"
+        );
+
+        let s = SickSummary::new(
+            &entries,
+            &markers,
+            2021,
+            Duration::hours(8),
+            &Options::default(),
+        );
+        assert_eq!(s.total(), 0.0);
+    }
+}
@@ -1,2 +1,40 @@
 pub mod activity;
+pub mod archive;
+pub mod backfill;
+pub mod balance;
+pub mod budget;
+pub mod cache;
+pub mod chain;
+pub mod cli;
+pub mod compress;
+pub mod config;
+pub mod dateparse;
+pub mod diff;
+pub mod doctor;
+pub mod error;
+pub mod export;
+pub mod footer;
+pub mod i18n;
+pub mod import;
+pub mod intern;
+pub mod json;
+pub mod leave;
+pub mod locale;
+pub mod markers;
+pub mod migrate;
+pub mod output;
+pub mod provenance;
+pub mod quickentry;
+pub mod regex;
+pub mod schedule;
+pub mod semdiff;
+pub mod sha256;
+pub mod snapshot;
 pub mod store;
+pub mod tasklist;
+pub mod team;
+pub mod toil;
+pub mod trace;
+pub mod ui;
+pub mod validate;
+pub mod widget;
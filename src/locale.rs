@@ -0,0 +1,346 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate chrono;
+
+use std::env;
+
+use chrono::format::StrftimeItems;
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+
+/**
+ * Locale: weekday/month names and date format for rendering dates
+ *
+ * chrono only knows English names (without its heavy unstable-locales feature), so we carry a
+ * small table of our own.
+ */
+#[derive(Debug, PartialEq, Eq)]
+pub struct Locale {
+    pub name: &'static str,
+    weekdays: [&'static str; 7],
+    weekdays_abbr: [&'static str; 7],
+    months: [&'static str; 12],
+    months_abbr: [&'static str; 12],
+    date_fmt: &'static str,
+    decimal_sep: char,
+}
+
+pub const EN: Locale = Locale {
+    name: "en",
+    weekdays: [
+        "Monday",
+        "Tuesday",
+        "Wednesday",
+        "Thursday",
+        "Friday",
+        "Saturday",
+        "Sunday",
+    ],
+    weekdays_abbr: ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"],
+    months: [
+        "January",
+        "February",
+        "March",
+        "April",
+        "May",
+        "June",
+        "July",
+        "August",
+        "September",
+        "October",
+        "November",
+        "December",
+    ],
+    months_abbr: [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ],
+    date_fmt: "%Y-%m-%d",
+    decimal_sep: '.',
+};
+
+pub const DE: Locale = Locale {
+    name: "de",
+    weekdays: [
+        "Montag",
+        "Dienstag",
+        "Mittwoch",
+        "Donnerstag",
+        "Freitag",
+        "Samstag",
+        "Sonntag",
+    ],
+    weekdays_abbr: ["Mo", "Di", "Mi", "Do", "Fr", "Sa", "So"],
+    months: [
+        "Januar",
+        "Februar",
+        "März",
+        "April",
+        "Mai",
+        "Juni",
+        "Juli",
+        "August",
+        "September",
+        "Oktober",
+        "November",
+        "Dezember",
+    ],
+    months_abbr: [
+        "Jan", "Feb", "Mär", "Apr", "Mai", "Jun", "Jul", "Aug", "Sep", "Okt", "Nov", "Dez",
+    ],
+    date_fmt: "%d.%m.%Y",
+    decimal_sep: ',',
+};
+
+pub const FR: Locale = Locale {
+    name: "fr",
+    weekdays: [
+        "lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche",
+    ],
+    weekdays_abbr: ["lun.", "mar.", "mer.", "jeu.", "ven.", "sam.", "dim."],
+    months: [
+        "janvier",
+        "février",
+        "mars",
+        "avril",
+        "mai",
+        "juin",
+        "juillet",
+        "août",
+        "septembre",
+        "octobre",
+        "novembre",
+        "décembre",
+    ],
+    months_abbr: [
+        "janv.", "févr.", "mars", "avr.", "mai", "juin", "juil.", "août", "sept.", "oct.", "nov.",
+        "déc.",
+    ],
+    date_fmt: "%d/%m/%Y",
+    decimal_sep: ',',
+};
+
+const LOCALES: [&Locale; 3] = [&EN, &DE, &FR];
+
+impl Locale {
+    /**
+     * Look up a locale by POSIX name like "de_DE.UTF-8"; unknown ones (including "C") fall back to
+     * English
+     */
+    pub fn from_name(name: &str) -> &'static Locale {
+        let lang = name.split(['_', '.', '@']).next().unwrap_or("");
+        LOCALES
+            .iter()
+            .find(|l| l.name == lang)
+            .copied()
+            .unwrap_or(&EN)
+    }
+
+    /**
+     * Locale for dates, following the usual POSIX precedence LC_ALL > LC_TIME > LANG
+     */
+    pub fn from_env() -> &'static Locale {
+        for var in ["LC_ALL", "LC_TIME", "LANG"] {
+            if let Ok(val) = env::var(var) {
+                if !val.is_empty() {
+                    return Locale::from_name(&val);
+                }
+            }
+        }
+        &EN
+    }
+
+    /**
+     * Expand the name conversions %A, %a, %B, %b and the date conversion %x in a strftime format
+     */
+    fn localize_fmt(&self, fmt: &str, date: &NaiveDate) -> String {
+        let weekday = date.weekday().num_days_from_monday() as usize;
+        let month = date.month0() as usize;
+        let mut out = String::with_capacity(fmt.len());
+        let mut chars = fmt.chars();
+
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                out.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('A') => out.push_str(self.weekdays[weekday]),
+                Some('a') => out.push_str(self.weekdays_abbr[weekday]),
+                Some('B') => out.push_str(self.months[month]),
+                Some('b') => out.push_str(self.months_abbr[month]),
+                Some('x') => out.push_str(self.date_fmt),
+                Some(other) => {
+                    out.push('%');
+                    out.push(other);
+                }
+                None => out.push('%'),
+            }
+        }
+        out
+    }
+
+    pub fn format_date(&self, date: &NaiveDate, fmt: &str) -> String {
+        let fmt = self.localize_fmt(fmt, date);
+        date.format_with_items(StrftimeItems::new(&fmt)).to_string()
+    }
+
+    pub fn format_datetime(&self, dt: &NaiveDateTime, fmt: &str) -> String {
+        let fmt = self.localize_fmt(fmt, &dt.date());
+        dt.format_with_items(StrftimeItems::new(&fmt)).to_string()
+    }
+
+    pub fn month_name(&self, date: &impl Datelike) -> &'static str {
+        self.months[date.month0() as usize]
+    }
+
+    /**
+     * Parse a date in ISO format, the locale's date format, or with a month name
+     *
+     * Names are matched case insensitively, in full or abbreviated, and weekday names are ignored:
+     * "2022-06-10", "10.06.2022", "Freitag, 10. Juni 2022", "10 juin 2022", "June 10, 2022".
+     */
+    pub fn parse_date(&self, s: &str) -> Option<NaiveDate> {
+        let s = s.trim();
+        for fmt in ["%Y-%m-%d", self.date_fmt] {
+            if let Ok(d) = NaiveDate::parse_from_str(s, fmt) {
+                return Some(d);
+            }
+        }
+
+        let lower = s.to_lowercase();
+        let words: Vec<&str> = lower
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|w| !w.is_empty())
+            .collect();
+        let name_matches = |names: &[&str], word: &str| {
+            let word = word.trim_end_matches('.');
+            names
+                .iter()
+                .any(|n| n.to_lowercase().trim_end_matches('.') == word)
+        };
+        let (mut day, mut month, mut year) = (None, None, None);
+        for word in words {
+            if let Some(i) =
+                (0..12).find(|i| name_matches(&[self.months[*i], self.months_abbr[*i]], word))
+            {
+                month = Some(i as u32 + 1);
+            } else if name_matches(&self.weekdays, word) || name_matches(&self.weekdays_abbr, word)
+            {
+                continue;
+            } else {
+                let number: u32 = word.trim_end_matches('.').parse().ok()?;
+                if number > 31 {
+                    year = Some(number as i32);
+                } else {
+                    day = Some(number);
+                }
+            }
+        }
+        NaiveDate::from_ymd_opt(year?, month?, day?)
+    }
+
+    /**
+     * Parse a decimal number with the locale's decimal separator (a point always works)
+     */
+    pub fn parse_number(&self, s: &str) -> Option<f64> {
+        s.trim().replace(self.decimal_sep, ".").parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(Locale::from_name("de_DE.UTF-8"), &DE);
+        assert_eq!(Locale::from_name("de_AT"), &DE);
+        assert_eq!(Locale::from_name("fr_FR@euro"), &FR);
+        assert_eq!(Locale::from_name("en_US.UTF-8"), &EN);
+        assert_eq!(Locale::from_name("C.UTF-8"), &EN);
+        assert_eq!(Locale::from_name("POSIX"), &EN);
+        assert_eq!(Locale::from_name(""), &EN);
+    }
+
+    #[test]
+    fn test_format_date() {
+        let d = NaiveDate::from_ymd_opt(2022, 6, 10).unwrap();
+        assert_eq!(
+            EN.format_date(&d, "%A, %x (week %U)"),
+            "Friday, 2022-06-10 (week 23)"
+        );
+        assert_eq!(
+            DE.format_date(&d, "%A, %x (week %U)"),
+            "Freitag, 10.06.2022 (week 23)"
+        );
+        assert_eq!(FR.format_date(&d, "%a %e %B"), "ven. 10 juin");
+        assert_eq!(DE.format_date(&d, "%b %Y"), "Jun 2022");
+        // literal percent stays alone
+        assert_eq!(DE.format_date(&d, "100%% %A"), "100% Freitag");
+    }
+
+    #[test]
+    fn test_format_datetime() {
+        let dt = NaiveDate::from_ymd_opt(2022, 3, 1)
+            .unwrap()
+            .and_hms_opt(14, 5, 0)
+            .unwrap();
+        assert_eq!(
+            DE.format_datetime(&dt, "%a %x %H:%M"),
+            "Di 01.03.2022 14:05"
+        );
+        assert_eq!(DE.month_name(&dt), "März");
+    }
+
+    #[test]
+    fn test_parse_date() {
+        let d = NaiveDate::from_ymd_opt(2022, 6, 10);
+        assert_eq!(EN.parse_date("2022-06-10"), d);
+        assert_eq!(DE.parse_date(" 2022-06-10 "), d);
+        assert_eq!(DE.parse_date("10.06.2022"), d);
+        assert_eq!(FR.parse_date("10/06/2022"), d);
+        assert_eq!(EN.parse_date("June 10, 2022"), d);
+        assert_eq!(EN.parse_date("Fri, 10 Jun 2022"), d);
+        assert_eq!(DE.parse_date("Freitag, 10. Juni 2022"), d);
+        assert_eq!(DE.parse_date("10. JUNI 2022"), d);
+        assert_eq!(FR.parse_date("vendredi 10 juin 2022"), d);
+        assert_eq!(FR.parse_date("ven. 10 juin 2022"), d);
+        assert_eq!(
+            DE.parse_date("1. Mär 2022"),
+            NaiveDate::from_ymd_opt(2022, 3, 1)
+        );
+        assert_eq!(
+            FR.parse_date("3 févr. 2022"),
+            NaiveDate::from_ymd_opt(2022, 2, 3)
+        );
+
+        // names of other locales, or incomplete dates
+        assert_eq!(EN.parse_date("10. Juni 2022"), None);
+        assert_eq!(DE.parse_date("10.06."), None);
+        assert_eq!(DE.parse_date("Juni 2022"), None);
+        assert_eq!(DE.parse_date("31. Juni 2022"), None);
+        assert_eq!(EN.parse_date(""), None);
+    }
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(EN.parse_number("1.5"), Some(1.5));
+        assert_eq!(EN.parse_number("1,5"), None);
+        assert_eq!(DE.parse_number("1,5"), Some(1.5));
+        assert_eq!(DE.parse_number("1.5"), Some(1.5));
+        assert_eq!(FR.parse_number(" 0,25 "), Some(0.25));
+        assert_eq!(FR.parse_number("x"), None);
+    }
+}
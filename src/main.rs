@@ -15,25 +15,60 @@
 
 use std::env;
 use std::error::Error;
-use std::io;
-use std::path::PathBuf;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::process;
+use std::thread;
 
-use chrono::prelude::*;
+use chrono::{prelude::*, Duration};
 use rustyline::{error::ReadlineError, Editor};
 
-use rtimelog::store::Timelog;
+use rtimelog::activity::{format_chronological, Activities, RoundingMode};
+use rtimelog::backfill::{self, Answer};
+use rtimelog::balance::Balance;
+use rtimelog::budget::BurnDown;
+use rtimelog::cache::DayCache;
+use rtimelog::chain;
+use rtimelog::cli::{self, Args};
+use rtimelog::config::{self, Config, DayGrouping};
+use rtimelog::dateparse;
+use rtimelog::debug;
+use rtimelog::doctor;
+use rtimelog::error::{self, ErrorKind};
+use rtimelog::export;
+use rtimelog::i18n::{self, tr};
+use rtimelog::import;
+use rtimelog::leave::{LeaveBalance, SickSummary};
+use rtimelog::locale::Locale;
+use rtimelog::markers::Markers;
+use rtimelog::migrate;
+use rtimelog::output::{self, Format, Report};
+use rtimelog::quickentry::{parse_duration, QuickEntry};
+use rtimelog::regex::Regex;
+use rtimelog::schedule::{self, Channel};
+use rtimelog::semdiff;
+use rtimelog::snapshot;
+use rtimelog::store::{self, Entry, Timelog};
+use rtimelog::tasklist::{self, TaskList};
+use rtimelog::team::TeamReport;
+use rtimelog::toil::Ledger;
+use rtimelog::trace::{self, Timer};
+use rtimelog::ui;
+use rtimelog::validate::Rules;
+use rtimelog::widget;
 
 enum TimeMode {
     Day,
     Week,
+    Chronological,
 }
 
 fn clear_screen() {
     print!("{esc}c", esc = 27 as char);
 }
 
-fn get_input(rl: &mut Editor<()>) -> Result<String, ReadlineError> {
+fn get_input(rl: &mut Editor<TaskList>) -> Result<String, ReadlineError> {
     match rl.readline("> ") {
         Ok(mut line) => {
             line.truncate(line.trim_end().len());
@@ -49,33 +84,96 @@ fn get_input(rl: &mut Editor<()>) -> Result<String, ReadlineError> {
 
 fn show_help() {
     println!(
-        "
+        "{}",
+        tr("
 :w - switch to weekly mode
 :d - switch to daily mode
+:c - switch to chronological list of today's entries
 :q - quit
 :h - show this help
 :e - open timelog.txt in $EDITOR
 ^r - history search (like in bash) through currently shown activities
 
-Any other input is the description of a task that you just finished."
+Any other input is the description of a task that you just finished.")
     );
 }
 
-fn show(timelog: &Timelog, mode: &TimeMode, rl_editor: &mut Editor<()>) {
+fn split_concurrent(activities: Activities, config: &Config) -> Activities {
+    match &config.task_separator {
+        Some(separator) => activities.split_concurrent(separator),
+        None => activities,
+    }
+}
+
+/**
+ * Today's entries, or the current shift with shift day grouping
+ */
+fn get_current_day<'a>(timelog: &'a Timelog, config: &Config) -> &'a [Entry] {
+    match config.day_grouping {
+        DayGrouping::Calendar => timelog.get_today(),
+        DayGrouping::Shift => {
+            timelog.get_current_shift(Local::now().naive_local(), config.shift_break)
+        }
+    }
+}
+
+fn show(
+    timelog: &Timelog,
+    config: &Config,
+    mode: &TimeMode,
+    cache: &mut DayCache,
+    rl_editor: &mut Editor<TaskList>,
+) {
     clear_screen();
+    let locale = Locale::from_env();
     let entries = match mode {
-        TimeMode::Day => {
-            println!("Work done today {}:", timelog.get_today_as_string());
-            timelog.get_today()
+        TimeMode::Day | TimeMode::Chronological => {
+            let title = match config.day_grouping {
+                DayGrouping::Calendar => tr("Work done today"),
+                DayGrouping::Shift => tr("Work done this shift"),
+            };
+            println!(
+                "{} {}:",
+                title,
+                timelog.get_today_as_string(locale, &config.date_format)
+            );
+            get_current_day(timelog, config)
         }
         TimeMode::Week => {
-            println!("Work done this week {}:", timelog.get_this_week_as_string());
+            println!(
+                "{} {}:",
+                tr("Work done this week"),
+                timelog.get_this_week_as_string(locale)
+            );
             timelog.get_this_week()
         }
     };
 
-    let a = rtimelog::activity::Activities::new_from_entries(entries);
-    println!("{}", a);
+    if let TimeMode::Chronological = mode {
+        print!(
+            "{}",
+            format_chronological(entries, &config.time_format, timelog.options())
+        );
+    } else {
+        let a = match (mode, config.day_grouping) {
+            (TimeMode::Day, DayGrouping::Shift) => {
+                Activities::new_from_shifts(&[entries], timelog.options())
+            }
+            (TimeMode::Week, DayGrouping::Calendar) => {
+                let today = timelog.options().today();
+                let monday = today - Duration::days(today.weekday().num_days_from_monday().into());
+                cache.range(timelog, monday, monday + Duration::days(6))
+            }
+            _ => Activities::new_from_entries(entries, timelog.options()),
+        };
+        println!("{}", split_concurrent(a, config));
+        if let (TimeMode::Day, DayGrouping::Calendar) = (mode, config.day_grouping) {
+            print!(
+                "{}",
+                Balance::new(timelog, timelog.options().today(), config)
+            );
+        }
+    }
 
     rl_editor.clear_history();
     for a in Timelog::get_history(entries) {
@@ -83,22 +181,26 @@ fn show(timelog: &Timelog, mode: &TimeMode, rl_editor: &mut Editor<()>) {
     }
 }
 
-fn show_prompt(timelog: &Timelog) -> Result<(), io::Error> {
-    let since_last = timelog
-        .get_today()
+fn show_prompt(timelog: &Timelog, config: &Config) -> Result<(), io::Error> {
+    let since_last = get_current_day(timelog, config)
         .last()
         .map(|e| Local::now().naive_local().signed_duration_since(e.stop));
 
     let since_str = match since_last {
-        None => "no entries yet today".to_string(),
+        None => tr("no entries yet today").to_string(),
         Some(d) => format!(
-            "{} h {} min since last entry",
+            "{} h {} min {}",
             d.num_hours(),
-            d.num_minutes() % 60
+            d.num_minutes() % 60,
+            tr("since last entry")
         ),
     };
 
-    println!("\n{}; type command (:h for help) or entry", since_str);
+    println!(
+        "\n{}; {}",
+        since_str,
+        tr("type command (:h for help) or entry")
+    );
     Ok(())
 }
 
@@ -109,19 +211,111 @@ fn run_editor(fname: &PathBuf) {
     }
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let mut timelog = Timelog::new_from_default_file();
+/**
+ * Shared task list from the configured task_list_url, if any
+ */
+fn load_task_list(config: &Config) -> Option<TaskList> {
+    let url = config.task_list_url.as_ref()?;
+    let cache = config
+        .store_options()
+        .file()
+        .with_file_name(tasklist::CACHE_FILE);
+    if let Some(parent) = cache.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    TaskList::load(url, &cache, config.task_list_expiry)
+        .map_err(|e| eprintln!("WARNING: {}", e))
+        .ok()
+}
+
+/**
+ * Warn about tasks which are not in the task list
+ */
+fn check_task(task_list: Option<&TaskList>, task: &str, markers: &Markers) {
+    if let Some(warning) = unknown_task(task_list, task, markers) {
+        eprintln!("WARNING: {}", warning);
+    }
+}
+
+fn unknown_task(task_list: Option<&TaskList>, task: &str, markers: &Markers) -> Option<String> {
+    task_list
+        .is_some_and(|l| !l.is_known(task, markers))
+        .then(|| format!("\"{}\" is not in the task list", task))
+}
+
+/**
+ * The local tasks.txt next to the timelog, if there is one
+ */
+fn load_local_tasks(options: &store::Options) -> Option<TaskList> {
+    let local = options.file().with_file_name(tasklist::LOCAL_FILE);
+    fs::read_to_string(local).map(|c| TaskList::parse(&c)).ok()
+}
+
+/**
+ * Tasks for completion: previously logged ones, tasks.txt, and the shared task list
+ */
+fn completion_list(timelog: &Timelog, task_list: Option<&TaskList>) -> TaskList {
+    let mut list = TaskList::from_history(timelog.get_all(), &timelog.options().markers);
+    list.merge(load_local_tasks(timelog.options()).unwrap_or_default());
+    list.merge(task_list.cloned().unwrap_or_default());
+    list
+}
+
+/**
+ * Validation rules from the configuration; the categories come from tasks.txt and the shared list
+ */
+fn validation_rules(
+    config: &Config,
+    task_list: Option<&TaskList>,
+) -> Result<Rules, Box<dyn Error>> {
+    let categories = if config.require_category {
+        let mut list = load_local_tasks(&config.store_options());
+        if let Some(remote) = task_list {
+            list.get_or_insert_with(TaskList::default)
+                .merge(remote.clone());
+        }
+        Some(list.ok_or_else(|| {
+            fail(
+                ErrorKind::Config,
+                format!(
+                    "require_category is set, but there is no {} or task_list_url",
+                    tasklist::LOCAL_FILE
+                ),
+            )
+        })?)
+    } else {
+        None
+    };
+    Ok(Rules {
+        task_pattern: config.task_pattern.clone(),
+        categories,
+        max_backdate: config.max_backdate,
+        markers: config.markers.clone(),
+    })
+}
+
+fn interactive(config: &Config) -> Result<(), Box<dyn Error>> {
+    let options = config.store_options();
+    let mut timelog = Timelog::new_from_default_file(&options)?;
     let mut running = true;
     let mut time_mode = TimeMode::Day;
-    let mut readline = Editor::<()>::new()?;
+    let mut readline = Editor::<TaskList>::new()?;
+    let task_list = load_task_list(config);
+    readline.set_helper(Some(completion_list(&timelog, task_list.as_ref())));
+    let rules = validation_rules(config, task_list.as_ref())?;
+    let mut cache = DayCache::new();
     let mut do_show = true;
 
     while running {
+        // the file may have been edited or synced meanwhile
+        if timelog.reload_if_changed() {
+            do_show = true;
+        }
         if do_show {
-            show(&timelog, &time_mode, &mut readline);
+            show(&timelog, config, &time_mode, &mut cache, &mut readline);
         }
         do_show = true;
-        show_prompt(&timelog)?;
+        show_prompt(&timelog, config)?;
 
         let input = get_input(&mut readline)?;
         match input.as_str() {
@@ -130,9 +324,13 @@ fn main() -> Result<(), Box<dyn Error>> {
                 show_help();
                 do_show = false;
             }
+            ":e" if options.read_only => {
+                println!("{}", tr("Read-only mode, not editing the timelog"));
+                do_show = false;
+            }
             ":e" => {
                 run_editor(&timelog.filename.unwrap());
-                timelog = Timelog::new_from_default_file();
+                timelog = Timelog::new_from_default_file(&options)?;
             }
             ":d" => {
                 time_mode = TimeMode::Day;
@@ -140,8 +338,25 @@ fn main() -> Result<(), Box<dyn Error>> {
             ":w" => {
                 time_mode = TimeMode::Week;
             }
+            ":c" => {
+                time_mode = TimeMode::Chronological;
+            }
             "" => (),
+            _ if options.read_only && !store::is_dry_run() => {
+                println!("{}", tr("Read-only mode, not adding entries"));
+                do_show = false;
+            }
             _ => {
+                let now = Local::now().naive_local();
+                if let Err(e) = rules.check(&input, now, now) {
+                    println!("{}: {}", tr("Not added"), e);
+                    do_show = false;
+                    continue;
+                }
+                check_task(task_list.as_ref(), &input, &config.markers);
+                if let Some(completion) = readline.helper_mut() {
+                    completion.add(&input);
+                }
                 timelog.add(input);
                 timelog.save()?;
             }
@@ -149,3 +364,1868 @@ fn main() -> Result<(), Box<dyn Error>> {
     }
     Ok(())
 }
+
+/**
+ * Recently used tasks, newest first, for the ui prompt history
+ */
+fn recent_tasks(timelog: &Timelog) -> Vec<String> {
+    let today = timelog.options().today();
+    backfill::recent_tasks(
+        timelog.get_date_range(&(today - Duration::days(30)), &today),
+        50,
+        timelog.options(),
+    )
+}
+
+/**
+ * Full-screen mode which keeps the time since the last entry up to date
+ */
+fn ui(config: &Config) -> Result<(), Box<dyn Error>> {
+    let mut timelog = Timelog::new_from_default_file(&config.store_options())?;
+    let task_list = load_task_list(config);
+    let rules = validation_rules(config, task_list.as_ref())?;
+    let mut terminal = ui::Terminal::enter()
+        .map_err(|e| fail(ErrorKind::Usage, format!("the ui needs a terminal: {}", e)))?;
+    let locale = Locale::from_env();
+    let mut prompt = ui::Prompt::new(recent_tasks(&timelog));
+    let mut message = String::new();
+    let mut pending = Vec::new();
+    let mut shown: Option<NaiveDateTime> = None;
+    let mut dirty = true;
+    let mut live = ui::LiveActivities::new(get_current_day(&timelog, config), timelog.options());
+
+    loop {
+        if timelog.reload_if_changed() {
+            prompt.set_history(recent_tasks(&timelog));
+            dirty = true;
+        }
+        let now = Local::now().naive_local();
+        let minute = now.with_second(0).unwrap();
+        if dirty || shown != Some(minute) {
+            let entries = get_current_day(&timelog, config);
+            let current = live.update(entries, now);
+            let since_last = entries.last().map(|_| current.pending());
+            let activities = split_concurrent(current.clone(), config);
+            let title = format!(
+                "{} {}",
+                tr("Work done today"),
+                timelog.get_today_as_string(locale, &config.date_format)
+            );
+            let view = ui::View {
+                title: &title,
+                activities: &activities,
+                since_last,
+                hours_per_day: config.hours_per_day,
+                message: &message,
+                color: true,
+            };
+            print!("{}", ui::render(&view, &prompt, terminal.height()));
+            io::stdout().flush()?;
+            shown = Some(minute);
+            dirty = false;
+        }
+
+        for key in terminal.read_keys(&mut pending)? {
+            dirty = true;
+            let task = match prompt.key(key) {
+                ui::Action::None => continue,
+                ui::Action::Quit => return Ok(()),
+                ui::Action::Submit(task) if task == ":q" => return Ok(()),
+                ui::Action::Submit(task) => task,
+            };
+            if timelog.options().read_only && !store::is_dry_run() {
+                message = tr("Read-only mode, not adding entries").to_string();
+                continue;
+            }
+            let now = Local::now().naive_local();
+            if let Err(e) = rules.check(&task, now, now) {
+                message = format!("{}: {}", tr("Not added"), e);
+                continue;
+            }
+            timelog.add(task.clone());
+            let added = timelog.get_all().last().cloned().unwrap();
+            // like when another program holds the lock; keep the task for trying again
+            if let Err(e) = timelog.save() {
+                timelog.remove_entry(&added);
+                message = format!("{}: {}", tr("Not added"), e);
+                prompt.set_text(task);
+                continue;
+            }
+            message = unknown_task(task_list.as_ref(), &task, &config.markers).unwrap_or_default();
+            prompt.set_history(recent_tasks(&timelog));
+        }
+    }
+}
+
+/**
+ * Error of a specific kind, for the exit code
+ */
+fn fail(kind: ErrorKind, message: impl Into<String>) -> Box<dyn Error> {
+    Box::new(error::Error::new(kind, message))
+}
+
+/**
+ * Refuse commands which change the timelog in read-only mode, before they do anything
+ */
+fn check_writable(options: &store::Options) -> Result<(), Box<dyn Error>> {
+    if options.read_only && !store::is_dry_run() {
+        return Err(fail(
+            ErrorKind::ReadOnly,
+            "the timelog is read-only (--read-only, read_only setting, or --file -)",
+        ));
+    }
+    Ok(())
+}
+
+/**
+ * Date range from --today, --date, or --from/--to options; defaults to `today`
+ */
+fn date_range(args: &Args, today: NaiveDate) -> Result<(NaiveDate, NaiveDate), Box<dyn Error>> {
+    let parse = |name: &str| {
+        args.value(name)
+            .map(|s| dateparse::parse_date(s, today))
+            .transpose()
+            .map_err(|e| fail(ErrorKind::InvalidRange, e))
+    };
+
+    let date = if args.flag("today") {
+        Some(today)
+    } else {
+        parse("date")?
+    };
+    let (from, to) = match (date, parse("from")?, parse("to")?) {
+        (Some(_), Some(_), _) | (Some(_), _, Some(_)) => {
+            return Err(fail(
+                ErrorKind::Usage,
+                "--date/--today cannot be combined with --from/--to",
+            ))
+        }
+        (Some(date), None, None) => (date, date),
+        (None, from, to) => (from.unwrap_or(today), to.unwrap_or(today)),
+    };
+    if from > to {
+        return Err(fail(
+            ErrorKind::InvalidRange,
+            format!("--from {} is after --to {}", from, to),
+        ));
+    }
+    Ok((from, to))
+}
+
+/**
+ * Whether any of the date_range() options were given
+ */
+fn has_date_range(args: &Args) -> bool {
+    args.flag("today")
+        || ["date", "from", "to"]
+            .iter()
+            .any(|o| args.value(o).is_some())
+}
+
+fn report_title(config: &Config, from: NaiveDate, to: NaiveDate) -> String {
+    let locale = Locale::from_env();
+    let fmt = config.date_format.as_str();
+    if from == to {
+        format!("{} {}", tr("Work done on"), locale.format_date(&from, fmt))
+    } else {
+        format!(
+            "{} {} - {}",
+            tr("Work done from"),
+            locale.format_date(&from, fmt),
+            locale.format_date(&to, fmt)
+        )
+    }
+}
+
+/**
+ * Report variants, from the options of the report command
+ */
+#[derive(Default)]
+struct ReportOptions<'a> {
+    // only count entries created this way, like "manual"
+    source: Option<&'a str>,
+    // only count entries whose task matches
+    filter: Option<&'a Regex>,
+    by_category: bool,
+    // add the time left or overtime of the last day and its week, and the flex balance
+    balance: bool,
+    rounding: RoundingMode,
+}
+
+/**
+ * Rounding of activity durations from --round, or else the rounding setting
+ */
+fn rounding(config: &Config, args: &Args) -> Result<RoundingMode, Box<dyn Error>> {
+    match args.value("round") {
+        Some(mode) => {
+            RoundingMode::parse(mode).map_err(|e| fail(ErrorKind::Usage, format!("--round: {}", e)))
+        }
+        None => Ok(config.rounding),
+    }
+}
+
+/**
+ * Report of the activities from `from` to `to`, restricted, grouped, rounded, and with extra totals
+ * as `options` say
+ */
+fn render_report(
+    config: &Config,
+    timelog: &Timelog,
+    from: NaiveDate,
+    to: NaiveDate,
+    format: Format,
+    options: &ReportOptions,
+) -> String {
+    let filtered = options.source.is_some() || options.filter.is_some();
+    let keep = report_filter(timelog, options);
+    let timer = Timer::new("aggregating the report");
+    let store_options = timelog.options();
+    let activities = match (filtered, config.day_grouping) {
+        (true, DayGrouping::Calendar) => Activities::new_from_entries_where(
+            timelog.get_date_range(&from, &to),
+            keep,
+            store_options,
+        ),
+        (true, DayGrouping::Shift) => Activities::new_from_shifts_where(
+            &timelog.get_shifts(&from, &to, config.shift_break),
+            keep,
+            store_options,
+        ),
+        #[cfg(feature = "parallel")]
+        (false, DayGrouping::Calendar) => {
+            Activities::new_from_entries_parallel(timelog.get_date_range(&from, &to), store_options)
+        }
+        #[cfg(not(feature = "parallel"))]
+        (false, DayGrouping::Calendar) => {
+            Activities::new_from_entries(timelog.get_date_range(&from, &to), store_options)
+        }
+        (false, DayGrouping::Shift) => Activities::new_from_shifts(
+            &timelog.get_shifts(&from, &to, config.shift_break),
+            store_options,
+        ),
+    };
+    let activities = split_concurrent(activities, config).round(options.rounding);
+    drop(timer);
+
+    let mut report = Report {
+        title: report_title(config, from, to),
+        activities: &activities,
+        extra_totals: Vec::new(),
+        footer: Vec::new(),
+        by_category: options.by_category,
+    };
+    if !config.markers.billable.is_empty() {
+        report.extra_totals.push((
+            tr("Total billable").to_string(),
+            activities.billable_total(),
+        ));
+    }
+    if !config.oncall.is_empty() {
+        report.extra_totals.push((
+            tr("Total with on-call multipliers").to_string(),
+            activities.weighted_work(&config.oncall),
+        ));
+    }
+    if options.filter.is_some() {
+        report.extra_totals.push((
+            tr("Total of matching tasks").to_string(),
+            activities.total_work() + activities.total_slack(),
+        ));
+    }
+    if options.balance {
+        report
+            .extra_totals
+            .extend(Balance::new(timelog, to, config).lines());
+    }
+    let vars = |name: &str| footer_variable(config, timelog, &activities, from, to, name);
+    for (label, expression) in &config.footer {
+        match expression.eval(&vars) {
+            Ok(d) => report.footer.push((label.clone(), d)),
+            Err(e) => eprintln!("WARNING: footer {}: {}", label, e),
+        }
+    }
+    report.render(format)
+}
+
+/**
+ * Which entries count for the report, with --source and --filter
+ */
+fn report_filter<'a>(
+    timelog: &'a Timelog,
+    options: &'a ReportOptions,
+) -> impl Fn(&Entry) -> bool + 'a {
+    move |e| {
+        options.source.is_none_or(|s| timelog.source_of(e) == s)
+            && options.filter.is_none_or(|r| r.is_match(&e.task))
+    }
+}
+
+/**
+ * Value of a variable in a footer expression; any name which is not a built-in one is the total
+ * of a category, where "_" also matches a space
+ */
+fn footer_variable(
+    config: &Config,
+    timelog: &Timelog,
+    activities: &Activities,
+    from: NaiveDate,
+    to: NaiveDate,
+    name: &str,
+) -> Option<Duration> {
+    let this_week = || {
+        let today = timelog.options().today();
+        let monday = today - Duration::days(today.weekday().num_days_from_monday().into());
+        (monday, monday + Duration::days(6))
+    };
+    let workdays = |from: NaiveDate, to: NaiveDate| {
+        from.iter_days()
+            .take_while(|d| *d <= to)
+            .filter(|d| d.weekday().num_days_from_monday() < 5)
+            .count() as i32
+    };
+    Some(match name {
+        "work" => activities.total_work(),
+        "slack" => activities.total_slack(),
+        "billable" => activities.billable_total(),
+        "hours_per_day" => config.hours_per_day,
+        "target" => config.hours_per_day * workdays(from, to),
+        "target_week" => config.week_target(),
+        "work_week" => {
+            let (monday, sunday) = this_week();
+            Activities::new_from_entries(
+                timelog.get_date_range(&monday, &sunday),
+                timelog.options(),
+            )
+            .total_work()
+        }
+        category => match activities.category_total(category) {
+            d if d.is_zero() => activities.category_total(&category.replace('_', " ")),
+            d => d,
+        },
+    })
+}
+
+fn report(config: &Config, args: &[String], format: Option<Format>) -> Result<(), Box<dyn Error>> {
+    let args = Args::parse(
+        args,
+        &["today", "week", "month", "by-category", "balance"],
+        &["date", "from", "to", "source", "filter", "output", "round"],
+    )?;
+    let filter = match args.value("filter") {
+        Some(pattern) => Some(
+            Regex::new(pattern)
+                .map_err(|e| fail(ErrorKind::Usage, format!("invalid --filter: {}", e)))?,
+        ),
+        None => None,
+    };
+    let store_options = config.store_options();
+    let today = store_options.today();
+    let daily = args.flag("week") || args.flag("month");
+    let (from, to) = if daily {
+        if args.flag("week") && args.flag("month") {
+            return Err(fail(
+                ErrorKind::Usage,
+                "--week cannot be combined with --month",
+            ));
+        }
+        if args.value("from").is_some() || args.value("to").is_some() {
+            return Err(fail(
+                ErrorKind::Usage,
+                "--week/--month cannot be combined with --from/--to",
+            ));
+        }
+        // the week or month of --date or --today
+        let (day, _) = date_range(&args, today)?;
+        let spec = if args.flag("week") { "week" } else { "month" };
+        (
+            dateparse::parse_date(&format!("beginning of {}", spec), day)?,
+            dateparse::parse_date(&format!("end of {}", spec), day)?,
+        )
+    } else {
+        date_range(&args, today)?
+    };
+    let output = args.value("output").map(PathBuf::from);
+    let format = output_format(format, output.as_deref())?;
+
+    // the flex balance needs all entries
+    let mut timelog = if args.flag("balance") {
+        Timelog::new_from_default_file(&store_options)?
+    } else {
+        load_since(from, &store_options)?
+    };
+    timelog.load_archives(&from, &to);
+    let options = ReportOptions {
+        source: args.value("source"),
+        filter: filter.as_ref(),
+        by_category: args.flag("by-category"),
+        balance: args.flag("balance"),
+        rounding: rounding(config, &args)?,
+    };
+    let mut rendered = render_report(config, &timelog, from, to, format, &options);
+    let entries = timelog.get_date_range(&from, &to);
+    let keep = report_filter(&timelog, &options);
+    // per-day subtotals before the grand total, like gtimelog's weekly report
+    if daily && format == Format::Text {
+        let days = Activities::new_per_day_where(entries, &keep, &store_options)
+            .map(|a| split_concurrent(a, config).round(options.rounding));
+        rendered = days.format_days(Locale::from_env(), &config.date_format) + &rendered;
+    }
+    if filter.is_some() && format == Format::Text {
+        let days = Activities::new_per_day_where(entries, &keep, &store_options)
+            .map(|a| a.round(options.rounding));
+        rendered = format!(
+            "{}:\n{}\n{}",
+            tr("Matched days"),
+            days.format_totals(Locale::from_env(), &config.date_format),
+            rendered
+        );
+    }
+    match output {
+        Some(path) => fs::write(&path, rendered)
+            .map_err(|e| format!("cannot write {}: {}", path.display(), e))?,
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
+
+/**
+ * Timelog for reading the entries from `from` on; of a large file, only the end gets read
+ */
+fn load_since(from: NaiveDate, options: &store::Options) -> io::Result<Timelog> {
+    // footers can refer to this week, and shifts can start on the day before
+    let today = options.today();
+    let monday = today - Duration::days(today.weekday().num_days_from_monday().into());
+    let since = from.min(monday).pred_opt().unwrap_or(NaiveDate::MIN);
+    Timelog::new_from_default_file_since(since, options)
+}
+
+/**
+ * Format from --format, otherwise from the --output file name, otherwise text
+ */
+fn output_format(format: Option<Format>, output: Option<&Path>) -> Result<Format, Box<dyn Error>> {
+    match (format, output) {
+        (Some(format), _) => Ok(format),
+        (None, Some(path)) => Format::from_path(path).map_err(|e| fail(ErrorKind::Usage, e)),
+        (None, None) => Ok(Format::Text),
+    }
+}
+
+fn entries(config: &Config, args: &[String], format: Option<Format>) -> Result<(), Box<dyn Error>> {
+    let options = config.store_options();
+    let args = Args::parse(args, &["today"], &["date", "from", "to", "output"])?;
+    let (from, to) = date_range(&args, options.today())?;
+    let output = args.value("output").map(PathBuf::from);
+    let format = output_format(format, output.as_deref())?;
+
+    let mut timelog = load_since(from, &options)?;
+    timelog.load_archives(&from, &to);
+    let rendered = output::render_entries(timelog.get_date_range(&from, &to), format, &options)
+        .map_err(|e| fail(ErrorKind::Usage, e))?;
+    match output {
+        Some(path) => fs::write(&path, rendered)
+            .map_err(|e| format!("cannot write {}: {}", path.display(), e))?,
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
+
+fn export(config: &Config, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let options = config.store_options();
+    let args = Args::parse(
+        args,
+        &["today", "ics", "week"],
+        &["date", "from", "to", "mapping", "slack", "output"],
+    )?;
+    if args.flag("ics") {
+        return export_ics(&args, &options);
+    }
+    let (from, to) = date_range(&args, options.today())?;
+    let path = args
+        .value("mapping")
+        .ok_or_else(|| fail(ErrorKind::Usage, "export needs --mapping FILE"))?;
+    let contents = fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+    let mapping = export::Mapping::parse(&contents)
+        .map_err(|e| fail(ErrorKind::Config, format!("{}: {}", path, e)))?;
+
+    let mut timelog = Timelog::new_from_default_file(&options)?;
+    timelog.load_archives(&from, &to);
+    let rows = export::rows(
+        timelog.get_date_range(&from, &to),
+        mapping.granularity,
+        &options,
+    );
+    let rendered = mapping
+        .render(&rows)
+        .map_err(|e| fail(ErrorKind::Config, format!("{}: {}", path, e)))?;
+    match args.value("output") {
+        Some(output) => {
+            fs::write(output, rendered).map_err(|e| format!("cannot write {}: {}", output, e))?
+        }
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
+
+/**
+ * Export the entries of a date range, or with --week of the week of --date/--today, as calendar
+ */
+fn export_ics(args: &Args, options: &store::Options) -> Result<(), Box<dyn Error>> {
+    let (from, to) = if args.flag("week") {
+        if args.value("from").is_some() || args.value("to").is_some() {
+            return Err(fail(
+                ErrorKind::Usage,
+                "--week cannot be combined with --from/--to",
+            ));
+        }
+        let (day, _) = date_range(args, options.today())?;
+        let monday = day - Duration::days(day.weekday().num_days_from_monday().into());
+        (monday, monday + Duration::days(6))
+    } else {
+        date_range(args, options.today())?
+    };
+    let slack = export::ics::Slack::from_name(args.value("slack").unwrap_or("tag"))
+        .map_err(|e| fail(ErrorKind::Usage, e))?;
+
+    let mut timelog = load_since(from, options)?;
+    timelog.load_archives(&from, &to);
+    let rendered = export::ics::calendar(
+        timelog.get_date_range(&from, &to),
+        slack,
+        Utc::now().naive_utc(),
+        options,
+    );
+    match args.value("output") {
+        Some(output) => {
+            fs::write(output, rendered).map_err(|e| format!("cannot write {}: {}", output, e))?
+        }
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
+
+fn add(config: &Config, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let options = config.store_options();
+    check_writable(&options)?;
+    let args = Args::parse(args, &[], &["source", "at"])?;
+    let task = args.positional.join(" ");
+    if task.trim().is_empty() {
+        return Err(fail(ErrorKind::Usage, "missing task description"));
+    }
+
+    let now = Local::now().naive_local();
+    let at = match args.value("at") {
+        Some(at) => {
+            let at = dateparse::parse_datetime(at, options.today(), options.virtual_midnight)
+                .map_err(|e| fail(ErrorKind::Parse, e))?;
+            if at > now {
+                return Err(fail(
+                    ErrorKind::InvalidRange,
+                    format!("{} is in the future", at.format("%Y-%m-%d %H:%M")),
+                ));
+            }
+            Some(at)
+        }
+        None => None,
+    };
+
+    let task_list = load_task_list(config);
+    let stop = at.unwrap_or(now);
+    validation_rules(config, task_list.as_ref())?
+        .check(&task, stop, stop)
+        .map_err(|e| fail(ErrorKind::Parse, e))?;
+    check_task(task_list.as_ref(), &task, &config.markers);
+    let mut timelog = Timelog::new_from_default_file(&options)?;
+    if let Some(source) = args.value("source") {
+        timelog.set_source(source);
+    }
+    match at {
+        Some(at) => timelog.insert_at(at, task),
+        None => timelog.add(task),
+    }
+    timelog.save()?;
+    Ok(())
+}
+
+fn amend(config: &Config, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let options = config.store_options();
+    check_writable(&options)?;
+    let args = Args::parse(args, &[], &[])?;
+    let task = args.positional.join(" ");
+    if task.trim().is_empty() {
+        return Err(fail(ErrorKind::Usage, "missing task description"));
+    }
+
+    let mut timelog = Timelog::new_from_default_file(&options)?;
+    let Some(last) = timelog.get_all().next_back() else {
+        return Err(fail(
+            ErrorKind::Usage,
+            "the timelog has no entries to amend",
+        ));
+    };
+    let task_list = load_task_list(config);
+    validation_rules(config, task_list.as_ref())?
+        .check(&task, last.stop, last.stop)
+        .map_err(|e| fail(ErrorKind::Parse, e))?;
+    check_task(task_list.as_ref(), &task, &config.markers);
+
+    let old = timelog
+        .amend_last(task.clone())
+        .expect("timelog has entries");
+    timelog.save()?;
+    println!(
+        "Changed \"{}\" to \"{}\"",
+        old,
+        Entry {
+            task: task.into(),
+            ..old
+        }
+    );
+    Ok(())
+}
+
+fn rm(config: &Config, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let options = config.store_options();
+    check_writable(&options)?;
+    let args = Args::parse(args, &[], &[])?;
+    if args.positional.is_empty() {
+        return Err(fail(
+            ErrorKind::Usage,
+            "rm needs the time of the entry, like \"14:30\"",
+        ));
+    }
+    let at = dateparse::parse_datetime(
+        &args.positional.join(" "),
+        options.today(),
+        options.virtual_midnight,
+    )
+    .map_err(|e| fail(ErrorKind::Parse, e))?;
+
+    let mut timelog = Timelog::new_from_default_file(&options)?;
+    let Some(entry) = timelog.get_all().find(|e| e.stop == at) else {
+        return Err(fail(
+            ErrorKind::InvalidRange,
+            format!("no entry at {}", at.format("%Y-%m-%d %H:%M")),
+        ));
+    };
+    if !cli::confirm(&format!("Remove \"{}\"?", entry)) {
+        return Err(fail(ErrorKind::Usage, "aborted"));
+    }
+    let entry = timelog.remove(at).expect("entry exists");
+    timelog.save()?;
+    println!("Removed \"{}\"", entry);
+    Ok(())
+}
+
+fn log(config: &Config, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let options = config.store_options();
+    check_writable(&options)?;
+    let args = Args::parse(args, &[], &[])?;
+    let now = Local::now().naive_local();
+    let entry = QuickEntry::parse(&args.positional.join(" "), now, &options)
+        .map_err(|e| fail(ErrorKind::Parse, e))?;
+    let task_list = load_task_list(config);
+    validation_rules(config, task_list.as_ref())?
+        .check(&entry.task, entry.start.unwrap_or(now), now)
+        .map_err(|e| fail(ErrorKind::Parse, e))?;
+    check_task(task_list.as_ref(), &entry.task, &config.markers);
+
+    let mut timelog = Timelog::new_from_default_file(&options)?;
+    match entry.start {
+        Some(start) => timelog.add_since(entry.task, start, now),
+        None => timelog.add(entry.task),
+    }
+    timelog.save()?;
+    Ok(())
+}
+
+/**
+ * Interactively fill the unlogged gaps of a day; returns false if aborted
+ */
+fn backfill_gaps(timelog: &mut Timelog, day: NaiveDate) -> Result<bool, Box<dyn Error>> {
+    let gaps = backfill::find_gaps(timelog.get_day(&day), timelog.options());
+    if gaps.is_empty() {
+        println!("No unlogged gaps on {}", day);
+        return Ok(true);
+    }
+
+    let recent = backfill::recent_tasks(
+        timelog.get_date_range(&(day - Duration::days(14)), &day),
+        9,
+        timelog.options(),
+    );
+    let mut rl = Editor::<()>::new()?;
+
+    for gap in &gaps {
+        let mut suggestions = recent.clone();
+        match (gap.start, gap.end) {
+            (Some(start), Some(end)) => {
+                println!(
+                    "\nUnlogged time {} - {}; enter \"HH:MM task\" for tasks that ended in \
+                     between, just \"task\" for the rest of the gap, or an empty line to skip.",
+                    start.format("%H:%M"),
+                    end.format("%H:%M")
+                );
+                suggestions.extend(backfill::git_suggestions(start, end));
+            }
+            _ => {
+                println!(
+                    "\nNo entries on {}; enter \"HH:MM task\" lines, starting with when you \
+                     arrived, and an empty line when done.",
+                    day
+                );
+                suggestions.extend(backfill::git_suggestions(
+                    day.and_hms_opt(0, 0, 0).unwrap(),
+                    day.and_hms_opt(23, 59, 59).unwrap(),
+                ));
+            }
+        }
+        for (i, task) in suggestions.iter().enumerate() {
+            println!("{:>3}: {}", i + 1, task);
+        }
+
+        let mut after = None;
+        loop {
+            let line = match rl.readline("backfill> ") {
+                Ok(line) => line,
+                Err(ReadlineError::Interrupted) => return Ok(false),
+                Err(ReadlineError::Eof) => return Ok(true),
+                Err(e) => return Err(e.into()),
+            };
+            if line.trim().is_empty() {
+                break;
+            }
+            match backfill::parse_answer(&line, day, gap, after, &suggestions) {
+                Ok(Answer::Fill(task)) => {
+                    backfill::apply_answer(timelog, gap, Answer::Fill(task));
+                    break;
+                }
+                Ok(Answer::Entry(e)) => {
+                    after = Some(e.stop);
+                    backfill::apply_answer(timelog, gap, Answer::Entry(e));
+                }
+                Err(e) => println!("{}", e),
+            }
+        }
+    }
+    Ok(true)
+}
+
+fn backfill(config: &Config, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let options = config.store_options();
+    check_writable(&options)?;
+    let args = Args::parse(args, &[], &[])?;
+    let today = options.today();
+    let day = match args.positional.as_slice() {
+        [] => today,
+        words => dateparse::parse_date(&words.join(" "), today)
+            .map_err(|e| fail(ErrorKind::InvalidRange, e))?,
+    };
+
+    let mut timelog = Timelog::new_from_default_file(&options)?;
+    timelog.set_source("backfill");
+    if backfill_gaps(&mut timelog, day)? {
+        timelog.save()?;
+    } else {
+        println!("Aborted, not saving changes");
+    }
+    Ok(())
+}
+
+fn shift(config: &Config, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let options = config.store_options();
+    check_writable(&options)?;
+    let args = Args::parse(args, &["today"], &["date", "from", "to"])?;
+    let (from, to) = date_range(&args, options.today())?;
+    let offset = match args.positional.as_slice() {
+        [offset] => {
+            let (negative, amount) = match offset.strip_prefix('-') {
+                Some(amount) => (true, amount),
+                None => (false, offset.strip_prefix('+').unwrap_or(offset)),
+            };
+            let d = parse_duration(amount)
+                .ok_or_else(|| fail(ErrorKind::Parse, format!("invalid offset {}", offset)))?;
+            if negative {
+                -d
+            } else {
+                d
+            }
+        }
+        _ => {
+            return Err(fail(
+                ErrorKind::Usage,
+                "shift needs exactly one offset like +15m or -1h",
+            ))
+        }
+    };
+
+    let mut timelog = Timelog::new_from_default_file(&options)?;
+    let question = format!(
+        "Move {} entries from {} to {} by {}?",
+        timelog.get_date_range(&from, &to).len(),
+        from,
+        to,
+        args.positional[0]
+    );
+    if !cli::confirm(&question) {
+        return Err(fail(ErrorKind::Usage, "aborted"));
+    }
+    let count = timelog
+        .shift(&from, &to, offset)
+        .map_err(|e| fail(ErrorKind::InvalidRange, e))?;
+    timelog.save()?;
+    println!("Shifted {} entries", count);
+    Ok(())
+}
+
+fn budget(config: &Config, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let options = config.store_options();
+    let args = Args::parse(args, &["today"], &["date", "from", "to"])?;
+    let today = options.today();
+    let (from, to) = if has_date_range(&args) {
+        date_range(&args, today)?
+    } else {
+        (
+            dateparse::parse_date("beginning of month", today)?,
+            dateparse::parse_date("end of month", today)?,
+        )
+    };
+    if config.budgets.is_empty() {
+        return Err(fail(
+            ErrorKind::Config,
+            "no budgets configured; add a [budgets] section to rtimelogrc",
+        ));
+    }
+
+    let mut timelog = Timelog::new_from_default_file(&options)?;
+    timelog.load_archives(&from, &to);
+    for (i, (category, hours)) in config.budgets.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        print!(
+            "{}",
+            BurnDown::new(&timelog, category, *hours, from, to, today)
+        );
+    }
+    Ok(())
+}
+
+fn leave(config: &Config, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let args = Args::parse(args, &[], &["year"])?;
+    let year = match args.value("year") {
+        Some(y) => y
+            .parse()
+            .map_err(|_| fail(ErrorKind::InvalidRange, format!("invalid year {}", y)))?,
+        None => Local::now().year(),
+    };
+    let begin = NaiveDate::from_ymd_opt(year, 1, 1)
+        .ok_or_else(|| fail(ErrorKind::InvalidRange, "invalid year"))?;
+    let end = NaiveDate::from_ymd_opt(year, 12, 31)
+        .ok_or_else(|| fail(ErrorKind::InvalidRange, "invalid year"))?;
+
+    let options = config.store_options();
+    let mut timelog = Timelog::new_from_default_file(&options)?;
+    timelog.load_archives(&begin, &end);
+    let entries = timelog.get_date_range(&begin, &end);
+    let balance = LeaveBalance::new(
+        entries,
+        &config.vacation_markers,
+        year,
+        config.vacation_days,
+        &options,
+    );
+    print!("{}", balance);
+    println!();
+    print!(
+        "{}",
+        SickSummary::new(
+            entries,
+            &config.sick_markers,
+            year,
+            config.hours_per_day,
+            &options
+        )
+    );
+    Ok(())
+}
+
+fn toil(config: &Config, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let options = config.store_options();
+    let args = Args::parse(args, &["today"], &["date", "from", "to"])?;
+    let today = options.today();
+    let (from, to) = if has_date_range(&args) {
+        date_range(&args, today)?
+    } else {
+        (dateparse::parse_date("beginning of year", today)?, today)
+    };
+
+    let mut timelog = Timelog::new_from_default_file(&options)?;
+    timelog.load_archives(&from, &to);
+    print!("{}", Ledger::new(&timelog, from, to, config));
+    Ok(())
+}
+
+fn config_command(config: &Config, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let args = Args::parse(args, &[], &[])?;
+    let path = Config::get_file();
+    match args
+        .positional
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()[..]
+    {
+        ["list"] | [] => {
+            for (key, value) in config.to_pairs() {
+                println!("{} = {}", key, config::format_value(&value));
+            }
+        }
+        ["get", key] => match config.get(key) {
+            Some(value) => println!("{}", value),
+            None => return Err(fail(ErrorKind::Config, format!("unknown key {}", key))),
+        },
+        ["set", key, value] => {
+            // validate first
+            config
+                .clone()
+                .set(key, value)
+                .map_err(|e| fail(ErrorKind::Config, e))?;
+            let contents = match fs::read_to_string(&path) {
+                Ok(c) => c,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+                Err(e) => return Err(e.into()),
+            };
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, config::set_in_contents(&contents, key, value))?;
+        }
+        ["edit"] => {
+            run_editor(&path);
+            // shows warnings for invalid settings
+            Config::new_from_file(&path).map_err(|e| fail(ErrorKind::Config, e))?;
+        }
+        _ => {
+            return Err(fail(
+                ErrorKind::Usage,
+                "usage: config [list | get KEY | set KEY VALUE | edit]",
+            ))
+        }
+    }
+    Ok(())
+}
+
+fn tasks(config: &Config, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let args = Args::parse(args, &[], &[])?;
+    let prefix = match args.positional.as_slice() {
+        [] => "",
+        [prefix] => prefix.as_str(),
+        _ => return Err(fail(ErrorKind::Usage, "usage: tasks [PREFIX]")),
+    };
+    let timelog = Timelog::new_from_default_file(&config.store_options())?;
+    let list = completion_list(&timelog, load_task_list(config).as_ref());
+    let mut tasks = list.complete(prefix);
+    tasks.sort_unstable();
+    for task in tasks {
+        println!("{}", task);
+    }
+    Ok(())
+}
+
+fn doctor(config: &Config) -> Result<(), Box<dyn Error>> {
+    let path = config.store_options().file();
+    let mut findings = doctor::check_config(&Config::get_file());
+    findings.extend(doctor::check_timelog_file(&path));
+    findings.push(doctor::check_timezone());
+    match fs::read(&path) {
+        Ok(raw) => findings.extend(doctor::check_data(
+            &String::from_utf8_lossy(&raw),
+            Local::now().naive_local(),
+            &config.store_options(),
+        )),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+        Err(e) => return Err(e.into()),
+    }
+
+    for f in &findings {
+        println!("{}", f);
+    }
+    let problems = findings
+        .iter()
+        .filter(|f| f.status == doctor::Status::Problem)
+        .count();
+    if problems > 0 {
+        return Err(format!("{} problems found", problems).into());
+    }
+    Ok(())
+}
+
+fn migrate_gtimelog(config: &Config, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let options = config.store_options();
+    check_writable(&options)?;
+    let args = Args::parse(args, &[], &["from"])?;
+    let source = match args.value("from") {
+        Some(dir) => PathBuf::from(dir),
+        None => migrate::data_dirs()
+            .into_iter()
+            .find(|d| d.join("timelog.txt").is_file())
+            .ok_or("no gtimelog data found; use --from DIR")?,
+    };
+    let source_log = source.join("timelog.txt");
+    let raw = fs::read(&source_log).map_err(|e| format!("{}: {}", source_log.display(), e))?;
+    let before = migrate::totals(&String::from_utf8_lossy(&raw), &options);
+
+    let target_log = options.file();
+    let target = target_log.parent().unwrap();
+    fs::create_dir_all(target)?;
+    for name in migrate::DATA_FILES {
+        let (from, to) = (source.join(name), target.join(name));
+        if !from.is_file() {
+            continue;
+        }
+        if to.is_file() && fs::canonicalize(&from)? == fs::canonicalize(&to)? {
+            println!("{} is already used by rtimelog", from.display());
+            continue;
+        }
+        if to.is_file()
+            && fs::read(&from)? != fs::read(&to)?
+            && !cli::confirm(&format!("Overwrite {}?", to.display()))
+        {
+            return Err(fail(ErrorKind::Usage, "aborted"));
+        }
+        println!("Copying {} to {}", from.display(), to.display());
+        if !store::is_dry_run() {
+            let _lock = (to == target_log)
+                .then(|| store::lock_timelog(&to))
+                .transpose()?;
+            store::write_atomically(&to, fs::read(&from)?)?;
+        }
+    }
+
+    if !store::is_dry_run() {
+        let after = migrate::totals(&String::from_utf8_lossy(&fs::read(&target_log)?), &options);
+        if after != before {
+            return Err(format!(
+                "{} differs from {} after copying",
+                target_log.display(),
+                source_log.display()
+            )
+            .into());
+        }
+    }
+    let (count, work, slack) = before;
+    println!(
+        "{} entries, total work: {} h {} min, total slacking: {} h {} min",
+        count,
+        work.num_hours(),
+        work.num_minutes() % 60,
+        slack.num_hours(),
+        slack.num_minutes() % 60
+    );
+
+    let mut candidates = vec![source.join("gtimelogrc")];
+    candidates.extend(migrate::config_files());
+    let Some(rc) = candidates.iter().find(|f| f.is_file()) else {
+        return Ok(());
+    };
+    let (settings, skipped) = migrate::translate_gtimelogrc(&fs::read_to_string(rc)?);
+    for key in skipped {
+        println!("Skipping unsupported setting {} in {}", key, rc.display());
+    }
+    if settings.is_empty() {
+        return Ok(());
+    }
+    let path = Config::get_file();
+    let mut contents = match fs::read_to_string(&path) {
+        Ok(c) => c,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let mut validated = config.clone();
+    for (key, value) in settings {
+        validated
+            .set(&key, &value)
+            .map_err(|e| fail(ErrorKind::Config, format!("{}: {}", rc.display(), e)))?;
+        println!("Setting {} = {}", key, value);
+        contents = config::set_in_contents(&contents, &key, &value);
+    }
+    if !store::is_dry_run() {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, contents)?;
+    }
+    Ok(())
+}
+
+/**
+ * Entries of a timelog file, for commands which work on other files than the default one
+ */
+fn read_entries(path: &str) -> Result<Vec<Entry>, Box<dyn Error>> {
+    let raw = fs::read(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+    let (entries, errors) = store::parse_entries(&String::from_utf8_lossy(&raw));
+    for e in &errors {
+        eprintln!("{} {}: {}", tr("WARNING: timelog"), path, e);
+    }
+    Ok(entries)
+}
+
+/**
+ * Timelog files of the team command's arguments; directories contribute their *.txt files
+ */
+fn team_files(paths: &[String]) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut files = Vec::new();
+    for path in paths.iter().map(PathBuf::from) {
+        if !path.is_dir() {
+            files.push(path);
+            continue;
+        }
+        let mut in_dir: Vec<PathBuf> = fs::read_dir(&path)
+            .map_err(|e| format!("cannot read {}: {}", path.display(), e))?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.is_file() && p.extension().is_some_and(|e| e == "txt"))
+            .collect();
+        in_dir.sort();
+        files.extend(in_dir);
+    }
+    Ok(files)
+}
+
+fn team(config: &Config, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let options = config.store_options();
+    let args = Args::parse(args, &["today", "files"], &["date", "from", "to"])?;
+    let today = options.today();
+    let (from, to) = if has_date_range(&args) {
+        date_range(&args, today)?
+    } else {
+        let monday = today - Duration::days(today.weekday().num_days_from_monday().into());
+        (monday, monday + Duration::days(6))
+    };
+    let files = team_files(&args.positional)?;
+    if files.is_empty() {
+        return Err(fail(
+            ErrorKind::Usage,
+            "team needs timelog files or a directory of them",
+        ));
+    }
+
+    let mut members = Vec::new();
+    for path in files {
+        let name = path.file_stem().map_or_else(
+            || path.display().to_string(),
+            |s| s.to_string_lossy().into_owned(),
+        );
+        members.push((name, read_entries(&path.to_string_lossy())?));
+    }
+    print!("{}", TeamReport::new(&members, from, to, &options));
+    Ok(())
+}
+
+fn diff(config: &Config, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let args = Args::parse(args, &[], &[])?;
+    let [old, new] = args.positional.as_slice() else {
+        return Err(fail(ErrorKind::Usage, "diff needs two timelog files"));
+    };
+    let options = config.store_options();
+    for day in semdiff::compare(&read_entries(old)?, &read_entries(new)?, &options) {
+        print!("{}", day);
+    }
+    Ok(())
+}
+
+/**
+ * Merge the changes between BASE and REMOTE into LOCAL, like a git merge driver
+ */
+fn merge(config: &Config, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let options = config.store_options();
+    check_writable(&options)?;
+    let args = Args::parse(args, &[], &[])?;
+    let [base, local, remote] = args.positional.as_slice() else {
+        return Err(fail(
+            ErrorKind::Usage,
+            "merge needs the base, local, and remote timelog files",
+        ));
+    };
+    let local_path = PathBuf::from(local);
+    if !local_path.is_file() {
+        return Err(format!("cannot read {}: not a file", local).into());
+    }
+    let mut timelog = Timelog::new_from_file(&local_path, &options)?;
+    let local_entries = timelog
+        .get_date_range(&NaiveDate::MIN, &NaiveDate::MAX)
+        .to_vec();
+    let merge = semdiff::merge(&read_entries(base)?, &local_entries, &read_entries(remote)?);
+
+    let question = format!(
+        "Add {} and remove {} entries of {}?",
+        merge.add.len(),
+        merge.remove.len(),
+        local
+    );
+    let changes = !merge.add.is_empty() || !merge.remove.is_empty();
+    if changes && !cli::confirm(&question) {
+        return Err(fail(ErrorKind::Usage, "aborted"));
+    }
+    for e in &merge.remove {
+        timelog.remove_entry(e);
+    }
+    for e in &merge.add {
+        timelog.insert_at(e.stop, e.task.to_string());
+    }
+    timelog.save()?;
+    println!(
+        "merged {}: {} added, {} removed",
+        local,
+        merge.add.len(),
+        merge.remove.len()
+    );
+
+    for (l, r) in &merge.conflicts {
+        eprintln!(
+            "conflict at {}: kept local \"{}\", remote has \"{}\"",
+            l.stop.format("%Y-%m-%d %H:%M"),
+            l.task,
+            r.task
+        );
+    }
+    if !merge.conflicts.is_empty() {
+        return Err(format!("{} conflicts", merge.conflicts.len()).into());
+    }
+    Ok(())
+}
+
+/**
+ * Timelog file for snapshots; they don't make sense for stdin
+ */
+fn snapshot_file(options: &store::Options) -> Result<PathBuf, Box<dyn Error>> {
+    let file = options.file();
+    if store::is_stdin(&file) {
+        return Err(fail(ErrorKind::Usage, "snapshots need a timelog file"));
+    }
+    Ok(file)
+}
+
+fn snapshot(config: &Config, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let args = Args::parse(args, &["list"], &[])?;
+    let file = snapshot_file(&config.store_options())?;
+    let dir = snapshot::dir(&file);
+    if args.flag("list") {
+        for s in snapshot::list(&dir)? {
+            println!("{}  {}", s.id, s.label);
+        }
+        return Ok(());
+    }
+
+    let contents =
+        fs::read_to_string(&file).map_err(|e| format!("cannot read {}: {}", file.display(), e))?;
+    let id = snapshot::create(
+        &dir,
+        &contents,
+        &args.positional.join(" "),
+        Local::now().naive_local(),
+    )?;
+    println!("Created snapshot {}", id);
+    Ok(())
+}
+
+fn restore(config: &Config, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let options = config.store_options();
+    check_writable(&options)?;
+    let args = Args::parse(args, &[], &[])?;
+    let [id] = args.positional.as_slice() else {
+        return Err(fail(ErrorKind::Usage, "restore needs a snapshot id"));
+    };
+    let file = snapshot_file(&options)?;
+    let dir = snapshot::dir(&file);
+    let contents = snapshot::read(&dir, id)?;
+    let current = fs::read_to_string(&file).unwrap_or_default();
+    if store::is_dry_run() {
+        store::print_dry_run(&file, &rtimelog::diff::unified(&current, &contents));
+        return Ok(());
+    }
+
+    let question = format!(
+        "Replace the {} entries of {} with the {} entries of snapshot {}?",
+        store::parse_entries(&current).0.len(),
+        file.display(),
+        store::parse_entries(&contents).0.len(),
+        id
+    );
+    if !cli::confirm(&question) {
+        return Err(fail(ErrorKind::Usage, "aborted"));
+    }
+    if options.hash_chain {
+        return Err(fail(
+            ErrorKind::ReadOnly,
+            "the timelog is hash-chained, it cannot be replaced",
+        ));
+    }
+    let _lock = store::lock_timelog(&file)?;
+    if fs::read_to_string(&file).unwrap_or_default() != current {
+        return Err(fail(
+            ErrorKind::Lock,
+            format!("{} was changed meanwhile, please try again", file.display()),
+        ));
+    }
+    // keep the current state, in case the wrong snapshot was restored
+    let backup = snapshot::create(
+        &dir,
+        &current,
+        &format!("before restoring {}", id),
+        Local::now().naive_local(),
+    )?;
+    store::write_atomically(&file, contents)?;
+    println!(
+        "Restored snapshot {}; the previous state is snapshot {}",
+        id, backup
+    );
+    Ok(())
+}
+
+fn verify(config: &Config) -> Result<(), Box<dyn Error>> {
+    let options = config.store_options();
+    let file = options.file();
+    let chain_file = file.with_file_name(chain::FILE);
+    let contents = fs::read_to_string(&chain_file)
+        .map_err(|e| format!("cannot read {}: {}", chain_file.display(), e))?;
+    let timelog = Timelog::new_from_file(&file, &options)?;
+    let count = chain::verify(
+        &contents,
+        timelog.get_date_range(&NaiveDate::MIN, &NaiveDate::MAX),
+    )?;
+    println!(
+        "OK: {} chained entries, last hash {}",
+        count,
+        chain::last_hash(&contents)
+    );
+    Ok(())
+}
+
+/**
+ * Send last week's report if it is due and was not sent yet
+ */
+fn send_due_report(config: &Config, channels: &[Channel]) -> Result<(), Box<dyn Error>> {
+    let now = Local::now().naive_local();
+    let (from, to, due) = schedule::last_week(now, config.schedule_day, config.schedule_time);
+    let store_options = config.store_options();
+    let sent_file = store_options.file().with_file_name(schedule::SENT_FILE);
+    let mut sent = fs::read_to_string(&sent_file).unwrap_or_default();
+    let pending: Vec<Channel> = channels
+        .iter()
+        .copied()
+        .filter(|c| !schedule::was_sent(&sent, &from, *c))
+        .collect();
+    if now < due || pending.is_empty() {
+        return Ok(());
+    }
+
+    let mut timelog = Timelog::new_from_default_file(&store_options)?;
+    timelog.load_archives(&from, &to);
+    let options = ReportOptions {
+        rounding: config.rounding,
+        ..Default::default()
+    };
+    for channel in pending {
+        let (recipient, body) = match channel {
+            Channel::Email => (
+                config.schedule_email.as_deref().unwrap(),
+                render_report(config, &timelog, from, to, Format::Text, &options),
+            ),
+            Channel::Webhook => (
+                config.schedule_webhook.as_deref().unwrap(),
+                render_report(config, &timelog, from, to, Format::Json, &options),
+            ),
+        };
+        if store::is_dry_run() {
+            println!(
+                "Dry run: would send by {} to {}:",
+                channel.name(),
+                recipient
+            );
+            print!("{}", body);
+            continue;
+        }
+        match channel {
+            Channel::Email => send_mail(config, recipient, &report_title(config, from, to), &body)?,
+            Channel::Webhook => schedule::post_webhook(recipient, &body)?,
+        }
+        // record right away, so that a failure of the next channel does not repeat this one
+        sent += &schedule::sent_record(&from, channel);
+        sent.push('\n');
+        fs::write(&sent_file, &sent)?;
+        println!(
+            "Sent the report of {} - {} by {} to {}",
+            from,
+            to,
+            channel.name(),
+            recipient
+        );
+    }
+    Ok(())
+}
+
+fn schedule(config: &Config, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let args = Args::parse(args, &["once"], &[])?;
+    let mut channels = Vec::new();
+    if config.schedule_email.is_some() {
+        channels.push(Channel::Email);
+    }
+    if config.schedule_webhook.is_some() {
+        channels.push(Channel::Webhook);
+    }
+    if channels.is_empty() {
+        return Err(fail(
+            ErrorKind::Config,
+            "no report recipient configured; set email or webhook in the [schedule] section",
+        ));
+    }
+
+    if args.flag("once") {
+        return send_due_report(config, &channels);
+    }
+    loop {
+        if let Err(e) = send_due_report(config, &channels) {
+            eprintln!("WARNING: {}", e);
+        }
+        // wake up at least hourly, to retry failures and survive suspend and clock changes
+        let now = Local::now().naive_local();
+        let next = schedule::next_due(now, config.schedule_day, config.schedule_time);
+        let sleep = (next - now).clamp(Duration::minutes(1), Duration::hours(1));
+        thread::sleep(sleep.to_std().unwrap());
+    }
+}
+
+/**
+ * Mail the report of a day or week in gtimelog's format
+ */
+fn send_report(config: &Config, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let options = config.store_options();
+    let args = Args::parse(args, &["today", "week"], &["date", "to", "round"])?;
+    let day = match (args.flag("today"), args.value("date")) {
+        (true, Some(_)) => {
+            return Err(fail(
+                ErrorKind::Usage,
+                "--today cannot be combined with --date",
+            ))
+        }
+        (_, Some(date)) => dateparse::parse_date(date, options.today())
+            .map_err(|e| fail(ErrorKind::InvalidRange, e))?,
+        _ => options.today(),
+    };
+    let (period, from, to) = if args.flag("week") {
+        let monday = day - Duration::days(day.weekday().num_days_from_monday().into());
+        (
+            output::Period::Week(monday),
+            monday,
+            monday + Duration::days(6),
+        )
+    } else {
+        (output::Period::Day(day), day, day)
+    };
+    let who = config
+        .name
+        .as_ref()
+        .or(config.email.as_ref())
+        .ok_or_else(|| {
+            fail(
+                ErrorKind::Config,
+                "reports need your name; set name (or email) in rtimelogrc",
+            )
+        })?;
+    let recipient = args
+        .value("to")
+        .or(config.mail_to.as_deref())
+        .ok_or_else(|| {
+            fail(
+                ErrorKind::Config,
+                "no report recipient; set to in the [mail] section or pass --to",
+            )
+        })?;
+
+    let mut timelog = load_since(from, &options)?;
+    timelog.load_archives(&from, &to);
+    let activities = split_concurrent(
+        Activities::new_from_entries(timelog.get_date_range(&from, &to), &options),
+        config,
+    )
+    .round(rounding(config, &args)?);
+    let (subject, body) = output::render_email(&activities, period, who);
+    if store::is_dry_run() {
+        println!("Dry run: would send to {}:", recipient);
+        println!("Subject: {}\n", subject);
+        print!("{}", body);
+        return Ok(());
+    }
+    send_mail(config, recipient, &subject, &body)?;
+    println!("Sent \"{}\" to {}", subject, recipient);
+    Ok(())
+}
+
+/**
+ * Send a mail through the SMTP server or with the sendmail command of the [mail] section
+ */
+fn send_mail(
+    config: &Config,
+    recipient: &str,
+    subject: &str,
+    body: &str,
+) -> Result<(), Box<dyn Error>> {
+    let sender = config.sender();
+    match &config.mail_smtp {
+        Some(server) => {
+            let address = config.email.as_deref().ok_or_else(|| {
+                fail(
+                    ErrorKind::Config,
+                    "sending by SMTP needs your email address; set email in rtimelogrc",
+                )
+            })?;
+            schedule::send_smtp(
+                server,
+                config.mail_smtp_user.as_deref(),
+                address,
+                sender.as_deref(),
+                recipient,
+                subject,
+                body,
+            )?
+        }
+        None => schedule::send_email_with(
+            &config.mail_sendmail,
+            sender.as_deref(),
+            recipient,
+            subject,
+            body,
+        )?,
+    }
+    Ok(())
+}
+
+/**
+ * One-line status; this only reads the end of the timelog, as status bars call it often
+ */
+fn widget(config: &Config) -> Result<(), Box<dyn Error>> {
+    let options = config.store_options();
+    let now = Local::now().naive_local();
+    let today = options.logical_date(now);
+    let file = options.file();
+    let raw = if store::is_stdin(&file) {
+        None
+    } else {
+        match store::read_tail(&file, today) {
+            Ok(raw) => Some(raw),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Some(String::new()),
+            Err(e) => return Err(format!("cannot read {}: {}", file.display(), e).into()),
+        }
+    };
+    let entries = match raw {
+        Some(raw) => store::parse_entries(&raw).0,
+        None => Timelog::new_from_default_file(&options)?
+            .get_today()
+            .to_vec(),
+    };
+    let first = entries.partition_point(|e| options.day(e) < today);
+    println!(
+        "{}",
+        widget::render(&entries[first..], now, config.hours_per_day, &options)
+    );
+    Ok(())
+}
+
+fn import(config: &Config, args: &[String]) -> Result<(), Box<dyn Error>> {
+    let options = config.store_options();
+    check_writable(&options)?;
+    let args = Args::parse(args, &[], &["input-locale"])?;
+    let [path] = args.positional.as_slice() else {
+        return Err(fail(ErrorKind::Usage, "import needs a CSV file"));
+    };
+    let locale = match args.value("input-locale") {
+        Some(name) => Locale::from_name(name),
+        None => Locale::from_env(),
+    };
+    let contents = fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+    let rows = import::parse_csv(&contents, locale)
+        .map_err(|e| fail(ErrorKind::Parse, format!("{}: {}", path, e)))?;
+
+    // refuse the whole file if any row breaks the rules, so that it can be fixed and imported again
+    let rules = validation_rules(config, load_task_list(config).as_ref())?;
+    let now = Local::now().naive_local();
+    let invalid: Vec<String> = rows
+        .iter()
+        .filter_map(|row| rules.check(&row.task, row.start, now).err())
+        .collect();
+    if !invalid.is_empty() {
+        for e in &invalid {
+            eprintln!("{}", e);
+        }
+        return Err(fail(
+            ErrorKind::Parse,
+            format!(
+                "{}: {} rows break the validation rules",
+                path,
+                invalid.len()
+            ),
+        ));
+    }
+
+    let mut timelog = Timelog::new_from_default_file(&options)?;
+    timelog.set_source("import");
+    let (added, skipped) = import::apply(&mut timelog, &rows);
+    for s in &skipped {
+        eprintln!("{} {}", tr("Skipped"), s);
+    }
+    timelog.save()?;
+    println!("Imported {} of {} rows", added, rows.len());
+    Ok(())
+}
+
+fn usage() {
+    println!(
+        "{}",
+        tr("Usage: rtimelog [COMMAND]
+
+Without a command, run the interactive mode.
+
+Commands:
+  ui
+      full-screen mode with today's activities and the time since the last
+      entry, updated every minute; type a task and press enter to add it,
+      arrow keys go through recent tasks, ^D or :q quits
+  report [--today | --date DATE | --from DATE --to DATE] [--week | --month]
+         [--by-category] [--balance] [--source SOURCE] [--filter REGEX]
+         [--round MODE] [--output FILE]
+      show the activities of a day or a date range (default: today); --week
+      and --month show each day of that week or month, and the total;
+      --by-category lists the tasks under their categories; --balance adds
+      the time left (or overtime) of the last day and its week, and the flex
+      time balance; with
+      --source, only count entries created that way, like \"manual\"; with
+      --filter, only count tasks which match REGEX, like \"^joe:\", and list
+      the days with such tasks and their total; --round nearest:15, up:6, or
+      none rounds each activity's duration (default: rounding setting); with
+      --output, write it to FILE as .txt, .md, .html, .csv, or .json
+  entries [--today | --date DATE | --from DATE --to DATE] [--output FILE]
+      dump the raw entries of a day or a date range (default: today), with
+      ISO-8601 start and stop times and durations in minutes
+  export --mapping MAPPING [--today | --date DATE | --from DATE --to DATE]
+         [--output FILE]
+      export work for an external timesheet system, as CSV or JSON with the
+      columns and project/activity codes defined in the MAPPING file
+  export --ics [--week] [--today | --date DATE | --from DATE --to DATE]
+         [--slack keep|tag|skip] [--output FILE]
+      export the entries as iCalendar events, to show them in a calendar;
+      --week exports the whole week, slack entries are tagged (default),
+      kept like work, or skipped
+  add [--source SOURCE] TASK
+      add an entry for a task that you just finished; tools which add entries
+      automatically should name themselves with --source
+  add --at DATETIME [--source SOURCE] TASK
+      add an entry for a task that ended at DATETIME, like \"14:30\" (today) or
+      \"yesterday 17:00\", in between the existing entries
+  amend TASK
+      change the task of the last entry, e.g. to fix a typo
+  rm DATETIME
+      remove the entry at DATETIME, like \"14:30\" or \"2022-06-10 14:30\"
+  log TEXT
+      add an entry; TEXT can start with a duration (\"30m code review\") or end
+      with a start time (\"billing since 14:00\") to backdate it
+  backfill [DATE]
+      interactively fill unlogged gaps (\"**\" entries) or an empty day
+  budget [--from DATE --to DATE]
+      burn-down of the configured category budgets (default: this month)
+  leave [--year YEAR]
+      vacation and sick days of a year (default: this year)
+  toil [--from DATE --to DATE]
+      overtime and time off in lieu balance (default: this year until today)
+  config [list | get KEY | set KEY VALUE | edit]
+      show or change settings, like \"config set hours_per_day 7.5\"
+  tasks [PREFIX]
+      list the known tasks (starting with PREFIX): the ones in the timelog,
+      tasks.txt, and the shared task list; the interactive mode completes
+      them with Tab
+  doctor
+      check configuration, files, time zone, and data for problems
+  team [--today | --date DATE | --from DATE --to DATE] [--files] FILE... | DIR
+      combined report of several people's timelogs (default: this week):
+      totals per person and category, and a timesheet; a directory stands for
+      all *.txt files in it, named after the file
+  diff OLD NEW
+      compare the entries of two timelog files, and the resulting daily totals
+  merge BASE LOCAL REMOTE
+      merge the changes from BASE to REMOTE into LOCAL; usable as git merge driver
+  snapshot [LABEL] | snapshot --list
+      store a compressed copy of the timelog, or list the stored ones
+  restore ID
+      replace the timelog with a snapshot (after taking a snapshot of it)
+  verify
+      check that the entries match their hash chain (hash_chain setting)
+  import [--input-locale LOCALE] FILE.csv
+      add the tasks from a CSV file with start, end or duration, and task columns
+  widget
+      print a one-line status of the current task and the day, for status bars
+  schedule [--once]
+      send last week's report by email or webhook when due ([schedule] section)
+  send-report [--week] [--today | --date DATE] [--to ADDRESS] [--round MODE]
+      mail the report of a day (default: today) or with --week of its week in
+      gtimelog's format, to --to or the address in the [mail] section
+  migrate-gtimelog [--from DIR]
+      copy timelog.txt and tasks.txt from gtimelog, and translate its gtimelogrc
+  shift [--today | --date DATE | --from DATE --to DATE] OFFSET
+      move all entries of a day or date range by an OFFSET like +15m or -1h
+
+Options for all commands:
+  --format text|markdown|html|csv|json
+      format of the output of report and entries (default: text, or from the
+      --output file name), and of error messages (json or text)
+  --dry-run
+      show the changes to the timelog as a diff instead of saving them
+  --yes
+      don't ask for confirmation of destructive changes like shift or rm
+  --read-only
+      never change the timelog, e.g. for looking at someone else's or an archive
+  --file FILE
+      use FILE instead of the default timelog; \"-\" reads it from stdin, for
+      pipelines like \"gpg -d timelog.txt.gpg | rtimelog --file - report\"
+  --config FILE
+      read the configuration from FILE instead of $RTIMELOG_CONFIG or
+      ~/.config/rtimelog/rtimelogrc
+  --set KEY=VALUE
+      override a configuration key for this run, like --set hours_per_day=6;
+      can be given several times; $RTIMELOG_KEY (like $RTIMELOG_HOURS_PER_DAY)
+      overrides keys as well
+  -v, -vv, -vvv
+      print diagnostic messages and timings on stderr; $RTIMELOG_LOG can also
+      be set to info, debug, or trace
+
+Exit codes: 1 general error, 2 invalid command line, 3 invalid input,
+4 timelog is locked, 5 configuration error, 6 invalid date or date range,
+7 change refused in read-only mode.
+
+DATE can be YYYY-MM-DD or a phrase like \"yesterday\", \"last monday\",
+\"2 weeks ago\", or \"beginning of month\".")
+    );
+}
+
+/**
+ * Configuration from --config FILE or the default file, the environment, and --set KEY=VALUE options
+ */
+fn load_config(args: &mut Vec<String>) -> Result<Config, error::Error> {
+    if let Some(path) = cli::take_option(args, "config")? {
+        config::set_file(PathBuf::from(path));
+    }
+    let mut settings = Vec::new();
+    while let Some(setting) = cli::take_option(args, "set")? {
+        settings.push(setting);
+    }
+    let mut config =
+        Config::new_from_default_file().map_err(|e| error::Error::new(ErrorKind::Config, e))?;
+    for setting in settings {
+        config
+            .apply_setting(&setting)
+            .map_err(|e| error::Error::new(ErrorKind::Config, format!("--set {}", e)))?;
+    }
+    Ok(config)
+}
+
+/**
+ * Load the configuration with the --file, --config, and --set options, and run the command
+ */
+fn configure_and_run(
+    args: &mut Vec<String>,
+    read_only: bool,
+    format: Option<Format>,
+) -> Result<(), Box<dyn Error>> {
+    let file = cli::take_option(args, "file")?;
+    let mut config = load_config(args)?;
+    // stdin cannot be written back
+    config.read_only |= read_only || file.as_deref() == Some(store::STDIN);
+    if let Some(file) = file {
+        config.file = Some(PathBuf::from(file));
+    }
+    debug!("{:?}", config);
+    run(&config, args, format)
+}
+
+fn run(config: &Config, args: &[String], format: Option<Format>) -> Result<(), Box<dyn Error>> {
+    match args.first().map(String::as_str) {
+        None if store::is_stdin(&config.store_options().file()) => Err(fail(
+            ErrorKind::Usage,
+            "the interactive mode cannot read the timelog from stdin",
+        )),
+        None => interactive(config),
+        Some("ui") => ui(config),
+        Some("report") => report(config, &args[1..], format),
+        Some("entries") => entries(config, &args[1..], format),
+        Some("export") => export(config, &args[1..]),
+        Some("add") => add(config, &args[1..]),
+        Some("amend") => amend(config, &args[1..]),
+        Some("rm") => rm(config, &args[1..]),
+        Some("log") => log(config, &args[1..]),
+        Some("backfill") => backfill(config, &args[1..]),
+        Some("shift") => shift(config, &args[1..]),
+        Some("budget") => budget(config, &args[1..]),
+        Some("leave") => leave(config, &args[1..]),
+        Some("toil") => toil(config, &args[1..]),
+        Some("config") => config_command(config, &args[1..]),
+        Some("tasks") => tasks(config, &args[1..]),
+        Some("doctor") => doctor(config),
+        Some("team") => team(config, &args[1..]),
+        Some("diff") => diff(config, &args[1..]),
+        Some("merge") => merge(config, &args[1..]),
+        Some("snapshot") => snapshot(config, &args[1..]),
+        Some("restore") => restore(config, &args[1..]),
+        Some("verify") => verify(config),
+        Some("schedule") => schedule(config, &args[1..]),
+        Some("send-report") => send_report(config, &args[1..]),
+        Some("widget") => widget(config),
+        Some("import") => import(config, &args[1..]),
+        Some("migrate-gtimelog") => migrate_gtimelog(config, &args[1..]),
+        Some("-h" | "--help" | "help") => {
+            usage();
+            Ok(())
+        }
+        Some(cmd) => Err(fail(
+            ErrorKind::Usage,
+            format!("unknown command {}; see --help", cmd),
+        )),
+    }
+}
+
+fn main() {
+    i18n::init_from_env();
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    trace::init(cli::take_verbosity(&mut args));
+    let dry_run = cli::take_flag(&mut args, "dry-run");
+    store::set_dry_run(dry_run);
+    let yes = cli::take_flag(&mut args, "yes");
+    // nothing to confirm when not saving anything
+    cli::set_assume_yes(yes || dry_run);
+    let read_only = cli::take_flag(&mut args, "read-only");
+    // also the output format of report and entries; taken first, so that all errors use it
+    let format = match cli::take_option(&mut args, "format") {
+        Ok(f) => f
+            .map(|f| Format::from_name(&f))
+            .transpose()
+            .map_err(|e| fail(ErrorKind::Usage, e)),
+        Err(e) => Err(e.into()),
+    };
+    let json = matches!(format, Ok(Some(Format::Json)));
+    let result = format.and_then(|format| configure_and_run(&mut args, read_only, format));
+
+    if let Err(e) = result {
+        let e = match e.downcast::<error::Error>() {
+            Ok(e) => *e,
+            Err(e) => {
+                let kind = match e.downcast_ref::<io::Error>() {
+                    Some(e) if e.kind() == io::ErrorKind::WouldBlock => ErrorKind::Lock,
+                    _ => ErrorKind::Other,
+                };
+                error::Error::new(kind, e.to_string())
+            }
+        };
+        if json {
+            println!("{}", e.to_json());
+        } else {
+            eprintln!("rtimelog: {}", e);
+        }
+        process::exit(e.kind.exit_code());
+    }
+}
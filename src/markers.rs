@@ -0,0 +1,117 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+/**
+ * How an entry counts, according to the prefix of its task
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Work,
+    Slack,
+    // not counted at all, like gtimelog's "***" entries
+    Ignored,
+}
+
+/**
+ * Task prefixes which mark slack time, ignored entries, and billable work
+ *
+ * An empty marker is disabled.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Markers {
+    pub slack: String,
+    pub ignore: String,
+    pub billable: String,
+}
+
+impl Default for Markers {
+    fn default() -> Markers {
+        Markers {
+            slack: "**".to_string(),
+            ignore: "***".to_string(),
+            billable: String::new(),
+        }
+    }
+}
+
+fn has_prefix(task: &str, marker: &str) -> bool {
+    !marker.is_empty() && task.starts_with(marker)
+}
+
+impl Markers {
+    pub fn kind(&self, task: &str) -> Kind {
+        // check the longer marker first, the default ones are prefixes of each other
+        let mut checks = [(&self.slack, Kind::Slack), (&self.ignore, Kind::Ignored)];
+        checks.sort_by_key(|(m, _)| std::cmp::Reverse(m.len()));
+        checks
+            .iter()
+            .find(|(m, _)| has_prefix(task, m))
+            .map_or(Kind::Work, |(_, k)| *k)
+    }
+
+    /**
+     * Task for unnamed slack, like for time that has not been logged yet
+     */
+    pub fn placeholder(&self) -> &str {
+        if self.slack.is_empty() {
+            "**"
+        } else {
+            &self.slack
+        }
+    }
+
+    pub fn is_billable(&self, task: &str) -> bool {
+        has_prefix(task, &self.billable) && self.kind(task) == Kind::Work
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kind() {
+        let m = Markers::default();
+        assert_eq!(m.kind("code"), Kind::Work);
+        assert_eq!(m.kind("**"), Kind::Slack);
+        assert_eq!(m.kind("** lunch"), Kind::Slack);
+        assert_eq!(m.kind("*** vacation"), Kind::Ignored);
+        assert_eq!(m.kind("a ** b"), Kind::Work);
+        assert!(!m.is_billable("code"));
+        assert_eq!(m.placeholder(), "**");
+
+        let m = Markers {
+            slack: "~".to_string(),
+            ignore: String::new(),
+            billable: "!".to_string(),
+        };
+        assert_eq!(m.kind("** lunch"), Kind::Work);
+        assert_eq!(m.kind("~lunch"), Kind::Slack);
+        assert_eq!(m.kind("*** vacation"), Kind::Work);
+        assert!(m.is_billable("!acme: code"));
+        assert!(!m.is_billable("acme: code"));
+        assert_eq!(m.placeholder(), "~");
+
+        // billable slack does not make sense
+        let m = Markers {
+            slack: "!!".to_string(),
+            ignore: "***".to_string(),
+            billable: "!".to_string(),
+        };
+        assert!(m.is_billable("!acme: code"));
+        assert_eq!(m.kind("!!coffee"), Kind::Slack);
+        assert!(!m.is_billable("!!coffee"));
+    }
+}
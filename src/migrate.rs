@@ -0,0 +1,169 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate chrono;
+extern crate dirs;
+
+use std::env;
+use std::path::PathBuf;
+
+use chrono::Duration;
+
+use crate::activity::Activities;
+use crate::store::{parse_entries, Options};
+
+/// files in gtimelog's data directory which rtimelog uses as well
+pub const DATA_FILES: [&str; 2] = ["timelog.txt", "tasks.txt"];
+
+/// gtimelogrc settings and their rtimelogrc equivalents
+const SETTINGS: [(&str, &str); 7] = [
+    ("name", "name"),
+    ("sender", "email"),
+    ("list-email", "mail.to"),
+    ("mailer", "mail.sendmail"),
+    ("hours", "hours_per_day"),
+    ("task_list_url", "task_list_url"),
+    ("virtual_midnight", "virtual_midnight"),
+];
+
+/**
+ * Possible gtimelog data directories, in the order in which gtimelog prefers them
+ */
+pub fn data_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(home) = dirs::home_dir() {
+        dirs.push(home.join(".gtimelog"));
+    }
+    match env::var_os("XDG_DATA_HOME") {
+        Some(val) => dirs.push(PathBuf::from(val).join("gtimelog")),
+        None => dirs.extend(dirs::data_dir().map(|d| d.join("gtimelog"))),
+    }
+    dirs
+}
+
+/**
+ * Possible gtimelogrc paths, in the order in which gtimelog prefers them
+ */
+pub fn config_files() -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    if let Some(home) = dirs::home_dir() {
+        files.push(home.join(".gtimelog").join("gtimelogrc"));
+    }
+    match env::var_os("XDG_CONFIG_HOME") {
+        Some(val) => files.push(PathBuf::from(val).join("gtimelog").join("gtimelogrc")),
+        None => files.extend(dirs::config_dir().map(|d| d.join("gtimelog").join("gtimelogrc"))),
+    }
+    files
+}
+
+/**
+ * Translate the [gtimelog] section of a gtimelogrc into rtimelogrc (key, value) settings
+ *
+ * Also returns the keys which have no rtimelog equivalent.
+ */
+pub fn translate_gtimelogrc(contents: &str) -> (Vec<(String, String)>, Vec<String>) {
+    let mut settings = Vec::new();
+    let mut skipped = Vec::new();
+    let mut in_section = false;
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            in_section = line[1..line.len() - 1].trim() == "gtimelog";
+            continue;
+        }
+        if !in_section {
+            continue;
+        }
+        // INI allows both key = value and key: value
+        let Some(pos) = line.find(['=', ':']) else {
+            continue;
+        };
+        let key = line[..pos].trim();
+        let value = line[pos + 1..].trim();
+        match SETTINGS.iter().find(|(g, _)| *g == key) {
+            Some((_, r)) => settings.push((r.to_string(), value.to_string())),
+            None => skipped.push(key.to_string()),
+        }
+    }
+    (settings, skipped)
+}
+
+/**
+ * Summary of a timelog for comparing it before and after migrating: number of entries, total
+ * work, and total slacking
+ */
+pub fn totals(raw: &str, options: &Options) -> (usize, Duration, Duration) {
+    let (entries, _) = parse_entries(raw);
+    let activities = Activities::new_from_entries(&entries, options);
+    (
+        entries.len(),
+        activities.total_work(),
+        activities.total_slack(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translate_gtimelogrc() {
+        let (settings, skipped) = translate_gtimelogrc(
+            "
+[gtimelog]
+# my settings
+list-email = activity@example.com
+sender = joe@example.com
+editor = gvim
+hours = 7.5
+virtual_midnight: 06:00
+
+[other]
+hours = 3
+",
+        );
+        assert_eq!(
+            settings,
+            vec![
+                ("mail.to".to_string(), "activity@example.com".to_string()),
+                ("email".to_string(), "joe@example.com".to_string()),
+                ("hours_per_day".to_string(), "7.5".to_string()),
+                ("virtual_midnight".to_string(), "06:00".to_string())
+            ]
+        );
+        assert_eq!(skipped, vec!["editor"]);
+
+        assert_eq!(translate_gtimelogrc(""), (vec![], vec![]));
+    }
+
+    #[test]
+    fn test_totals() {
+        let (count, work, slack) = totals(
+            "
+2022-06-10 08:00: arrived
+2022-06-10 10:00: code
+2022-06-10 10:30: ** lunch
+2022-06-10 11:00: review
+",
+            &Options::default(),
+        );
+        assert_eq!(count, 4);
+        assert_eq!(work, Duration::minutes(150));
+        assert_eq!(slack, Duration::minutes(30));
+    }
+}
@@ -0,0 +1,683 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate chrono;
+
+use std::fmt::Write as _; // import without risk of name clashing
+use std::path::Path;
+
+use chrono::{Datelike, Duration, NaiveDate};
+
+use crate::activity::{category, format_duration, Activities, Activity};
+use crate::i18n::tr;
+use crate::json;
+use crate::markers::Kind;
+use crate::store::{Entry, Options};
+
+const ISO_TIME: &str = "%Y-%m-%dT%H:%M:%S";
+
+/**
+ * File format of a report
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Markdown,
+    Html,
+    Csv,
+    Json,
+}
+
+impl Format {
+    /**
+     * Format for a file name's extension, like "report.html"
+     */
+    pub fn from_path(path: &Path) -> Result<Format, String> {
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_lowercase);
+        match ext.as_deref() {
+            Some("txt") => Ok(Format::Text),
+            Some("md" | "markdown") => Ok(Format::Markdown),
+            Some("html" | "htm") => Ok(Format::Html),
+            Some("csv") => Ok(Format::Csv),
+            Some("json") => Ok(Format::Json),
+            _ => Err(format!(
+                "cannot tell the format of {}; use .txt, .md, .html, .csv, or .json",
+                path.display()
+            )),
+        }
+    }
+
+    /**
+     * Format for a --format option like "json"
+     */
+    pub fn from_name(name: &str) -> Result<Format, String> {
+        match name.to_lowercase().as_str() {
+            "text" | "txt" => Ok(Format::Text),
+            "md" | "markdown" => Ok(Format::Markdown),
+            "html" => Ok(Format::Html),
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            _ => Err(format!(
+                "unknown format {}; use text, markdown, html, csv, or json",
+                name
+            )),
+        }
+    }
+}
+
+/**
+ * Raw entries with their start (the previous entry on the same day, if any) and duration
+ *
+ * This supports text (the timelog format), CSV, and JSON, with ISO-8601 times and minutes.
+ */
+pub fn render_entries(
+    entries: &[Entry],
+    format: Format,
+    options: &Options,
+) -> Result<String, String> {
+    let mut out = String::new();
+    let intervals = entries.iter().enumerate().map(|(i, e)| {
+        let start = i
+            .checked_sub(1)
+            .map(|p| entries[p].stop)
+            .filter(|s| options.logical_date(*s) == options.day(e));
+        (start, e)
+    });
+    match format {
+        Format::Text => {
+            for e in entries {
+                writeln!(out, "{}", e).unwrap();
+            }
+        }
+        Format::Csv => {
+            out.push_str("start,stop,minutes,task\n");
+            for (start, e) in intervals {
+                writeln!(
+                    out,
+                    "{},{},{},{}",
+                    start.map_or(String::new(), |s| s.format(ISO_TIME).to_string()),
+                    e.stop.format(ISO_TIME),
+                    start.map_or(String::new(), |s| (e.stop - s).num_minutes().to_string()),
+                    csv_field(&e.task)
+                )
+                .unwrap();
+            }
+        }
+        Format::Json => {
+            let objects: Vec<String> = intervals
+                .map(|(start, e)| {
+                    format!(
+                        "{{\"start\": {}, \"stop\": {}, \"minutes\": {}, \"task\": {}}}",
+                        start.map_or("null".to_string(), |s| json::datetime(&s)),
+                        json::datetime(&e.stop),
+                        start.map_or("null".to_string(), |s| (e.stop - s)
+                            .num_minutes()
+                            .to_string()),
+                        json::string(&e.task)
+                    )
+                })
+                .collect();
+            writeln!(out, "[{}]", objects.join(",\n ")).unwrap();
+        }
+        Format::Markdown | Format::Html => {
+            return Err("entries can be written as text, csv, or json".to_string())
+        }
+    }
+    Ok(out)
+}
+
+/**
+ * Report with a title, activities, and totals
+ */
+pub struct Report<'a> {
+    pub title: String,
+    pub activities: &'a Activities,
+    // additional totals after work and slacking, like with on-call multipliers
+    pub extra_totals: Vec<(String, Duration)>,
+    // computed lines at the end, from the [footer] configuration
+    pub footer: Vec<(String, Duration)>,
+    // list the tasks under their categories, with category totals
+    pub by_category: bool,
+}
+
+impl Report<'_> {
+    fn totals(&self) -> Vec<(String, Duration)> {
+        let mut totals = vec![
+            (
+                tr("Total work done").to_string(),
+                self.activities.total_work(),
+            ),
+            (
+                tr("Total slacking").to_string(),
+                self.activities.total_slack(),
+            ),
+        ];
+        totals.extend(self.extra_totals.iter().cloned());
+        totals
+    }
+
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Text => self.text(),
+            Format::Markdown => self.markdown(),
+            Format::Html => self.html(),
+            Format::Csv => self.csv(),
+            Format::Json => self.json(),
+        }
+    }
+
+    fn text(&self) -> String {
+        let mut out = if self.by_category {
+            format!("{}:\n{}", self.title, self.activities.grouped_by_category())
+        } else {
+            format!("{}:\n{}", self.title, self.activities)
+        };
+        for (name, d) in self.extra_totals.iter().chain(&self.footer) {
+            writeln!(out, "{}: {}", name, format_duration(*d)).unwrap();
+        }
+        out
+    }
+
+    fn markdown(&self) -> String {
+        let mut out = format!("# {}\n\n| Time | Activity |\n|---:|---|\n", self.title);
+        for a in self.activities.iter() {
+            let name = a.name().replace('|', "\\|").replace('*', "\\*");
+            writeln!(out, "| {} | {} |", format_duration(a.duration()), name).unwrap();
+        }
+        out.push('\n');
+        for (name, d) in self.totals() {
+            writeln!(out, "**{}:** {}  ", name, format_duration(d)).unwrap();
+        }
+        if !self.footer.is_empty() {
+            out.push('\n');
+        }
+        for (name, d) in &self.footer {
+            writeln!(out, "{}: {}  ", name, format_duration(*d)).unwrap();
+        }
+        out
+    }
+
+    fn html(&self) -> String {
+        let title = html_escape(&self.title);
+        let mut out = format!(
+            "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{}</title></head>\n<body>\n<h1>{}</h1>\n<table>\n",
+            title, title
+        );
+        for a in self.activities.iter() {
+            writeln!(
+                out,
+                "<tr><td>{}</td><td>{}</td></tr>",
+                format_duration(a.duration()),
+                html_escape(a.name())
+            )
+            .unwrap();
+        }
+        out.push_str("</table>\n<p>\n");
+        for (name, d) in self.totals() {
+            writeln!(out, "{}: {}<br>", html_escape(&name), format_duration(d)).unwrap();
+        }
+        out.push_str("</p>\n");
+        if !self.footer.is_empty() {
+            out.push_str("<footer>\n");
+            for (name, d) in &self.footer {
+                writeln!(out, "{}: {}<br>", html_escape(name), format_duration(*d)).unwrap();
+            }
+            out.push_str("</footer>\n");
+        }
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+
+    fn csv(&self) -> String {
+        let mut out = String::from("activity,minutes\n");
+        for a in self.activities.iter() {
+            writeln!(
+                out,
+                "{},{}",
+                csv_field(a.name()),
+                a.duration().num_minutes()
+            )
+            .unwrap();
+        }
+        // footer lines as extra rows, so that spreadsheets show them below the activities
+        for (name, d) in &self.footer {
+            writeln!(out, "{},{}", csv_field(name), d.num_minutes()).unwrap();
+        }
+        out
+    }
+
+    fn json(&self) -> String {
+        let activities: Vec<String> = self.activities.iter().map(|a| a.to_json()).collect();
+        let named = |items: &[(String, Duration)]| {
+            items
+                .iter()
+                .map(|(name, d)| {
+                    format!(
+                        "{{\"name\": {}, \"minutes\": {}}}",
+                        json::string(name),
+                        d.num_minutes()
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join(", ")
+        };
+        let mut out = format!(
+            "{{\"title\": {}, \"activities\": [{}], \"totals\": [{}]",
+            json::string(&self.title),
+            activities.join(", "),
+            named(&self.totals())
+        );
+        if self.by_category {
+            let categories: Vec<(String, Duration)> = self
+                .activities
+                .grouped_by_category()
+                .groups
+                .into_iter()
+                .map(|g| (g.name, g.duration))
+                .collect();
+            write!(out, ", \"categories\": [{}]", named(&categories)).unwrap();
+        }
+        if !self.footer.is_empty() {
+            write!(out, ", \"footer\": [{}]", named(&self.footer)).unwrap();
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+/**
+ * Period of a mailed report
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    Day(NaiveDate),
+    // the Monday of the week
+    Week(NaiveDate),
+}
+
+/**
+ * Report mail in gtimelog's format: the subject, like "Weekly report for Joe (week 23)", and a
+ * body with the work by category, and the totals of the categories at the end
+ *
+ * Like in gtimelog, this is not translated, as it usually goes to a shared list.
+ */
+pub fn render_email(activities: &Activities, period: Period, who: &str) -> (String, String) {
+    let (subject, period_name) = match period {
+        Period::Day(day) => (
+            format!(
+                "{} report for {} ({}, week {:02})",
+                day.format("%Y-%m-%d"),
+                who,
+                day.format("%a"),
+                day.iso_week().week()
+            ),
+            "day",
+        ),
+        Period::Week(monday) => (
+            format!(
+                "Weekly report for {} (week {:02})",
+                who,
+                monday.iso_week().week()
+            ),
+            "week",
+        ),
+    };
+
+    let markers = &activities.options().markers;
+    let mut work: Vec<&Activity> = activities
+        .iter()
+        .filter(|a| markers.kind(a.name()) == Kind::Work && !a.duration().is_zero())
+        .collect();
+    if work.is_empty() {
+        return (subject, format!("No work done this {}.\n", period_name));
+    }
+    work.sort_by(|a, b| a.name().cmp(b.name()));
+    let mut categories: Vec<(Option<&str>, Vec<&Activity>)> = Vec::new();
+    for a in work {
+        let cat = category(a.name());
+        match categories.iter_mut().find(|(c, _)| *c == cat) {
+            Some((_, tasks)) => tasks.push(a),
+            None => categories.push((cat, vec![a])),
+        }
+    }
+    // tasks without a category last
+    categories.sort_by_key(|(c, _)| (c.is_none(), *c));
+
+    let mut body = format!("{:>70}\n", "time");
+    let mut totals = Vec::new();
+    for (cat, tasks) in &categories {
+        let name = cat.unwrap_or("(none)");
+        writeln!(body, "{}:", name).unwrap();
+        let mut total = Duration::zero();
+        for a in tasks {
+            let task = match cat {
+                Some(_) => a.name().split_once(':').unwrap().1.trim(),
+                None => a.name(),
+            };
+            writeln!(body, "  {:<61}  {:>5}", task, format_short(a.duration())).unwrap();
+            total += a.duration();
+        }
+        writeln!(body, "{}", "-".repeat(70)).unwrap();
+        writeln!(body, "{:>70}\n", format_short(total)).unwrap();
+        totals.push((name, total));
+    }
+    writeln!(
+        body,
+        "Total work done this {}: {}\n",
+        period_name,
+        format_long(activities.total_work())
+    )
+    .unwrap();
+    body.push_str("By category:\n\n");
+    for (name, total) in totals {
+        writeln!(body, "{:<62}  {}", name, format_long(total)).unwrap();
+    }
+    (subject, body)
+}
+
+/**
+ * Duration like "1:05", as in gtimelog's reports
+ */
+fn format_short(d: Duration) -> String {
+    format!("{}:{:02}", d.num_hours(), d.num_minutes() % 60)
+}
+
+/**
+ * Duration like "1 hour 5 min", "2 hours", or "45 min", as in gtimelog's reports
+ */
+fn format_long(d: Duration) -> String {
+    let (h, m) = (d.num_hours(), d.num_minutes() % 60);
+    let hours = format!("{} hour{}", h, if h == 1 { "" } else { "s" });
+    match (h, m) {
+        (0, m) => format!("{} min", m),
+        (_, 0) => hours,
+        (_, m) => format!("{} {} min", hours, m),
+    }
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/**
+ * Quote a CSV field if necessary (RFC 4180)
+ */
+pub fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::Timelog;
+
+    fn activities() -> Activities {
+        let tl = Timelog::new_from_string(
+            "
+2022-06-10 08:00: arrived
+2022-06-10 10:00: code, \"review\"
+2022-06-10 10:30: ** lunch
+2022-06-10 11:00: <email>
+",
+        );
+        let entries: Vec<Entry> = tl.get_all().cloned().collect();
+        Activities::new_from_entries(&entries, &Options::default())
+    }
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(Format::from_path(Path::new("r.HTML")), Ok(Format::Html));
+        assert_eq!(
+            Format::from_path(Path::new("/tmp/r.md")),
+            Ok(Format::Markdown)
+        );
+        assert_eq!(Format::from_path(Path::new("r.csv")), Ok(Format::Csv));
+        assert_eq!(Format::from_path(Path::new("r.json")), Ok(Format::Json));
+        assert_eq!(Format::from_path(Path::new("r.txt")), Ok(Format::Text));
+        assert!(Format::from_path(Path::new("r.pdf")).is_err());
+        assert!(Format::from_path(Path::new("report")).is_err());
+    }
+
+    #[test]
+    fn test_render() {
+        let a = activities();
+        let report = Report {
+            title: "Work done on 2022-06-10".to_string(),
+            activities: &a,
+            extra_totals: vec![("Weighted".to_string(), Duration::minutes(90))],
+            footer: Vec::new(),
+            by_category: false,
+        };
+
+        assert_eq!(
+            report.render(Format::Text),
+            "Work done on 2022-06-10:
+ 2 h  0 min: code, \"review\"
+ 0 h 30 min: ** lunch
+ 0 h 30 min: <email>
+-------
+Total work done: 2 h 30 min
+Total slacking: 0 h 30 min
+Weighted: 1 h 30 min
+"
+        );
+        assert_eq!(
+            report.render(Format::Csv),
+            "activity,minutes\n\"code, \"\"review\"\"\",120\n** lunch,30\n<email>,30\n"
+        );
+        assert_eq!(
+            report.render(Format::Json),
+            "{\"title\": \"Work done on 2022-06-10\", \"activities\": [\
+             {\"name\": \"code, \\\"review\\\"\", \"minutes\": 120}, \
+             {\"name\": \"** lunch\", \"minutes\": 30}, \
+             {\"name\": \"<email>\", \"minutes\": 30}], \"totals\": [\
+             {\"name\": \"Total work done\", \"minutes\": 150}, \
+             {\"name\": \"Total slacking\", \"minutes\": 30}, \
+             {\"name\": \"Weighted\", \"minutes\": 90}]}\n"
+        );
+        let md = report.render(Format::Markdown);
+        assert!(md.starts_with("# Work done on 2022-06-10\n"));
+        assert!(md.contains("| 2 h 0 min | code, \"review\" |\n"));
+        assert!(md.contains("| 0 h 30 min | \\*\\* lunch |\n"));
+        assert!(md.contains("**Total work done:** 2 h 30 min  \n"));
+        let html = report.render(Format::Html);
+        assert!(html.contains("<tr><td>0 h 30 min</td><td>&lt;email&gt;</td></tr>"));
+        assert!(html.contains("<title>Work done on 2022-06-10</title>"));
+        assert!(!html.contains("<footer>"));
+    }
+
+    #[test]
+    fn test_render_by_category() {
+        let tl = Timelog::new_from_string(
+            "2022-06-10 07:00: arrived\n2022-06-10 08:00: acme: code\n2022-06-10 08:30: acme: review\n",
+        );
+        let entries: Vec<Entry> = tl.get_all().cloned().collect();
+        let a = Activities::new_from_entries(&entries, &Options::default());
+        let report = Report {
+            title: "Work".to_string(),
+            activities: &a,
+            extra_totals: Vec::new(),
+            footer: Vec::new(),
+            by_category: true,
+        };
+        assert!(report.render(Format::Text).starts_with(
+            "Work:\n 1 h 30 min: acme\n     1 h  0 min: code\n     0 h 30 min: review\n"
+        ));
+        assert!(report
+            .render(Format::Json)
+            .ends_with(", \"categories\": [{\"name\": \"acme\", \"minutes\": 90}]}\n"));
+    }
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(Format::from_name("JSON"), Ok(Format::Json));
+        assert_eq!(Format::from_name("text"), Ok(Format::Text));
+        assert!(Format::from_name("pdf").is_err());
+    }
+
+    #[test]
+    fn test_render_entries() {
+        let tl = Timelog::new_from_string(
+            "2022-06-09 17:00: yesterday\n2022-06-10 08:00: arrived\n2022-06-10 09:30: code, \"review\"\n",
+        );
+        let entries: Vec<Entry> = tl.get_all().cloned().collect();
+        assert_eq!(
+            render_entries(&entries, Format::Text, &Options::default()),
+            Ok("2022-06-09 17:00: yesterday\n2022-06-10 08:00: arrived\n2022-06-10 09:30: code, \"review\"\n".to_string())
+        );
+        assert_eq!(
+            render_entries(&entries, Format::Csv, &Options::default()),
+            Ok("start,stop,minutes,task
+,2022-06-09T17:00:00,,yesterday
+,2022-06-10T08:00:00,,arrived
+2022-06-10T08:00:00,2022-06-10T09:30:00,90,\"code, \"\"review\"\"\"
+"
+            .to_string())
+        );
+        assert_eq!(
+            render_entries(&entries[1..], Format::Json, &Options::default()),
+            Ok("[{\"start\": null, \"stop\": \"2022-06-10T08:00:00\", \"minutes\": null, \"task\": \"arrived\"},
+ {\"start\": \"2022-06-10T08:00:00\", \"stop\": \"2022-06-10T09:30:00\", \"minutes\": 90, \"task\": \"code, \\\"review\\\"\"}]
+"
+            .to_string())
+        );
+        assert!(render_entries(&entries, Format::Html, &Options::default()).is_err());
+
+        assert_eq!(
+            entries[2].to_json(),
+            "{\"stop\": \"2022-06-10T09:30:00\", \"task\": \"code, \\\"review\\\"\"}"
+        );
+        assert_eq!(
+            Activities::new_from_entries(&entries, &Options::default()).to_json(),
+            "{\"activities\": [{\"name\": \"code, \\\"review\\\"\", \"minutes\": 90}], \"work_minutes\": 90, \"slack_minutes\": 0}"
+        );
+    }
+
+    #[test]
+    fn test_render_footer() {
+        let a = activities();
+        let report = Report {
+            title: "Work done on 2022-06-10".to_string(),
+            activities: &a,
+            extra_totals: Vec::new(),
+            footer: vec![
+                ("Billable".to_string(), Duration::minutes(120)),
+                ("Remaining".to_string(), Duration::minutes(-75)),
+            ],
+            by_category: false,
+        };
+
+        assert!(report.render(Format::Text).ends_with(
+            "Total slacking: 0 h 30 min\nBillable: 2 h 0 min\nRemaining: -1 h 15 min\n"
+        ));
+        assert!(report
+            .render(Format::Csv)
+            .ends_with("<email>,30\nBillable,120\nRemaining,-75\n"));
+        assert!(report.render(Format::Json).ends_with(
+            "\"footer\": [{\"name\": \"Billable\", \"minutes\": 120}, \
+             {\"name\": \"Remaining\", \"minutes\": -75}]}\n"
+        ));
+        assert!(report
+            .render(Format::Markdown)
+            .ends_with("\nBillable: 2 h 0 min  \nRemaining: -1 h 15 min  \n"));
+        assert!(report.render(Format::Html).contains(
+            "<footer>\nBillable: 2 h 0 min<br>\nRemaining: -1 h 15 min<br>\n</footer>\n"
+        ));
+    }
+
+    #[test]
+    fn test_render_email() {
+        let tl = Timelog::new_from_string(
+            "
+2022-06-06 08:00: arrived
+2022-06-06 09:00: internal: planning
+2022-06-06 11:30: acme: code
+2022-06-06 12:00: ** lunch
+2022-06-06 13:05: acme: review
+2022-06-06 14:05: email
+",
+        );
+        let entries: Vec<Entry> = tl.get_all().cloned().collect();
+        let monday = NaiveDate::from_ymd_opt(2022, 6, 6).unwrap();
+        let (subject, body) = render_email(
+            &Activities::new_from_entries(&entries, &Options::default()),
+            Period::Week(monday),
+            "Joe",
+        );
+        assert_eq!(subject, "Weekly report for Joe (week 23)");
+        let time = " ".repeat(66);
+        let dashes = "-".repeat(70);
+        let pad = |s: &str| format!("{:<62}", s);
+        let task = |s: &str| format!("  {:<61}", s);
+        assert_eq!(
+            body,
+            format!(
+                "{time}time
+acme:
+{code}   2:30
+{review}   1:05
+{dashes}
+{}3:35
+
+internal:
+{planning}   1:00
+{dashes}
+{}1:00
+
+(none):
+{email}   1:00
+{dashes}
+{}1:00
+
+Total work done this week: 5 hours 35 min
+
+By category:
+
+{}  3 hours 35 min
+{}  1 hour
+{}  1 hour
+",
+                " ".repeat(66),
+                " ".repeat(66),
+                " ".repeat(66),
+                pad("acme"),
+                pad("internal"),
+                pad("(none)"),
+                code = task("code"),
+                review = task("review"),
+                planning = task("planning"),
+                email = task("email"),
+            )
+        );
+
+        let (subject, body) = render_email(
+            &Activities::new_from_entries(&[], &Options::default()),
+            Period::Day(monday),
+            "Joe",
+        );
+        assert_eq!(subject, "2022-06-06 report for Joe (Mon, week 23)");
+        assert_eq!(body, "No work done this day.\n");
+    }
+}
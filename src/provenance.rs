@@ -0,0 +1,85 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+use std::fmt::Write as _; // import without risk of name clashing
+
+use crate::intern::Symbol;
+use crate::store::{parse_entry, Entry};
+
+/**
+ * How entries were created is recorded in this file next to the timelog
+ *
+ * gtimelog must be able to read the timelog, so there is no room for metadata in it.
+ */
+pub const FILE: &str = "provenance.txt";
+
+/// source of entries without a record: typed in, or written with gtimelog or an editor
+pub const MANUAL: &str = "manual";
+
+/**
+ * Parse the provenance file: lines of "source<TAB>YYYY-MM-DD HH:MM: task"
+ *
+ * Invalid lines are ignored.
+ */
+pub fn parse(contents: &str) -> HashMap<Entry, Symbol> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let (source, entry) = line.split_once('\t')?;
+            let source = source.trim();
+            if source.is_empty() {
+                return None;
+            }
+            Some((parse_entry(entry)?, source.into()))
+        })
+        .collect()
+}
+
+/**
+ * Format the provenance file, in time order
+ */
+pub fn format(sources: &HashMap<Entry, Symbol>) -> String {
+    let mut records: Vec<_> = sources.iter().collect();
+    records.sort_by_key(|(e, _)| (e.stop, e.task.as_str()));
+    let mut output = String::new();
+    for (entry, source) in records {
+        writeln!(output, "{}\t{}", source, entry).unwrap();
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_format() {
+        let contents = "backfill\t2022-06-10 10:00: code
+garbage
+\t2022-06-10 11:00: nobody
+import:toggl\t2022-06-09 08:00: review
+";
+        let sources = parse(contents);
+        assert_eq!(sources.len(), 2);
+        let entry = parse_entry("2022-06-10 10:00: code").unwrap();
+        assert_eq!(sources[&entry], "backfill");
+        assert_eq!(
+            format(&sources),
+            "import:toggl\t2022-06-09 08:00: review\nbackfill\t2022-06-10 10:00: code\n"
+        );
+        assert!(parse("").is_empty());
+    }
+}
@@ -0,0 +1,252 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate chrono;
+
+use chrono::{Duration, NaiveDateTime, NaiveTime};
+
+use crate::store::Options;
+
+/**
+ * Free text entry with an optional hint when the task started
+ *
+ * Understood forms are a leading duration ("30m code review", "1h15m meeting", "1.5h email",
+ * "45 min support") or a trailing start time ("worked on billing since 14:00", "... from 9:30").
+ */
+#[derive(Debug, PartialEq, Eq)]
+pub struct QuickEntry {
+    pub task: String,
+    pub start: Option<NaiveDateTime>,
+}
+
+impl QuickEntry {
+    pub fn parse(text: &str, now: NaiveDateTime, options: &Options) -> Result<QuickEntry, String> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let (start, task_words) = match (leading_duration(&words), words.as_slice()) {
+            (Some((minutes, n)), _) => {
+                let start = Duration::try_minutes(minutes.round() as i64)
+                    .and_then(|d| now.checked_sub_signed(d))
+                    .ok_or_else(|| format!("duration {} is too long", words[..n].join(" ")))?;
+                (Some(start), &words[n..])
+            }
+            (None, [rest @ .., "since" | "from", time]) => {
+                let time = NaiveTime::parse_from_str(time, "%H:%M")
+                    .map_err(|_| format!("invalid start time {}", time))?;
+                // times before the virtual midnight belong to the next calendar day
+                let mut start = options.logical_date(now).and_time(time);
+                if time < options.virtual_midnight {
+                    start += Duration::days(1);
+                }
+                if start > now {
+                    return Err(format!(
+                        "start time {} is in the future",
+                        time.format("%H:%M")
+                    ));
+                }
+                (Some(start), rest)
+            }
+            (None, all) => (None, all),
+        };
+
+        if task_words.is_empty() {
+            return Err("missing task description".to_string());
+        }
+        Ok(QuickEntry {
+            task: task_words.join(" "),
+            start,
+        })
+    }
+}
+
+/**
+ * Minutes of the duration at the start of `words` ("30m", or "30 min" as two words) and the number
+ * of words it took
+ */
+fn leading_duration(words: &[&str]) -> Option<(f64, usize)> {
+    if let [amount, unit, ..] = words {
+        if let Some(minutes) = parse_minutes(&format!("{}{}", amount, unit)) {
+            return Some((minutes, 2));
+        }
+    }
+    parse_minutes(words.first()?).map(|minutes| (minutes, 1))
+}
+
+/**
+ * Parse durations like "30m", "30min", "2h", "1h30m", "1.5h", "2hours"; None if invalid or out of
+ * range
+ */
+pub fn parse_duration(s: &str) -> Option<Duration> {
+    Duration::try_minutes(parse_minutes(s)?.round() as i64)
+}
+
+/**
+ * Positive number of minutes of a duration like parse_duration() accepts
+ */
+fn parse_minutes(s: &str) -> Option<f64> {
+    let s = s.to_lowercase();
+    let mut minutes = 0.0;
+    let mut rest = s.as_str();
+    let mut any = false;
+
+    while !rest.is_empty() {
+        let num_len = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        let number: f64 = rest[..num_len].parse().ok()?;
+        rest = &rest[num_len..];
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let factor = match &rest[..unit_len] {
+            "h" | "hr" | "hrs" | "hour" | "hours" => 60.0,
+            "m" | "min" | "mins" | "minute" | "minutes" => 1.0,
+            _ => return None,
+        };
+        rest = &rest[unit_len..];
+        minutes += number * factor;
+        any = true;
+    }
+
+    if any && minutes > 0.0 {
+        Some(minutes)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn now() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2022, 6, 10)
+            .unwrap()
+            .and_hms_opt(16, 0, 0)
+            .unwrap()
+    }
+
+    fn at(h: u32, m: u32) -> Option<NaiveDateTime> {
+        Some(now().date().and_hms_opt(h, m, 0).unwrap())
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30m"), Some(Duration::minutes(30)));
+        assert_eq!(parse_duration("30min"), Some(Duration::minutes(30)));
+        assert_eq!(parse_duration("2h"), Some(Duration::hours(2)));
+        assert_eq!(parse_duration("2Hours"), Some(Duration::hours(2)));
+        assert_eq!(parse_duration("1h30m"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration("1.5h"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration("0m"), None);
+        assert_eq!(parse_duration("30"), None);
+        assert_eq!(parse_duration("h"), None);
+        assert_eq!(parse_duration("code"), None);
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("3x"), None);
+        assert_eq!(parse_duration("99999999999999999h"), None);
+        assert_eq!(parse_duration(&format!("{}h", "9".repeat(400))), None);
+    }
+
+    #[test]
+    fn test_parse_duration_prefix() {
+        assert_eq!(
+            QuickEntry::parse("30m code review", now(), &Options::default()),
+            Ok(QuickEntry {
+                task: "code review".to_string(),
+                start: at(15, 30)
+            })
+        );
+        assert_eq!(
+            QuickEntry::parse("45 min customer joe: support", now(), &Options::default()),
+            Ok(QuickEntry {
+                task: "customer joe: support".to_string(),
+                start: at(15, 15)
+            })
+        );
+        assert_eq!(
+            QuickEntry::parse("1h15m  meeting", now(), &Options::default())
+                .unwrap()
+                .start,
+            at(14, 45)
+        );
+    }
+
+    #[test]
+    fn test_parse_since() {
+        assert_eq!(
+            QuickEntry::parse("worked on billing since 14:00", now(), &Options::default()),
+            Ok(QuickEntry {
+                task: "worked on billing".to_string(),
+                start: at(14, 0)
+            })
+        );
+        assert_eq!(
+            QuickEntry::parse("email from 9:30", now(), &Options::default())
+                .unwrap()
+                .start,
+            at(9, 30)
+        );
+        assert_eq!(
+            QuickEntry::parse("email since 17:00", now(), &Options::default()),
+            Err("start time 17:00 is in the future".to_string())
+        );
+        assert_eq!(
+            QuickEntry::parse("email since noon", now(), &Options::default()),
+            Err("invalid start time noon".to_string())
+        );
+
+        // after midnight, times before the virtual midnight are still today
+        let options = Options {
+            virtual_midnight: NaiveTime::from_hms_opt(4, 0, 0).unwrap(),
+            ..Options::default()
+        };
+        let night = now().date().and_hms_opt(23, 30, 0).unwrap() + Duration::hours(2);
+        assert_eq!(
+            QuickEntry::parse("deploy since 23:00", night, &options)
+                .unwrap()
+                .start,
+            at(23, 0)
+        );
+        assert_eq!(
+            QuickEntry::parse("deploy since 0:30", night, &options)
+                .unwrap()
+                .start,
+            Some(night - Duration::hours(1))
+        );
+    }
+
+    #[test]
+    fn test_parse_plain() {
+        assert_eq!(
+            QuickEntry::parse("bug triage", now(), &Options::default()),
+            Ok(QuickEntry {
+                task: "bug triage".to_string(),
+                start: None
+            })
+        );
+        // numbers without unit are part of the task
+        assert_eq!(
+            QuickEntry::parse("42 reviews", now(), &Options::default())
+                .unwrap()
+                .task,
+            "42 reviews"
+        );
+        assert!(QuickEntry::parse("", now(), &Options::default()).is_err());
+        assert!(QuickEntry::parse("30m", now(), &Options::default()).is_err());
+        assert!(QuickEntry::parse("99999999999h foo", now(), &Options::default()).is_err());
+        assert!(QuickEntry::parse("99999999999999999h foo", now(), &Options::default()).is_err());
+    }
+}
@@ -0,0 +1,300 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+/**
+ * Small backtracking regular expression matcher, for validating tasks
+ *
+ * Supports literals, `.`, character classes like `[a-z_]` and `[^:]`, the escapes `\d`, `\w`,
+ * `\s` (and their negations), escaped punctuation like `\.`, anchors `^` and `$`, groups with
+ * alternatives `(a|b)`, and the quantifiers `*`, `+`, `?`, `{n}`, `{n,}`, `{n,m}`.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Regex {
+    pattern: String,
+    alternatives: Vec<Vec<Item>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Char(char),
+    Any,
+    // ranges, negated
+    Class(Vec<(char, char)>, bool),
+    Start,
+    End,
+    Group(Vec<Vec<Item>>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Item {
+    node: Node,
+    min: usize,
+    max: usize,
+}
+
+const DIGIT: &[(char, char)] = &[('0', '9')];
+const WORD: &[(char, char)] = &[('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')];
+const SPACE: &[(char, char)] = &[(' ', ' '), ('\t', '\t'), ('\n', '\n'), ('\r', '\r')];
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl Parser<'_> {
+    fn alternatives(&mut self) -> Result<Vec<Vec<Item>>, String> {
+        let mut alternatives = vec![self.sequence()?];
+        while self.chars.peek() == Some(&'|') {
+            self.chars.next();
+            alternatives.push(self.sequence()?);
+        }
+        Ok(alternatives)
+    }
+
+    fn sequence(&mut self) -> Result<Vec<Item>, String> {
+        let mut items = Vec::new();
+        while let Some(&c) = self.chars.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            self.chars.next();
+            let node = match c {
+                '(' => {
+                    let group = self.alternatives()?;
+                    if self.chars.next() != Some(')') {
+                        return Err("missing )".to_string());
+                    }
+                    Node::Group(group)
+                }
+                '[' => self.class()?,
+                '.' => Node::Any,
+                '^' => Node::Start,
+                '$' => Node::End,
+                '\\' => self.escape()?,
+                '*' | '+' | '?' | '{' => return Err(format!("nothing to repeat before {}", c)),
+                c => Node::Char(c),
+            };
+            let (min, max) = self.quantifier()?;
+            items.push(Item { node, min, max });
+        }
+        Ok(items)
+    }
+
+    fn escape(&mut self) -> Result<Node, String> {
+        Ok(match self.chars.next().ok_or("trailing backslash")? {
+            'd' => Node::Class(DIGIT.to_vec(), false),
+            'D' => Node::Class(DIGIT.to_vec(), true),
+            'w' => Node::Class(WORD.to_vec(), false),
+            'W' => Node::Class(WORD.to_vec(), true),
+            's' => Node::Class(SPACE.to_vec(), false),
+            'S' => Node::Class(SPACE.to_vec(), true),
+            c if c.is_ascii_punctuation() => Node::Char(c),
+            c => return Err(format!("unsupported escape \\{}", c)),
+        })
+    }
+
+    fn class(&mut self) -> Result<Node, String> {
+        let negated = self.chars.peek() == Some(&'^');
+        if negated {
+            self.chars.next();
+        }
+        let mut ranges = Vec::new();
+        let mut first = true;
+        loop {
+            let c = self.chars.next().ok_or("missing ]")?;
+            match c {
+                ']' if !first => break,
+                '\\' => match self.escape()? {
+                    Node::Class(r, false) => ranges.extend(r),
+                    Node::Char(c) => ranges.push((c, c)),
+                    _ => return Err("negated escape in character class".to_string()),
+                },
+                c => {
+                    let mut lookahead = self.chars.clone();
+                    if lookahead.next() == Some('-') && lookahead.peek().is_some_and(|e| *e != ']')
+                    {
+                        self.chars.next();
+                        let end = self.chars.next().unwrap();
+                        if end < c {
+                            return Err(format!("invalid range {}-{}", c, end));
+                        }
+                        ranges.push((c, end));
+                    } else {
+                        ranges.push((c, c));
+                    }
+                }
+            }
+            first = false;
+        }
+        Ok(Node::Class(ranges, negated))
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let mut digits = String::new();
+        while let Some(c) = self.chars.peek().filter(|c| c.is_ascii_digit()) {
+            digits.push(*c);
+            self.chars.next();
+        }
+        digits.parse().ok()
+    }
+
+    fn quantifier(&mut self) -> Result<(usize, usize), String> {
+        let q = match self.chars.peek() {
+            Some('*') => (0, usize::MAX),
+            Some('+') => (1, usize::MAX),
+            Some('?') => (0, 1),
+            Some('{') => {
+                self.chars.next();
+                let min = self.number().ok_or("invalid repetition")?;
+                let max = match self.chars.next() {
+                    Some('}') => return Ok((min, min)),
+                    Some(',') => self.number().unwrap_or(usize::MAX),
+                    _ => return Err("invalid repetition".to_string()),
+                };
+                if self.chars.next() != Some('}') || max < min {
+                    return Err("invalid repetition".to_string());
+                }
+                return Ok((min, max));
+            }
+            _ => return Ok((1, 1)),
+        };
+        self.chars.next();
+        Ok(q)
+    }
+}
+
+fn match_node(node: &Node, text: &[char], pos: usize, k: &mut dyn FnMut(usize) -> bool) -> bool {
+    match node {
+        Node::Char(c) => text.get(pos) == Some(c) && k(pos + 1),
+        Node::Any => pos < text.len() && k(pos + 1),
+        Node::Class(ranges, negated) => match text.get(pos) {
+            Some(c) => ranges.iter().any(|(lo, hi)| lo <= c && c <= hi) != *negated && k(pos + 1),
+            None => false,
+        },
+        Node::Start => pos == 0 && k(pos),
+        Node::End => pos == text.len() && k(pos),
+        Node::Group(alternatives) => alternatives
+            .iter()
+            .any(|items| match_items(items, text, pos, k)),
+    }
+}
+
+fn match_items(
+    items: &[Item],
+    text: &[char],
+    pos: usize,
+    k: &mut dyn FnMut(usize) -> bool,
+) -> bool {
+    match items.split_first() {
+        None => k(pos),
+        Some((item, rest)) => match_repeat(item, rest, 0, text, pos, k),
+    }
+}
+
+// greedy: try one more repetition first, then the rest of the sequence
+fn match_repeat(
+    item: &Item,
+    rest: &[Item],
+    count: usize,
+    text: &[char],
+    pos: usize,
+    k: &mut dyn FnMut(usize) -> bool,
+) -> bool {
+    if count < item.max
+        && match_node(&item.node, text, pos, &mut |p| {
+            // an empty repetition beyond the minimum would loop forever
+            (p != pos || count < item.min) && match_repeat(item, rest, count + 1, text, p, k)
+        })
+    {
+        return true;
+    }
+    count >= item.min && match_items(rest, text, pos, k)
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Regex, String> {
+        let mut parser = Parser {
+            chars: pattern.chars().peekable(),
+        };
+        let alternatives = parser.alternatives()?;
+        if parser.chars.next().is_some() {
+            return Err("unmatched )".to_string());
+        }
+        Ok(Regex {
+            pattern: pattern.to_string(),
+            alternatives,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /**
+     * Does the pattern match anywhere in `text`? Use ^ and $ to match all of it.
+     */
+    pub fn is_match(&self, text: &str) -> bool {
+        let text: Vec<char> = text.chars().collect();
+        let group = Node::Group(self.alternatives.clone());
+        (0..=text.len()).any(|start| match_node(&group, &text, start, &mut |_| true))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        Regex::new(pattern).unwrap().is_match(text)
+    }
+
+    #[test]
+    fn test_match() {
+        assert!(matches("", "anything"));
+        assert!(matches("code", "acme: code review"));
+        assert!(!matches("^code", "acme: code"));
+        assert!(matches("^acme: ", "acme: code"));
+        assert!(matches("^[a-z]+: .+$", "acme: code"));
+        assert!(!matches("^[a-z]+: .+$", "Acme: code"));
+        assert!(!matches("^[a-z]+: .+$", "acme:"));
+        assert!(matches("^(acme|internal): ", "internal: meeting"));
+        assert!(!matches("^(acme|internal): ", "other: meeting"));
+        assert!(matches("^[^:]+: \\S", "customer joe: support"));
+        assert!(matches("#\\d+$", "bug #1234"));
+        assert!(!matches("#\\d+$", "bug #12a"));
+        assert!(matches("^\\w{2,4}-\\d{1,3}\\s", "ABC-12 fix"));
+        assert!(matches("^a{3}$", "aaa"));
+        assert!(!matches("^a{3}$", "aaaa"));
+        assert!(matches("^a{2,}$", "aaaa"));
+        assert!(matches("^colou?r$", "color"));
+        assert!(matches("^(ab)*c$", "ababc"));
+        assert!(matches("^(a*)*b$", "aaab"));
+        assert!(!matches("^(a*)*b$", "aaaa"));
+        assert!(matches("^ü.$", "üß"));
+        assert!(matches("[-a]", "x-y"));
+        assert!(matches("\\.", "a.b"));
+        assert!(!matches("\\.", "ab"));
+        assert!(matches("^\\(\\w+\\) \\$\\\\", "(x) $\\"));
+        assert!(matches("[\\]-]", "a]"));
+    }
+
+    #[test]
+    fn test_invalid() {
+        for pattern in [
+            "(a", "a)", "[a-", "*a", "a{x}", "a{3,1}", "[z-a]", "\\", "\\b", "\\bfix", "[\\x]",
+        ] {
+            assert!(Regex::new(pattern).is_err(), "{}", pattern);
+        }
+    }
+}
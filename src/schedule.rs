@@ -0,0 +1,309 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate chrono;
+
+use std::io::Write;
+use std::process::{self, Stdio};
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+
+/**
+ * Periods which were already sent are recorded in this file next to the timelog
+ */
+pub const SENT_FILE: &str = "schedule-sent.txt";
+
+/**
+ * Ways of sending the report
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    Email,
+    Webhook,
+}
+
+impl Channel {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Channel::Email => "email",
+            Channel::Webhook => "webhook",
+        }
+    }
+}
+
+/**
+ * The last complete week (Monday to Sunday) before `now`, and when its report is due
+ */
+pub fn last_week(
+    now: NaiveDateTime,
+    day: Weekday,
+    time: NaiveTime,
+) -> (NaiveDate, NaiveDate, NaiveDateTime) {
+    let today = now.date();
+    let monday = today - Duration::days(today.weekday().num_days_from_monday() as i64 + 7);
+    let sunday = monday + Duration::days(6);
+    let due = sunday + Duration::days(day.num_days_from_monday() as i64 + 1);
+    (monday, sunday, due.and_time(time))
+}
+
+/**
+ * When the report of the current week will be due
+ */
+pub fn next_due(now: NaiveDateTime, day: Weekday, time: NaiveTime) -> NaiveDateTime {
+    let (_, _, due) = last_week(now + Duration::weeks(1), day, time);
+    due
+}
+
+/**
+ * Was the period starting at `from` already sent through `channel`?
+ *
+ * `sent` are the contents of SENT_FILE: lines of "YYYY-MM-DD channel".
+ */
+pub fn was_sent(sent: &str, from: &NaiveDate, channel: Channel) -> bool {
+    let record = sent_record(from, channel);
+    sent.lines().any(|line| line.trim() == record)
+}
+
+/**
+ * SENT_FILE line for the period starting at `from`
+ */
+pub fn sent_record(from: &NaiveDate, channel: Channel) -> String {
+    format!("{} {}", from.format("%Y-%m-%d"), channel.name())
+}
+
+/**
+ * Run a program with `input` on stdin
+ */
+fn run_with_input(command: &mut process::Command, input: &str) -> Result<(), String> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("cannot run {}: {}", program, e))?;
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .map_err(|e| format!("cannot write to {}: {}", program, e))?;
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+/**
+ * Send a plain text mail with a sendmail compatible command like "sendmail -t", which takes the
+ * recipients from the message
+ */
+pub fn send_email_with(
+    command: &str,
+    from: Option<&str>,
+    to: &str,
+    subject: &str,
+    body: &str,
+) -> Result<(), String> {
+    let mut words = command.split_whitespace();
+    let program = words.next().ok_or("empty mail command")?;
+    run_with_input(
+        process::Command::new(program).args(words),
+        &email_message(from, to, subject, body),
+    )
+}
+
+/**
+ * Send a plain text mail through an SMTP server with curl
+ *
+ * `server` is a URL like "smtps://mail.example.com"; `address` is the bare sender address for the
+ * envelope, `from` the From: header.
+ */
+pub fn send_smtp(
+    server: &str,
+    user: Option<&str>,
+    address: &str,
+    from: Option<&str>,
+    to: &str,
+    subject: &str,
+    body: &str,
+) -> Result<(), String> {
+    run_with_input(
+        process::Command::new("curl").args(smtp_args(server, user, address, to)),
+        &email_message(from, to, subject, body),
+    )
+}
+
+/**
+ * curl options for sending mail; the password comes from ~/.netrc, unless `user` is
+ * "user:password"
+ *
+ * smtps:// servers require TLS; plain smtp:// ones are used as they are, for local relays
+ * without STARTTLS.
+ */
+fn smtp_args(server: &str, user: Option<&str>, address: &str, to: &str) -> Vec<String> {
+    let mut args: Vec<String> = vec!["--silent".to_string(), "--show-error".to_string()];
+    if server.starts_with("smtps://") {
+        args.push("--ssl-reqd".to_string());
+    }
+    args.extend(
+        ["--netrc-optional", "--url", server, "--mail-from", address]
+            .iter()
+            .map(|a| a.to_string()),
+    );
+    for recipient in to.split(',').map(str::trim).filter(|r| !r.is_empty()) {
+        args.push("--mail-rcpt".to_string());
+        args.push(recipient.to_string());
+    }
+    if let Some(user) = user {
+        args.push("--user".to_string());
+        args.push(user.to_string());
+    }
+    args.push("--upload-file".to_string());
+    args.push("-".to_string());
+    args
+}
+
+fn email_message(from: Option<&str>, to: &str, subject: &str, body: &str) -> String {
+    let from = from.map_or(String::new(), |f| format!("From: {}\n", f));
+    format!(
+        "{}To: {}\nSubject: {}\nContent-Type: text/plain; charset=utf-8\n\n{}",
+        from, to, subject, body
+    )
+}
+
+/**
+ * POST a JSON document with curl
+ */
+pub fn post_webhook(url: &str, json: &str) -> Result<(), String> {
+    run_with_input(
+        process::Command::new("curl")
+            .args(["--fail", "--silent", "--show-error", "--location"])
+            .args(["--max-time", "30"])
+            .args(["--header", "Content-Type: application/json"])
+            .args(["--data-binary", "@-"])
+            .arg(url),
+        json,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, m, d)
+            .unwrap()
+            .and_hms_opt(h, min, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_last_week() {
+        let eight = NaiveTime::from_hms_opt(8, 0, 0).unwrap();
+        // Friday 2022-06-10
+        let (from, to, due) = last_week(at(2022, 6, 10, 12, 0), Weekday::Mon, eight);
+        assert_eq!(from, NaiveDate::from_ymd_opt(2022, 5, 30).unwrap());
+        assert_eq!(to, NaiveDate::from_ymd_opt(2022, 6, 5).unwrap());
+        assert_eq!(due, at(2022, 6, 6, 8, 0));
+
+        // on Monday, the previous week
+        let (from, _, due) = last_week(at(2022, 6, 6, 7, 0), Weekday::Wed, eight);
+        assert_eq!(from, NaiveDate::from_ymd_opt(2022, 5, 30).unwrap());
+        assert_eq!(due, at(2022, 6, 8, 8, 0));
+        assert_eq!(
+            next_due(at(2022, 6, 6, 7, 0), Weekday::Wed, eight),
+            at(2022, 6, 15, 8, 0)
+        );
+
+        // Sunday is still part of the current week
+        let (from, _, due) = last_week(at(2022, 6, 12, 23, 0), Weekday::Sun, eight);
+        assert_eq!(from, NaiveDate::from_ymd_opt(2022, 5, 30).unwrap());
+        assert_eq!(due, at(2022, 6, 12, 8, 0));
+    }
+
+    #[test]
+    fn test_was_sent() {
+        let from = NaiveDate::from_ymd_opt(2022, 5, 30).unwrap();
+        let sent = "2022-05-23 email\n2022-05-30 webhook\n";
+        assert!(was_sent(sent, &from, Channel::Webhook));
+        assert!(!was_sent(sent, &from, Channel::Email));
+        assert!(!was_sent("", &from, Channel::Email));
+        assert_eq!(sent_record(&from, Channel::Email), "2022-05-30 email");
+    }
+
+    #[test]
+    fn test_smtp_args() {
+        assert_eq!(
+            smtp_args(
+                "smtps://mail.example.com",
+                Some("joe"),
+                "joe@example.com",
+                "a@example.com, b@example.com"
+            ),
+            vec![
+                "--silent",
+                "--show-error",
+                "--ssl-reqd",
+                "--netrc-optional",
+                "--url",
+                "smtps://mail.example.com",
+                "--mail-from",
+                "joe@example.com",
+                "--mail-rcpt",
+                "a@example.com",
+                "--mail-rcpt",
+                "b@example.com",
+                "--user",
+                "joe",
+                "--upload-file",
+                "-"
+            ]
+        );
+        assert_eq!(
+            smtp_args("smtp://localhost", None, "j@x", "b@x"),
+            vec![
+                "--silent",
+                "--show-error",
+                "--netrc-optional",
+                "--url",
+                "smtp://localhost",
+                "--mail-from",
+                "j@x",
+                "--mail-rcpt",
+                "b@x",
+                "--upload-file",
+                "-"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_email_message() {
+        assert_eq!(
+            email_message(None, "boss@example.com", "Report", "work\n"),
+            "To: boss@example.com\nSubject: Report\nContent-Type: text/plain; charset=utf-8\n\nwork\n"
+        );
+        assert!(
+            email_message(Some("Joe <joe@example.com>"), "b@example.com", "R", "")
+                .starts_with("From: Joe <joe@example.com>\nTo: b@example.com\n")
+        );
+    }
+}
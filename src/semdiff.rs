@@ -0,0 +1,338 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate chrono;
+
+use std::collections::{BTreeSet, HashSet};
+use std::fmt;
+
+use chrono::{Duration, NaiveDate};
+
+use crate::activity::{format_duration, Activities};
+use crate::store::{Entry, Options};
+
+/**
+ * Difference of a single entry
+ */
+#[derive(Debug, PartialEq, Eq)]
+pub enum Change {
+    Added(Entry),
+    Removed(Entry),
+    // different task at the same time, or the same task at a different time on the same day
+    Changed(Entry, Entry),
+}
+
+/**
+ * Changed entries of a day, and how they change its totals
+ */
+#[derive(Debug, PartialEq, Eq)]
+pub struct DayDiff {
+    pub day: NaiveDate,
+    pub changes: Vec<Change>,
+    pub old_work: Duration,
+    pub new_work: Duration,
+    pub old_slack: Duration,
+    pub new_slack: Duration,
+}
+
+fn day_slice<'a>(entries: &'a [Entry], day: &NaiveDate, options: &Options) -> &'a [Entry] {
+    let first = entries.partition_point(|e| options.day(e) < *day);
+    let last = entries.partition_point(|e| options.day(e) <= *day);
+    &entries[first..last]
+}
+
+/**
+ * Compare the entries of a day, which must be sorted by time
+ */
+fn compare_day(old: &[Entry], new: &[Entry]) -> Vec<Change> {
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        match (old.get(i), new.get(j)) {
+            (Some(o), Some(n)) if o.stop == n.stop => {
+                if o.task != n.task {
+                    changes.push(Change::Changed(o.clone(), n.clone()));
+                }
+                i += 1;
+                j += 1;
+            }
+            (Some(o), Some(n)) if o.stop < n.stop => {
+                changes.push(Change::Removed(o.clone()));
+                i += 1;
+            }
+            (Some(o), None) => {
+                changes.push(Change::Removed(o.clone()));
+                i += 1;
+            }
+            (_, Some(n)) => {
+                changes.push(Change::Added(n.clone()));
+                j += 1;
+            }
+            (None, None) => unreachable!(),
+        }
+    }
+
+    // a removed and an added entry with the same task means that it moved
+    let mut result: Vec<Change> = Vec::new();
+    for change in changes {
+        let partner = result.iter().position(|c| match (c, &change) {
+            (Change::Removed(o), Change::Added(n)) | (Change::Added(n), Change::Removed(o)) => {
+                o.task == n.task
+            }
+            _ => false,
+        });
+        match (partner, change) {
+            (Some(pos), Change::Added(n)) => {
+                if let Change::Removed(o) = result.remove(pos) {
+                    result.insert(pos, Change::Changed(o, n));
+                }
+            }
+            (Some(pos), Change::Removed(o)) => {
+                if let Change::Added(n) = result.remove(pos) {
+                    result.insert(pos, Change::Changed(o, n));
+                }
+            }
+            (_, change) => result.push(change),
+        }
+    }
+    result
+}
+
+/**
+ * Compare two timelogs, by day; days without changes are left out
+ */
+pub fn compare(old: &[Entry], new: &[Entry], options: &Options) -> Vec<DayDiff> {
+    let days: BTreeSet<NaiveDate> = old.iter().chain(new).map(|e| options.day(e)).collect();
+    days.into_iter()
+        .filter_map(|day| {
+            let (o, n) = (day_slice(old, &day, options), day_slice(new, &day, options));
+            let changes = compare_day(o, n);
+            if changes.is_empty() {
+                return None;
+            }
+            let (old_a, new_a) = (
+                Activities::new_from_entries(o, options),
+                Activities::new_from_entries(n, options),
+            );
+            Some(DayDiff {
+                day,
+                changes,
+                old_work: old_a.total_work(),
+                new_work: new_a.total_work(),
+                old_slack: old_a.total_slack(),
+                new_slack: new_a.total_slack(),
+            })
+        })
+        .collect()
+}
+
+fn write_total(
+    f: &mut fmt::Formatter<'_>,
+    name: &str,
+    old: Duration,
+    new: Duration,
+) -> fmt::Result {
+    if old == new {
+        return Ok(());
+    }
+    let sign = if new > old { "+" } else { "" };
+    writeln!(
+        f,
+        "  {}: {} -> {} ({}{})",
+        name,
+        format_duration(old),
+        format_duration(new),
+        sign,
+        format_duration(new - old)
+    )
+}
+
+impl fmt::Display for DayDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}:", self.day.format("%Y-%m-%d %a"))?;
+        let time = |e: &Entry| e.stop.format("%H:%M").to_string();
+        for change in &self.changes {
+            match change {
+                Change::Added(e) => writeln!(f, "  + {} {}", time(e), e.task)?,
+                Change::Removed(e) => writeln!(f, "  - {} {}", time(e), e.task)?,
+                Change::Changed(o, n) => {
+                    writeln!(f, "  ~ {} {} -> {} {}", time(o), o.task, time(n), n.task)?
+                }
+            }
+        }
+        write_total(f, "work", self.old_work, self.new_work)?;
+        write_total(f, "slacking", self.old_slack, self.new_slack)
+    }
+}
+
+/**
+ * What to change in the local timelog to merge the remote changes into it
+ */
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Merge {
+    pub remove: Vec<Entry>,
+    pub add: Vec<Entry>,
+    // (local, remote) entries which were both added or changed at the same time
+    pub conflicts: Vec<(Entry, Entry)>,
+}
+
+/**
+ * Three-way merge of timelogs, based on their common ancestor
+ *
+ * An entry is kept if both sides have it, or if one side added it. Entries which one side
+ * removed (or changed) are dropped. If both sides added different tasks at the same time,
+ * the local one wins and the pair is reported as conflict.
+ */
+pub fn merge(base: &[Entry], local: &[Entry], remote: &[Entry]) -> Merge {
+    let base: HashSet<&Entry> = base.iter().collect();
+    let local_set: HashSet<&Entry> = local.iter().collect();
+    let remote_set: HashSet<&Entry> = remote.iter().collect();
+    let mut result = Merge::default();
+
+    for e in local {
+        if base.contains(e) && !remote_set.contains(e) {
+            result.remove.push(e.clone());
+        }
+    }
+
+    for e in remote {
+        if base.contains(e) || local_set.contains(e) {
+            continue;
+        }
+        match local.iter().find(|l| l.stop == e.stop && !base.contains(l)) {
+            Some(l) => result.conflicts.push((l.clone(), e.clone())),
+            None => result.add.push(e.clone()),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::parse_entries;
+
+    #[test]
+    fn test_compare() {
+        let (old, _) = parse_entries(
+            "
+2022-06-09 08:00: arrived
+2022-06-09 12:00: code
+
+2022-06-10 07:00: arrived
+2022-06-10 12:05: rtimelog: code
+2022-06-10 12:30: **lunch
+2022-06-10 14:00: rtimelog: code
+2022-06-10 15:00: bug triage
+",
+        );
+        let (new, _) = parse_entries(
+            "
+2022-06-09 08:00: arrived
+2022-06-09 12:00: code
+
+2022-06-10 07:00: arrived
+2022-06-10 12:05: rtimelog: code
+2022-06-10 12:45: **lunch
+2022-06-10 14:00: rtimelog: review
+2022-06-10 16:00: email
+
+2022-06-11 09:00: arrived
+",
+        );
+        assert!(compare(&old, &old, &Options::default()).is_empty());
+
+        let diff = compare(&old, &new, &Options::default());
+        assert_eq!(diff.len(), 2);
+        assert_eq!(
+            format!("{}", diff[0]),
+            "2022-06-10 Fri:
+  ~ 12:30 **lunch -> 12:45 **lunch
+  ~ 14:00 rtimelog: code -> 14:00 rtimelog: review
+  - 15:00 bug triage
+  + 16:00 email
+  work: 7 h 35 min -> 8 h 20 min (+0 h 45 min)
+  slacking: 0 h 25 min -> 0 h 40 min (+0 h 15 min)
+"
+        );
+        assert_eq!(diff[1].changes.len(), 1);
+        assert_eq!(diff[1].new_work, Duration::zero());
+    }
+
+    #[test]
+    fn test_merge() {
+        let (base, _) = parse_entries(
+            "
+2022-06-10 07:00: arrived
+2022-06-10 09:00: email
+2022-06-10 10:00: code
+2022-06-10 11:00: meeting
+2022-06-10 12:00: **lunch
+",
+        );
+        let (local, _) = parse_entries(
+            "
+2022-06-10 07:00: arrived
+2022-06-10 09:00: email
+2022-06-10 10:00: code review
+2022-06-10 12:00: **lunch
+2022-06-10 13:00: bug triage
+",
+        );
+        let (remote, _) = parse_entries(
+            "
+2022-06-10 07:00: arrived
+2022-06-10 09:30: email
+2022-06-10 10:00: code
+2022-06-10 11:00: meeting
+2022-06-10 12:00: **lunch
+2022-06-10 13:00: support
+2022-06-10 14:00: docs
+",
+        );
+        let entry = |s| parse_entries(s).0.remove(0);
+
+        // unchanged sides
+        assert_eq!(merge(&base, &local, &base), Merge::default());
+        let m = merge(&base, &base, &remote);
+        assert_eq!(m.remove, vec![entry("2022-06-10 09:00: email")]);
+        assert_eq!(
+            m.add,
+            vec![
+                entry("2022-06-10 09:30: email"),
+                entry("2022-06-10 13:00: support"),
+                entry("2022-06-10 14:00: docs")
+            ]
+        );
+
+        let m = merge(&base, &local, &remote);
+        // local changes (code review, removed meeting) stay as they are
+        assert_eq!(m.remove, vec![entry("2022-06-10 09:00: email")]);
+        assert_eq!(
+            m.add,
+            vec![
+                entry("2022-06-10 09:30: email"),
+                entry("2022-06-10 14:00: docs")
+            ]
+        );
+        assert_eq!(
+            m.conflicts,
+            vec![(
+                entry("2022-06-10 13:00: bug triage"),
+                entry("2022-06-10 13:00: support")
+            )]
+        );
+    }
+}
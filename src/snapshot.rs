@@ -0,0 +1,182 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate chrono;
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDateTime;
+
+use crate::compress;
+
+/**
+ * Directory of the snapshots, next to the timelog
+ */
+pub const DIR: &str = "snapshots";
+
+const LABELS: &str = "labels.txt";
+const SUFFIX: &str = ".txt.gz";
+
+/**
+ * A compressed copy of the timelog
+ */
+#[derive(Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    pub id: String,
+    pub label: String,
+}
+
+pub fn dir(timelog_file: &Path) -> PathBuf {
+    timelog_file.with_file_name(DIR)
+}
+
+/**
+ * Parse "id<TAB>label" lines
+ */
+fn parse_labels(contents: &str) -> Vec<(&str, &str)> {
+    contents
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .collect()
+}
+
+/**
+ * All snapshots in `dir`, oldest first
+ */
+pub fn list(dir: &Path) -> io::Result<Vec<Snapshot>> {
+    let labels = fs::read_to_string(dir.join(LABELS)).unwrap_or_default();
+    let labels = parse_labels(&labels);
+    let mut ids = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok())
+            .filter_map(|e| {
+                e.file_name()
+                    .to_str()
+                    .and_then(|n| n.strip_suffix(SUFFIX))
+                    .map(String::from)
+            })
+            .collect::<Vec<_>>(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    ids.sort();
+    Ok(ids
+        .into_iter()
+        .map(|id| {
+            let label = labels
+                .iter()
+                .rev()
+                .find(|(i, _)| *i == id)
+                .map_or("", |(_, l)| l)
+                .to_string();
+            Snapshot { id, label }
+        })
+        .collect())
+}
+
+/**
+ * Id for a new snapshot: its time, with a counter if there already is one from the same second
+ */
+fn new_id(dir: &Path, now: NaiveDateTime) -> String {
+    let base = now.format("%Y%m%d-%H%M%S").to_string();
+    let mut id = base.clone();
+    let mut n = 1;
+    while dir.join(id.clone() + SUFFIX).exists() {
+        n += 1;
+        id = format!("{}-{}", base, n);
+    }
+    id
+}
+
+/**
+ * Store `contents` as new snapshot in `dir`, and return its id
+ */
+pub fn create(dir: &Path, contents: &str, label: &str, now: NaiveDateTime) -> io::Result<String> {
+    fs::create_dir_all(dir)?;
+    let id = new_id(dir, now);
+    fs::write(
+        dir.join(id.clone() + SUFFIX),
+        compress::gzip(contents.as_bytes())?,
+    )?;
+    if !label.is_empty() {
+        let mut labels = fs::read_to_string(dir.join(LABELS)).unwrap_or_default();
+        // labels are single lines
+        let label = label.replace(['\t', '\n'], " ");
+        labels.push_str(&format!("{}\t{}\n", id, label));
+        fs::write(dir.join(LABELS), labels)?;
+    }
+    Ok(id)
+}
+
+/**
+ * Contents of a snapshot
+ */
+pub fn read(dir: &Path, id: &str) -> io::Result<String> {
+    if id.contains('/') || id.contains(std::path::MAIN_SEPARATOR) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid snapshot id {}", id),
+        ));
+    }
+    let compressed = fs::read(dir.join(id.to_string() + SUFFIX))
+        .map_err(|e| io::Error::new(e.kind(), format!("cannot read snapshot {}: {}", id, e)))?;
+    let contents = compress::gunzip(&compressed)?;
+    String::from_utf8(contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_create_read_list() {
+        let dir =
+            std::env::temp_dir().join(format!("rtimelog-snapshot-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let now = NaiveDate::from_ymd_opt(2022, 6, 10)
+            .unwrap()
+            .and_hms_opt(12, 30, 5)
+            .unwrap();
+
+        assert_eq!(list(&dir).unwrap(), vec![]);
+        let a = create(&dir, "2022-06-10 07:00: arrived\n", "before\timport", now).unwrap();
+        assert_eq!(a, "20220610-123005");
+        let b = create(&dir, "", "", now).unwrap();
+        assert_eq!(b, "20220610-123005-2");
+
+        assert_eq!(
+            list(&dir).unwrap(),
+            vec![
+                Snapshot {
+                    id: a.clone(),
+                    label: "before import".to_string()
+                },
+                Snapshot {
+                    id: b.clone(),
+                    label: "".to_string()
+                },
+            ]
+        );
+        assert_eq!(read(&dir, &a).unwrap(), "2022-06-10 07:00: arrived\n");
+        assert_eq!(read(&dir, &b).unwrap(), "");
+        assert!(read(&dir, "nonexisting").is_err());
+        assert!(read(&dir, "../timelog").is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
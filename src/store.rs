@@ -16,26 +16,58 @@
 extern crate chrono;
 extern crate dirs;
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fmt;
 use std::fmt::Write as _; // import without risk of name clashing
-use std::fs::{self, File};
+use std::fs::{self, File, OpenOptions};
 use std::io::{self, prelude::*};
-use std::path::PathBuf;
+use std::ops::{Bound, RangeBounds};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Instant, SystemTime};
 
-use chrono::{prelude::*, Local, NaiveDate, NaiveDateTime, Weekday};
+use chrono::{prelude::*, Duration, Local, Months, NaiveDate, NaiveDateTime};
 
-/**
- * Single timelog entry
- */
+use crate::archive;
+use crate::chain;
+use crate::diff;
+use crate::i18n::tr;
+use crate::intern::Symbol;
+use crate::json;
+use crate::locale::Locale;
+use crate::markers::Markers;
+use crate::provenance::{self, MANUAL};
+use crate::trace::Timer;
 
 const TIME_FMT: &str = "%Y-%m-%d %H:%M";
+// length of a time formatted with TIME_FMT
+const TIME_FMT_LEN: usize = 16;
+// number of reported invalid lines
+const MAX_DIAGNOSTICS: usize = 10;
 
-#[derive(Default, Debug, Clone, PartialEq, Eq)]
+/**
+ * Single timelog entry
+ */
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Entry {
     pub stop: NaiveDateTime,
-    pub task: String,
+    pub task: Symbol,
+}
+
+impl Entry {
+    /**
+     * JSON object like {"stop": "2022-06-10T08:00:00", "task": "code"}
+     */
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"stop\": {}, \"task\": {}}}",
+            json::datetime(&self.stop),
+            json::string(&self.task)
+        )
+    }
 }
 
 impl fmt::Display for Entry {
@@ -45,35 +77,476 @@ impl fmt::Display for Entry {
 }
 
 /**
- * Collection of all entries
+ * Invalid line in a timelog file
+ */
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseError {
+    // 1-based line and column (in characters)
+    pub line: usize,
+    pub column: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line,
+            self.column,
+            tr(self.message)
+        )
+    }
+}
+
+/**
+ * Parse the contents of a timelog file into entries, and problems with invalid lines
+ *
+ * This never fails: invalid lines are skipped, and lines which go back in time are sorted in.
  */
+pub fn parse_entries(raw: &str) -> (Vec<Entry>, Vec<ParseError>) {
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut errors = Vec::new();
+    let mut sorted = true;
+
+    for (i, line) in raw.lines().enumerate() {
+        match Timelog::parse_line(line) {
+            Ok(Some(e)) => {
+                if entries.last().is_some_and(|prev| e.stop < prev.stop) {
+                    errors.push(ParseError {
+                        line: i + 1,
+                        column: 1,
+                        message: "goes back in time",
+                    });
+                    sorted = false;
+                }
+                entries.push(e);
+            }
+            Ok(None) => (),
+            Err((column, message)) => errors.push(ParseError {
+                line: i + 1,
+                column,
+                message,
+            }),
+        }
+    }
+
+    if !sorted {
+        // stable, so that entries with the same time keep their order
+        entries.sort_by_key(|e| e.stop);
+    }
+    (entries, errors)
+}
 
-#[derive(Default, Debug)]
+/**
+ * Parse a single "YYYY-MM-DD HH:MM: task" line
+ */
+pub fn parse_entry(line: &str) -> Option<Entry> {
+    Timelog::parse_line(line).ok().flatten()
+}
+
+/**
+ * Split entries into work shifts: blocks of entries without a break longer than `min_break`
+ *
+ * Unlike calendar days, shifts may cross midnight.
+ */
+pub fn split_shifts(entries: &[Entry], min_break: Duration) -> Vec<&[Entry]> {
+    let mut shifts = Vec::new();
+    let mut begin = 0;
+    for i in 1..entries.len() {
+        if entries[i].stop.signed_duration_since(entries[i - 1].stop) > min_break {
+            shifts.push(&entries[begin..i]);
+            begin = i;
+        }
+    }
+    if begin < entries.len() {
+        shifts.push(&entries[begin..]);
+    }
+    shifts
+}
+
+/**
+ * How a timelog gets read, interpreted, and written; usually from the configuration, see
+ * Config::store_options()
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Options {
+    // the timelog file; None for the default one, see Timelog::get_default_file()
+    pub file: Option<PathBuf>,
+    // entries before this time of day count for the previous day, like in gtimelog
+    pub virtual_midnight: NaiveTime,
+    // task prefixes which mark slack time, ignored entries, and billable work
+    pub markers: Markers,
+    // round the times of new entries to this many minutes; 0 keeps them exact
+    pub snap_minutes: u32,
+    // record hashes of appended entries in chain::FILE, and refuse changing existing ones
+    pub hash_chain: bool,
+    // refuse saving (except for showing the changes in dry-run mode)
+    pub read_only: bool,
+}
+
+impl Options {
+    /**
+     * The configured timelog file, or the default one
+     */
+    pub fn file(&self) -> PathBuf {
+        self.file.clone().unwrap_or_else(Timelog::get_default_file)
+    }
+
+    /**
+     * Logical day of a time, with respect to the virtual midnight
+     */
+    pub fn logical_date(&self, t: NaiveDateTime) -> NaiveDate {
+        day_of(t, self.virtual_midnight)
+    }
+
+    /**
+     * Logical day of an entry, which differs from the date of its stop time before the virtual
+     * midnight
+     */
+    pub fn day(&self, entry: &Entry) -> NaiveDate {
+        self.logical_date(entry.stop)
+    }
+
+    /**
+     * Logical day of the current local time
+     */
+    pub fn today(&self) -> NaiveDate {
+        self.logical_date(Local::now().naive_local())
+    }
+}
+
+/**
+ * Collection of all entries
+ *
+ * Like gtimelog, new entries get appended to the file, so that it stays byte-identical otherwise.
+ * Only changing existing entries rewrites the file; lines which are not entries (invalid lines,
+ * notes) are kept after the entry which preceded them.
+ */
+#[derive(Debug)]
 pub struct Timelog {
     entries: Vec<Entry>,
     pub filename: Option<PathBuf>,
+    // file contents when loading or saving
+    original: String,
+    // number of entries in `original`, and how many of them are still unchanged
+    loaded: usize,
+    unchanged: usize,
+    // lines which are not entries, with the time of the entry before them
+    foreign: Vec<(Option<NaiveDateTime>, String)>,
+    // how new entries are created, and recorded sources of entries which are not MANUAL
+    source: Symbol,
+    sources: HashMap<Entry, Symbol>,
+    sources_changed: bool,
+    // entries from archives were added, or only the end of the file was read; such a timelog
+    // does not match the file and must not be saved
+    partial: bool,
+    // state of the file when loading or saving, to notice changes by other programs
+    stamp: Option<Stamp>,
+    // where the file is, when days begin, and how to write it
+    options: Options,
+}
+
+/**
+ * Modification time and size of a file, to notice changes by other programs
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Stamp {
+    modified: SystemTime,
+    len: u64,
+}
+
+impl Stamp {
+    fn of(path: &Path) -> Option<Stamp> {
+        let meta = fs::metadata(path).ok()?;
+        Some(Stamp {
+            modified: meta.modified().ok()?,
+            len: meta.len(),
+        })
+    }
+}
+
+/**
+ * Hidden file next to `path` (or its symlink target), like .timelog.txt.lock
+ */
+fn hidden_sibling(path: &Path, suffix: &str) -> io::Result<(PathBuf, PathBuf)> {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::other(format!("{} is not a file", path.display())))?;
+    let sibling = path.with_file_name(format!(".{}.{}", name.to_string_lossy(), suffix));
+    Ok((path, sibling))
+}
+
+const LOCK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/**
+ * Take the advisory lock of a timelog, waiting up to `timeout` for other writers
+ *
+ * The lock is on a separate file, as rewriting replaces the timelog itself. It is released when
+ * the returned file gets closed.
+ */
+fn lock(path: &Path, timeout: std::time::Duration) -> io::Result<File> {
+    let (_, lock_path) = hidden_sibling(path, "lock")?;
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(lock_path)?;
+    let start = Instant::now();
+    loop {
+        match file.try_lock() {
+            Ok(()) => return Ok(file),
+            Err(fs::TryLockError::WouldBlock) if start.elapsed() < timeout => {
+                thread::sleep(std::time::Duration::from_millis(100))
+            }
+            Err(fs::TryLockError::WouldBlock) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::WouldBlock,
+                    format!("{} is locked by another program", path.display()),
+                ))
+            }
+            Err(fs::TryLockError::Error(e)) => return Err(e),
+        }
+    }
+}
+
+/**
+ * Take the lock of a timelog for changing it outside of Timelog::save(), like replacing it as a
+ * whole with write_atomically(); it is held until the returned file gets dropped
+ */
+pub fn lock_timelog(path: &Path) -> io::Result<File> {
+    lock(path, LOCK_TIMEOUT)
+}
+
+/**
+ * Replace a file by writing a temporary file next to it and renaming it
+ *
+ * This never leaves a partially written file behind, and keeps the permissions of the old file.
+ * A symlink stays in place, its target gets replaced.
+ */
+pub fn write_atomically(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let (path, tmp) = hidden_sibling(path, "tmp")?;
+    let result = (|| {
+        let mut f = File::create(&tmp)?;
+        f.write_all(contents.as_ref())?;
+        if let Ok(meta) = fs::metadata(&path) {
+            f.set_permissions(meta.permissions())?;
+        }
+        f.sync_all()?;
+        fs::rename(&tmp, &path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/**
+ * In dry-run mode, Timelog::save() only prints the changes it would do
+ */
+pub fn set_dry_run(dry_run: bool) {
+    DRY_RUN.store(dry_run, Ordering::Relaxed);
+}
+
+pub fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+/**
+ * Show the changes which a command would have done to `filename` in dry-run mode
+ */
+pub fn print_dry_run(filename: &Path, changes: &str) {
+    if changes.is_empty() {
+        println!("{}", tr("Dry run: no changes"));
+    } else {
+        println!("--- {}", filename.display());
+        println!("+++ {} ({})", filename.display(), tr("dry run"));
+        print!("{}", changes);
+    }
+}
+
+/**
+ * Read the end of a timelog file, from the last line before `since` on
+ *
+ * This is much faster than reading the whole file for things which only need the latest entries.
+ * The result may start with some older lines.
+ */
+pub fn read_tail(path: &Path, since: NaiveDate) -> io::Result<String> {
+    const BLOCK: u64 = 4096;
+    let mut f = File::open(path)?;
+    let mut pos = f.metadata()?.len();
+    let mut tail: Vec<u8> = Vec::new();
+
+    while pos > 0 {
+        let n = BLOCK.min(pos);
+        pos -= n;
+        f.seek(io::SeekFrom::Start(pos))?;
+        let mut block = vec![0; n as usize];
+        f.read_exact(&mut block)?;
+        block.extend_from_slice(&tail);
+        tail = block;
+
+        // the first line may be incomplete, unless this is the start of the file
+        let mut lines = tail.split(|b| *b == b'\n');
+        if pos > 0 {
+            lines.next();
+        }
+        // only entries count, notes can start with digits too
+        if lines.any(|l| {
+            std::str::from_utf8(l)
+                .ok()
+                .and_then(parse_entry)
+                .is_some_and(|e| e.stop.date() < since)
+        }) {
+            break;
+        }
+    }
+    if pos > 0 {
+        let start = tail.iter().position(|b| *b == b'\n').map_or(0, |i| i + 1);
+        tail.drain(..start);
+    }
+    Ok(String::from_utf8_lossy(&tail).into_owned())
+}
+
+/// file name for reading the timelog from stdin
+pub const STDIN: &str = "-";
+
+pub fn is_stdin(path: &Path) -> bool {
+    path.as_os_str() == STDIN
+}
+
+/**
+ * Round a time to the nearest multiple of `minutes` on its day, dropping seconds
+ */
+pub fn snap_to_grid(t: NaiveDateTime, minutes: u32) -> NaiveDateTime {
+    let midnight = t.date().and_hms_opt(0, 0, 0).unwrap();
+    if minutes == 0 {
+        return midnight + Duration::minutes((t - midnight).num_minutes());
+    }
+    let grid = i64::from(minutes) * 60;
+    let seconds = (t - midnight).num_seconds();
+    let mut snapped = (seconds + grid / 2) / grid * grid;
+    // don't round up into the next day
+    if snapped >= 24 * 3600 {
+        snapped -= grid;
+    }
+    midnight + Duration::seconds(snapped)
+}
+
+fn day_of(t: NaiveDateTime, midnight: NaiveTime) -> NaiveDate {
+    if t.time() < midnight {
+        t.date().pred_opt().unwrap_or(NaiveDate::MIN)
+    } else {
+        t.date()
+    }
+}
+
+/**
+ * First and last second of a logical day
+ */
+fn day_bounds(day: &NaiveDate, midnight: NaiveTime) -> (NaiveDateTime, NaiveDateTime) {
+    let start = day.and_time(midnight);
+    let end = day.succ_opt().map_or(NaiveDateTime::MAX, |next| {
+        next.and_time(midnight) - Duration::seconds(1)
+    });
+    (start, end)
 }
 
 impl Timelog {
-    pub fn new_from_default_file() -> Timelog {
-        Timelog::new_from_file(&Timelog::get_default_file())
+    /**
+     * Load the file of `options` (see Options::file())
+     */
+    pub fn new_from_default_file(options: &Options) -> io::Result<Timelog> {
+        Timelog::new_from_file(&options.file(), options)
     }
 
-    pub fn new_from_file(path: &PathBuf) -> Timelog {
-        Timelog {
-            entries: Timelog::parse(&Timelog::read(path)),
-            filename: Some(path.clone()),
+    pub fn new_from_default_file_since(since: NaiveDate, options: &Options) -> io::Result<Timelog> {
+        Timelog::new_from_file_since(&options.file(), since, options)
+    }
+
+    pub fn new_from_file(path: &PathBuf, options: &Options) -> io::Result<Timelog> {
+        // before reading, so that a change while reading gets noticed later
+        let stamp = Stamp::of(path);
+        let contents = Timelog::read(path)?;
+        crate::info!("loaded {}: {} bytes", path.display(), contents.len());
+        let mut timelog = Timelog::new_from_contents(contents, Some(path.clone()), options);
+        if is_stdin(path) {
+            return Ok(timelog);
+        }
+        timelog.stamp = stamp;
+        if let Ok(sources) = fs::read_to_string(path.with_file_name(provenance::FILE)) {
+            timelog.sources = provenance::parse(&sources);
+        }
+        Ok(timelog)
+    }
+
+    /**
+     * Only read the end of the file, with the entries from `since` on (and maybe some older ones)
+     *
+     * This is much faster for large files, but such a timelog is only for reading, it cannot be
+     * saved.
+     */
+    pub fn new_from_file_since(
+        path: &PathBuf,
+        since: NaiveDate,
+        options: &Options,
+    ) -> io::Result<Timelog> {
+        if is_stdin(path) {
+            return Timelog::new_from_file(path, options);
+        }
+        // the logical day starts at the virtual midnight of its calendar day
+        let tail = match read_tail(path, since) {
+            Ok(tail) => tail,
+            Err(_) => return Timelog::new_from_file(path, options),
+        };
+        crate::info!("loaded the end of {}: {} bytes", path.display(), tail.len());
+        let mut timelog = Timelog::new_from_contents(tail, Some(path.clone()), options);
+        if let Ok(sources) = fs::read_to_string(path.with_file_name(provenance::FILE)) {
+            timelog.sources = provenance::parse(&sources);
         }
+        timelog.partial = true;
+        Ok(timelog)
     }
 
     #[cfg(test)]
     pub fn new_from_string(contents: &str) -> Timelog {
+        Timelog::new_from_contents(contents.to_string(), None, &Options::default())
+    }
+
+    fn new_from_contents(
+        contents: String,
+        filename: Option<PathBuf>,
+        options: &Options,
+    ) -> Timelog {
+        let (entries, has_errors) = Timelog::parse(&contents);
+        let foreign = if has_errors {
+            Timelog::foreign_lines(&contents)
+        } else {
+            Vec::new()
+        };
         Timelog {
-            entries: Timelog::parse(contents),
-            filename: None,
+            loaded: entries.len(),
+            unchanged: entries.len(),
+            entries,
+            filename,
+            original: contents,
+            foreign,
+            source: MANUAL.into(),
+            sources: HashMap::new(),
+            sources_changed: false,
+            partial: false,
+            stamp: None,
+            options: options.clone(),
         }
     }
 
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
     pub fn get_default_file() -> PathBuf {
         let mut legacy_dir = dirs::home_dir().unwrap();
         legacy_dir.push(".gtimelog");
@@ -91,160 +564,538 @@ impl Timelog {
         log_path
     }
 
-    fn read(path: &PathBuf) -> String {
-        match File::open(path) {
-            Ok(mut f) => {
-                let mut contents = String::new();
-                f.read_to_string(&mut contents)
-                    .unwrap_or_else(|e| panic!("Failed to read {}: {:?}", path.display(), e));
-                contents
+    /**
+     * Contents of the timelog file, empty if it does not exist yet
+     */
+    fn read(path: &PathBuf) -> io::Result<String> {
+        static STDIN_CONTENTS: OnceLock<Vec<u8>> = OnceLock::new();
+        let file = if is_stdin(path) {
+            // stdin can only be read once, but the timelog may get reloaded
+            match STDIN_CONTENTS.get() {
+                Some(contents) => Ok(contents.clone()),
+                None => {
+                    let mut contents = Vec::new();
+                    io::stdin()
+                        .read_to_end(&mut contents)
+                        .map(|_| STDIN_CONTENTS.get_or_init(|| contents).clone())
+                }
             }
+        } else {
+            File::open(path).and_then(|mut f| {
+                let mut contents = Vec::new();
+                f.read_to_end(&mut contents).map(|_| contents)
+            })
+        };
 
-            Err(e) => {
-                if e.kind() == io::ErrorKind::NotFound {
-                    println!("No existing {}, starting new log", path.display());
-                    String::new()
-                } else {
-                    panic!("Could not open {}: {:?}", path.display(), e);
-                }
+        match file {
+            Ok(contents) => Ok(String::from_utf8(contents).unwrap_or_else(|e| {
+                eprintln!(
+                    "{}: {}",
+                    tr("WARNING: replacing invalid UTF-8 in timelog"),
+                    path.display()
+                );
+                String::from_utf8_lossy(e.as_bytes()).into_owned()
+            })),
+
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                eprintln!(
+                    "{}: {}",
+                    tr("No existing file, starting new log"),
+                    path.display()
+                );
+                Ok(String::new())
             }
+            Err(e) => Err(io::Error::new(
+                e.kind(),
+                format!("could not read {}: {}", path.display(), e),
+            )),
         }
     }
 
-    fn parse(raw: &str) -> Vec<Entry> {
-        let mut entries = Vec::new();
-        let mut prev: Option<NaiveDateTime> = None;
+    fn parse(raw: &str) -> (Vec<Entry>, bool) {
+        let timer = Timer::new("parsing");
+        let (entries, errors) = parse_entries(raw);
+        drop(timer);
+        crate::info!(
+            "parsed {} entries, {} invalid lines",
+            entries.len(),
+            errors.len()
+        );
+        for e in errors.iter().take(MAX_DIAGNOSTICS) {
+            eprintln!("{} {}", tr("WARNING: timelog"), e);
+        }
+        if errors.len() > MAX_DIAGNOSTICS {
+            eprintln!(
+                "{}: {}",
+                tr("WARNING: more invalid lines in timelog"),
+                errors.len() - MAX_DIAGNOSTICS
+            );
+        }
+        (entries, !errors.is_empty())
+    }
 
+    /**
+     * Lines which are not entries, with the time of the entry before them, in time order
+     */
+    fn foreign_lines(raw: &str) -> Vec<(Option<NaiveDateTime>, String)> {
+        let mut lines = Vec::new();
+        let mut anchor = None;
         for line in raw.lines() {
-            if let Some(e) = Timelog::parse_line(line) {
-                // require a monotonously increasing file
-                if prev.is_some() && e.stop < prev.unwrap() {
-                    panic!("line {} goes back in time", line);
-                }
-                prev = Some(e.stop);
-                entries.push(e);
+            match Timelog::parse_line(line) {
+                Ok(Some(e)) => anchor = Some(e.stop),
+                Ok(None) => (),
+                Err(_) => lines.push((anchor, line.to_string())),
             }
         }
-        entries
+        // stable, and lines before the first entry come first
+        lines.sort_by_key(|(anchor, _)| *anchor);
+        lines
     }
 
-    fn parse_line(line: &str) -> Option<Entry> {
+    /**
+     * Parse a "YYYY-MM-DD HH:MM: task" line
+     *
+     * Returns None for empty lines, and the (1-based) column and message for invalid lines.
+     */
+    fn parse_line(line: &str) -> Result<Option<Entry>, (usize, &'static str)> {
+        if let Some(pos) = line.find('\0') {
+            return Err((line[..pos].chars().count() + 1, "NUL character"));
+        }
+        let indent = line.len() - line.trim_start().len();
         let line = line.trim();
         if line.is_empty() {
-            return None;
+            return Ok(None);
         }
+        let column = |pos: usize| line[..pos].chars().count() + indent + 1;
 
-        if let Some((time, task)) = line.split_once(": ") {
-            if let Ok(dt) = NaiveDateTime::parse_from_str(time, TIME_FMT) {
-                Some(Entry {
-                    stop: dt,
-                    task: task.to_string(),
-                })
-            } else {
-                eprintln!(
-                    "WARNING: ignoring line with invalid date in timelog: {}",
-                    line
-                );
-                None
+        // the separator after the time is a colon and a space or tab
+        let separator = line.match_indices(':').map(|(i, _)| i).find(|&i| {
+            line[i + 1..]
+                .chars()
+                .next()
+                .is_some_and(char::is_whitespace)
+        });
+        if let Some(sep) = separator {
+            if let Ok(stop) = NaiveDateTime::parse_from_str(&line[..sep], TIME_FMT) {
+                return Ok(Some(Entry {
+                    stop,
+                    task: line[sep + 1..].trim().into(),
+                }));
             }
-        } else {
-            eprintln!("WARNING: ignoring invalid line in timelog: {}", line);
-            None
+        }
+
+        // be precise about what is wrong
+        let time_len = TIME_FMT_LEN.min(line.len());
+        match line
+            .get(..time_len)
+            .map(|t| NaiveDateTime::parse_from_str(t, TIME_FMT))
+        {
+            Some(Ok(_)) if line[time_len..] == *":" => Err((column(time_len + 1), "missing task")),
+            Some(Ok(_)) => Err((column(time_len), "expected \": \" after the time")),
+            _ if separator.is_some() => Err((column(0), "invalid date or time")),
+            _ => Err((column(0), "expected \"YYYY-MM-DD HH:MM: task\"")),
         }
     }
 
     fn format_store(&self) -> String {
         let mut output = String::new();
         let mut prev: Option<NaiveDate> = None;
+        let mut foreign = self.foreign.iter().peekable();
 
-        for entry in &self.entries {
+        while let Some((None, line)) = foreign.peek() {
+            writeln!(output, "{}", line).unwrap();
+            foreign.next();
+        }
+        for (i, entry) in self.entries.iter().enumerate() {
             // leave an empty line between days
-            if prev.is_some() && prev.unwrap() != entry.stop.date() {
+            let day = self.options.day(entry);
+            if prev.is_some_and(|p| p != day) {
                 output.push('\n');
             }
-            prev = Some(entry.stop.date());
+            prev = Some(day);
             writeln!(output, "{}", entry).expect("failed to format entry");
+
+            let next = self.entries.get(i + 1).map(|e| e.stop);
+            while let Some((Some(anchor), line)) = foreign.peek() {
+                if next.is_some_and(|n| n <= *anchor) {
+                    break;
+                }
+                writeln!(output, "{}", line).unwrap();
+                foreign.next();
+            }
+        }
+        for (_, line) in foreign {
+            writeln!(output, "{}", line).unwrap();
         }
 
         output
     }
 
-    pub fn save(&self) -> Result<(), io::Error> {
+    /**
+     * Can the new entries just be appended to the file?
+     */
+    fn is_append_only(&self) -> bool {
+        self.unchanged == self.loaded && self.entries.len() >= self.loaded
+    }
+
+    /**
+     * Entries added since loading, formatted like gtimelog appends them
+     */
+    fn format_appended(&self) -> String {
+        let mut output = String::new();
+        let new = &self.entries[self.loaded..];
+        if new.is_empty() {
+            return output;
+        }
+        if !self.original.is_empty() && !self.original.ends_with('\n') {
+            output.push('\n');
+        }
+        let mut prev = self.entries[..self.loaded]
+            .last()
+            .map(|e| self.options.day(e));
+        for entry in new {
+            let day = self.options.day(entry);
+            if prev.is_some_and(|p| p != day) {
+                output.push('\n');
+            }
+            prev = Some(day);
+            writeln!(output, "{}", entry).expect("failed to format entry");
+        }
+        output
+    }
+
+    /**
+     * File contents with the current entries
+     */
+    fn contents(&self) -> String {
+        if self.is_append_only() {
+            self.original.clone() + &self.format_appended()
+        } else {
+            self.format_store()
+        }
+    }
+
+    /**
+     * Changes to the file contents since loading, as unified diff
+     */
+    pub fn changes(&self) -> String {
+        diff::unified(&self.original, &self.contents())
+    }
+
+    pub fn save(&mut self) -> Result<(), io::Error> {
         assert!(self.filename.is_some());
-        let filename = self.filename.as_ref().unwrap();
+        let filename = self.filename.clone().unwrap();
+        if self.partial {
+            return Err(io::Error::other(
+                "cannot save a timelog with entries from archives or only its latest entries",
+            ));
+        }
+        if is_dry_run() {
+            print_dry_run(&filename, &self.changes());
+            return Ok(());
+        }
+        if self.options.read_only || is_stdin(&filename) {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{} is read-only", filename.display()),
+            ));
+        }
         if let Some(parent) = filename.parent() {
             fs::create_dir_all(parent)?;
         }
-        let mut f = File::create(filename)?;
-        write!(f, "{}", self.format_store())?;
+        let _lock = lock(&filename, LOCK_TIMEOUT)?;
+        if Stamp::of(&filename) != self.stamp {
+            self.rebase(&filename)?;
+        }
+        if self.options.hash_chain && !self.is_append_only() {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!(
+                    "{} is hash-chained, existing entries cannot be changed",
+                    filename.display()
+                ),
+            ));
+        }
+        let contents = self.contents();
+        if self.is_append_only() {
+            let mut f = OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(&filename)?;
+            write!(f, "{}", self.format_appended())?;
+            if self.options.hash_chain {
+                let chain_file = filename.with_file_name(chain::FILE);
+                let mut chain_contents = fs::read_to_string(&chain_file).unwrap_or_default();
+                chain_contents += &chain::extend(&chain_contents, &self.entries[self.loaded..]);
+                fs::write(chain_file, chain_contents)?;
+            }
+        } else {
+            write_atomically(&filename, &contents)?;
+        }
+        crate::info!(
+            "saved {} entries to {}",
+            self.entries.len(),
+            filename.display()
+        );
+        self.original = contents;
+        self.loaded = self.entries.len();
+        self.unchanged = self.loaded;
+        self.stamp = Stamp::of(&filename);
+
+        if self.sources_changed {
+            fs::write(
+                filename.with_file_name(provenance::FILE),
+                provenance::format(&self.sources),
+            )?;
+            self.sources_changed = false;
+        }
         Ok(())
     }
 
-    #[cfg(test)]
-    pub fn get_all(&self) -> impl Iterator<Item = &Entry> {
-        return self.entries.iter();
+    /**
+     * Re-read the file after another program changed it, and add the new entries to it again
+     *
+     * Other changes cannot be merged, so this fails for them instead of overwriting the file.
+     */
+    fn rebase(&mut self, filename: &PathBuf) -> Result<(), io::Error> {
+        if !self.is_append_only() {
+            return Err(io::Error::other(format!(
+                "{} was changed by another program, not overwriting it; please try again",
+                filename.display()
+            )));
+        }
+        crate::info!("{} changed since loading, reloading it", filename.display());
+        let new = self.entries.split_off(self.loaded);
+        let mut reloaded = Timelog::new_from_file(filename, &self.options)?;
+        for entry in new {
+            if let Some(source) = self.sources.get(&entry) {
+                reloaded.sources.insert(entry.clone(), *source);
+            }
+            let pos = reloaded.entries.partition_point(|e| e.stop <= entry.stop);
+            reloaded.unchanged = reloaded.unchanged.min(pos);
+            reloaded.entries.insert(pos, entry);
+        }
+        reloaded.source = self.source;
+        reloaded.sources_changed = self.sources_changed;
+        *self = reloaded;
+        Ok(())
+    }
+
+    /**
+     * Load the file again if another program changed it and there are no unsaved changes
+     *
+     * Returns whether it got reloaded.
+     */
+    pub fn reload_if_changed(&mut self) -> bool {
+        let Some(filename) = self.filename.clone() else {
+            return false;
+        };
+        let unsaved = !self.is_append_only() || self.entries.len() > self.loaded;
+        if is_stdin(&filename) || self.partial || unsaved || Stamp::of(&filename) == self.stamp {
+            return false;
+        }
+        crate::info!("{} changed, reloading it", filename.display());
+        let Ok(reloaded) = Timelog::new_from_file(&filename, &self.options) else {
+            return false;
+        };
+        let source = self.source;
+        *self = reloaded;
+        self.source = source;
+        true
+    }
+
+    /**
+     * Also read the archives of the years from `from` to `to`, if they are before the timelog
+     *
+     * Such a timelog is only for reading, it cannot be saved any more.
+     */
+    pub fn load_archives(&mut self, from: &NaiveDate, to: &NaiveDate) {
+        let Some(filename) = &self.filename else {
+            return;
+        };
+        if is_stdin(filename) || self.entries.first().is_some_and(|e| e.stop.date() <= *from) {
+            return;
+        }
+        for archive in archive::find(filename) {
+            if archive.year < from.year() || archive.year > to.year() {
+                continue;
+            }
+            match archive::read(&archive) {
+                Ok(raw) => {
+                    crate::info!("loaded archive {}", archive.path.display());
+                    self.add_archived(&raw);
+                }
+                Err(e) => eprintln!("{} {}", tr("WARNING: cannot read archive"), e),
+            }
+        }
+    }
+
+    fn add_archived(&mut self, raw: &str) {
+        let (entries, _) = Timelog::parse(raw);
+        self.entries.extend(entries);
+        // stable, so that entries with the same time keep their order
+        self.entries.sort_by_key(|e| e.stop);
+        self.entries.dedup();
+        self.partial = true;
+    }
+
+    pub fn get_all(&self) -> impl DoubleEndedIterator<Item = &Entry> {
+        self.entries.iter()
     }
 
+    /**
+     * All entries which stopped between `begin` and `end` (inclusive)
+     */
     pub fn get_time_range(&self, begin: NaiveDateTime, end: NaiveDateTime) -> &[Entry] {
-        let first = self
-            .entries
-            .iter()
-            .position(move |e| e.stop >= begin)
-            .unwrap_or(self.entries.len());
-        let last = self
-            .entries
-            .iter()
-            .position(move |e| e.stop > end)
-            .unwrap_or(self.entries.len());
+        // entries are sorted by time, so these are binary searches
+        let first = self.entries.partition_point(|e| e.stop < begin);
+        let last = self.entries.partition_point(|e| e.stop <= end);
+        &self.entries[first..last.max(first)]
+    }
 
-        &self.entries[first..last]
+    /**
+     * All entries of the logical days in `range`, like `from..=to` or `from..`
+     */
+    pub fn get_range(&self, range: impl RangeBounds<NaiveDate>) -> &[Entry] {
+        let midnight = self.options.virtual_midnight;
+        let begin = match range.start_bound() {
+            Bound::Included(from) => day_bounds(from, midnight).0,
+            Bound::Excluded(from) => day_bounds(from, midnight)
+                .1
+                .checked_add_signed(Duration::seconds(1))
+                .unwrap_or(NaiveDateTime::MAX),
+            Bound::Unbounded => NaiveDateTime::MIN,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(to) => day_bounds(to, midnight).1,
+            Bound::Excluded(to) => day_bounds(to, midnight)
+                .0
+                .checked_sub_signed(Duration::seconds(1))
+                .unwrap_or(NaiveDateTime::MIN),
+            Bound::Unbounded => NaiveDateTime::MAX,
+        };
+        self.get_time_range(begin, end)
     }
 
+    /**
+     * All entries of a logical day (see Options::virtual_midnight)
+     */
     pub fn get_day(&self, day: &NaiveDate) -> &[Entry] {
-        self.get_time_range(
-            day.and_hms_opt(0, 0, 0).unwrap(),
-            day.and_hms_opt(23, 59, 59).unwrap(),
-        )
+        self.get_date_range(day, day)
+    }
+
+    /**
+     * All entries from the beginning of `from` to the end of `to`
+     */
+    pub fn get_date_range(&self, from: &NaiveDate, to: &NaiveDate) -> &[Entry] {
+        self.get_range(*from..=*to)
+    }
+
+    /**
+     * All shifts (see split_shifts) which start between the beginning of `from` and the end of `to`
+     */
+    pub fn get_shifts(
+        &self,
+        from: &NaiveDate,
+        to: &NaiveDate,
+        min_break: Duration,
+    ) -> Vec<&[Entry]> {
+        split_shifts(&self.entries, min_break)
+            .into_iter()
+            .filter(|s| {
+                let start = s[0].stop.date();
+                start >= *from && start <= *to
+            })
+            .collect()
+    }
+
+    /**
+     * The last shift, if it is still ongoing at `now`
+     */
+    pub fn get_current_shift(&self, now: NaiveDateTime, min_break: Duration) -> &[Entry] {
+        match split_shifts(&self.entries, min_break).last() {
+            Some(shift) if now.signed_duration_since(shift[shift.len() - 1].stop) <= min_break => {
+                shift
+            }
+            _ => &[],
+        }
     }
 
     pub fn get_today(&self) -> &[Entry] {
-        self.get_day(&Local::now().date_naive())
+        self.get_day(&self.options.today())
     }
 
-    pub fn get_today_as_string(&self) -> String {
-        Local::now().format("%A, %F (week %U)").to_string()
+    /**
+     * Header for today; `date_fmt` replaces the %x (locale date) conversion
+     */
+    pub fn get_today_as_string(&self, locale: &Locale, date_fmt: &str) -> String {
+        let fmt = tr("%A, %x (week %U)").replace("%x", date_fmt);
+        locale.format_date(&self.options.today(), &fmt)
     }
 
+    /**
+     * All entries of the week (Monday to Sunday) which contains `day`
+     */
     pub fn get_week(&self, day: &NaiveDate) -> &[Entry] {
-        let week = day.iso_week().week();
-        let begin = NaiveDate::from_isoywd_opt(day.year(), week, Weekday::Mon)
-            .unwrap()
-            .and_hms_opt(0, 0, 0)
-            .unwrap();
-        let end = NaiveDate::from_isoywd_opt(day.year(), week + 1, Weekday::Mon)
-            .unwrap()
-            .and_hms_opt(0, 0, 0)
-            .unwrap();
-        self.get_time_range(begin, end)
+        let monday = *day - Duration::days(day.weekday().num_days_from_monday().into());
+        self.get_date_range(&monday, &(monday + Duration::days(6)))
+    }
+
+    /**
+     * All entries of the calendar month which contains `day`
+     */
+    pub fn get_month(&self, day: &NaiveDate) -> &[Entry] {
+        let first = day.with_day(1).unwrap();
+        let last = first
+            .checked_add_months(Months::new(1))
+            .map_or(NaiveDate::MAX, |next| next.pred_opt().unwrap());
+        self.get_date_range(&first, &last)
     }
 
     pub fn get_this_week(&self) -> &[Entry] {
-        self.get_week(&Local::now().date_naive())
+        self.get_week(&self.options.today())
     }
 
-    pub fn get_this_week_as_string(&self) -> String {
-        let now_local = Local::now();
-        let week_begin = now_local.day() - now_local.weekday().num_days_from_monday();
-        let week_end = week_begin + 6;
-        let this_week = format!("{} {}-{}", now_local.format("%B"), week_begin, week_end);
-        format!("{} ({})", now_local.format("%Y, week %U"), this_week)
+    pub fn get_this_week_as_string(&self, locale: &Locale) -> String {
+        let today = self.options.today();
+        let week_begin = today - Duration::days(today.weekday().num_days_from_monday().into());
+        let week_end = week_begin + Duration::days(6);
+        let this_week = format!(
+            "{} {}-{}",
+            locale.month_name(&week_begin),
+            week_begin.day(),
+            week_end.day()
+        );
+        format!("{} ({})", today.format(tr("%Y, week %U")), this_week)
     }
 
-    pub fn get_history(entries: &[Entry]) -> Vec<&String> {
+    /**
+     * Record entries which get added from now on as created by `source`, like "backfill"
+     */
+    pub fn set_source(&mut self, source: &str) {
+        self.source = source.into();
+    }
+
+    /**
+     * How the entry was created; MANUAL if there is no record
+     */
+    pub fn source_of(&self, entry: &Entry) -> &'static str {
+        self.sources.get(entry).map_or(MANUAL, |s| s.as_str())
+    }
+
+    fn push(&mut self, pos: usize, entry: Entry) {
+        if self.source != MANUAL {
+            self.sources.insert(entry.clone(), self.source);
+            self.sources_changed = true;
+        }
+        self.entries.insert(pos, entry);
+    }
+
+    pub fn get_history(entries: &[Entry]) -> Vec<&str> {
         let mut seen = HashSet::new();
         entries
             .iter()
-            .map(|e| &e.task)
+            .map(|e| e.task)
             .filter(|&t| {
                 if seen.contains(&t) {
                     false
@@ -253,28 +1104,201 @@ impl Timelog {
                     true
                 }
             })
+            .map(|t| t.as_str())
             .collect()
     }
 
+    /**
+     * Add a task that started at `start` and ends `now`, backdating as necessary
+     *
+     * If there is a gap between the previous entry and `start`, it gets closed with an "arrived"
+     * entry when this is the first entry of the day, otherwise with an unnamed slack entry ("**" by default).
+     */
+    pub fn add_since(&mut self, task: String, start: NaiveDateTime, now: NaiveDateTime) {
+        let start = self.snap(start);
+        let now = self.snap(now).max(start);
+        let same_day = |t| self.options.logical_date(t) == self.options.logical_date(start);
+        let gap = match self.entries.last().map(|e| e.stop) {
+            Some(last) if same_day(last) && last >= start => None,
+            Some(last) if same_day(last) => Some(self.options.markers.placeholder().to_string()),
+            _ => Some("arrived".to_string()),
+        };
+        if let Some(gap) = gap {
+            self.push(
+                self.entries.len(),
+                Entry {
+                    stop: start,
+                    task: gap.into(),
+                },
+            );
+        }
+        self.push(
+            self.entries.len(),
+            Entry {
+                stop: now,
+                task: task.into(),
+            },
+        );
+    }
+
+    /**
+     * Insert an entry at the right place, after existing entries with the same time
+     */
+    pub fn insert_at(&mut self, stop: NaiveDateTime, task: String) {
+        let pos = self.entries.partition_point(|e| e.stop <= stop);
+        self.unchanged = self.unchanged.min(pos);
+        self.push(
+            pos,
+            Entry {
+                stop,
+                task: task.into(),
+            },
+        );
+    }
+
+    /**
+     * Replace the task of the last entry; returns the previous last entry
+     */
+    pub fn amend_last(&mut self, task: String) -> Option<Entry> {
+        let pos = self.entries.len().checked_sub(1)?;
+        let old = self.remove_at(pos);
+        self.push(
+            pos,
+            Entry {
+                stop: old.stop,
+                task: task.into(),
+            },
+        );
+        Some(old)
+    }
+
+    /**
+     * Remove the (first) entry with the given time
+     */
+    pub fn remove(&mut self, stop: NaiveDateTime) -> Option<Entry> {
+        let pos = self.entries.iter().position(|e| e.stop == stop)?;
+        Some(self.remove_at(pos))
+    }
+
+    /**
+     * Remove an entry with the given time and task; returns whether there was one
+     */
+    pub fn remove_entry(&mut self, entry: &Entry) -> bool {
+        match self.entries.iter().position(|e| e == entry) {
+            Some(pos) => {
+                self.remove_at(pos);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn remove_at(&mut self, pos: usize) -> Entry {
+        self.unchanged = self.unchanged.min(pos);
+        let entry = self.entries.remove(pos);
+        if self.sources.remove(&entry).is_some() {
+            self.sources_changed = true;
+        }
+        entry
+    }
+
+    /**
+     * Move all entries from the beginning of `from` to the end of `to` by `offset`
+     *
+     * This fails if the shifted entries would overlap their unchanged neighbours. Returns the
+     * number of shifted entries.
+     */
+    pub fn shift(
+        &mut self,
+        from: &NaiveDate,
+        to: &NaiveDate,
+        offset: Duration,
+    ) -> Result<usize, String> {
+        let first = self
+            .entries
+            .partition_point(|e| self.options.day(e) < *from);
+        let last = self.entries.partition_point(|e| self.options.day(e) <= *to);
+        if first >= last {
+            return Ok(0);
+        }
+
+        let stops = self.entries[first..last]
+            .iter()
+            .map(|e| e.stop.checked_add_signed(offset))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| "shifted entries would be out of range".to_string())?;
+        let new_first = stops[0];
+        let new_last = stops[stops.len() - 1];
+        if first > 0 && new_first < self.entries[first - 1].stop {
+            return Err(format!(
+                "shifted entries would go before {}",
+                self.entries[first - 1]
+            ));
+        }
+        if last < self.entries.len() && new_last > self.entries[last].stop {
+            return Err(format!(
+                "shifted entries would go after {}",
+                self.entries[last]
+            ));
+        }
+
+        let mut moved = Vec::new();
+        for (e, stop) in self.entries[first..last].iter_mut().zip(stops) {
+            if let Some(source) = self.sources.remove(e) {
+                moved.push((stop, e.task, source));
+            }
+            e.stop = stop;
+        }
+        if !moved.is_empty() {
+            self.sources_changed = true;
+        }
+        for (stop, task, source) in moved {
+            self.sources.insert(Entry { stop, task }, source);
+        }
+        self.unchanged = self.unchanged.min(first);
+        Ok(last - first)
+    }
+
+    /**
+     * Snap the time of a new entry to the configured grid, but not before the last entry
+     */
+    fn snap(&self, t: NaiveDateTime) -> NaiveDateTime {
+        self.snap_to(t, self.options.snap_minutes)
+    }
+
+    fn snap_to(&self, t: NaiveDateTime, minutes: u32) -> NaiveDateTime {
+        if minutes == 0 {
+            return t;
+        }
+        let snapped = snap_to_grid(t, minutes);
+        match self.entries.last() {
+            Some(last) if last.stop > snapped && last.stop <= t => last.stop,
+            _ => snapped,
+        }
+    }
+
     pub fn add(&mut self, task: String) {
         let now = Local::now();
         let naivenow = NaiveDate::from_ymd_opt(now.year(), now.month(), now.day())
             .unwrap()
             .and_hms_opt(now.hour(), now.minute(), now.second())
             .unwrap();
-        self.entries.push(Entry {
-            task,
-            stop: naivenow,
-        });
+        let naivenow = self.snap(naivenow);
+        self.push(
+            self.entries.len(),
+            Entry {
+                task: task.into(),
+                stop: naivenow,
+            },
+        );
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chrono::Duration;
 
-    const TWO_DAYS: &'static str = "
+    const TWO_DAYS: &str = "
 2022-06-09 06:02: arrived
 2022-06-09 06:27: email
 2022-06-09 06:32: **tea
@@ -288,7 +1312,7 @@ mod tests {
 2022-06-10 16:00: customer joe: support
 ";
 
-    const TWO_WEEKS: &'static str = "
+    const TWO_WEEKS: &str = "
 2022-06-01 06:00: arrived
 2022-06-01 07:00: workw1
 2022-06-01 07:10: ** tea
@@ -310,36 +1334,75 @@ mod tests {
 
     #[test]
     fn test_parse_line_valid() {
-        let e1 = Timelog::parse_line("2022-05-31 13:59: email").unwrap();
+        let e1 = Timelog::parse_line("2022-05-31 13:59: email")
+            .unwrap()
+            .unwrap();
         assert_eq!(e1.task, "email");
         assert_eq!(e1.stop.format(TIME_FMT).to_string(), "2022-05-31 13:59");
 
-        let e2 = Timelog::parse_line("2022-05-31 14:07: read docs").unwrap();
+        let e2 = Timelog::parse_line("2022-05-31 14:07: read docs")
+            .unwrap()
+            .unwrap();
         assert_eq!(e2.task, "read docs");
         assert_eq!(e2.stop.format(TIME_FMT).to_string(), "2022-05-31 14:07");
 
         assert_eq!(e2.stop.signed_duration_since(e1.stop), Duration::minutes(8));
+
+        // tabs
+        let e3 = Timelog::parse_line("\t2022-05-31 14:07:\tmeeting: planning ")
+            .unwrap()
+            .unwrap();
+        assert_eq!(e3.task, "meeting: planning");
     }
 
     #[test]
     fn test_parse_line_invalid() {
-        assert_eq!(Timelog::parse_line(""), None);
-        assert_eq!(Timelog::parse_line("  "), None);
-        assert_eq!(Timelog::parse_line("a"), None);
-        // no ': -'
-        assert_eq!(Timelog::parse_line("2022-05-31 13:59 email"), None);
+        assert_eq!(Timelog::parse_line(""), Ok(None));
+        assert_eq!(Timelog::parse_line("  "), Ok(None));
+        assert_eq!(
+            Timelog::parse_line("a"),
+            Err((1, "expected \"YYYY-MM-DD HH:MM: task\""))
+        );
+        // no ': '
+        assert_eq!(
+            Timelog::parse_line("2022-05-31 13:59 email"),
+            Err((17, "expected \": \" after the time"))
+        );
+        assert_eq!(
+            Timelog::parse_line("  2022-05-31 13:59:"),
+            Err((20, "missing task"))
+        );
+        // partial timestamp
+        assert_eq!(
+            Timelog::parse_line("2022-05-31 13: email"),
+            Err((1, "invalid date or time"))
+        );
         // invalid time
-        assert_eq!(Timelog::parse_line("2022-05-31 25:61: email"), None);
+        assert_eq!(
+            Timelog::parse_line("2022-05-31 25:61: email"),
+            Err((1, "invalid date or time"))
+        );
         // invalid date
-        assert_eq!(Timelog::parse_line("2022-13-32 13:59: email"), None);
+        assert_eq!(
+            Timelog::parse_line("2022-13-32 13:59: email"),
+            Err((1, "invalid date or time"))
+        );
+        assert_eq!(
+            Timelog::parse_line("2022-05-31 13:59: ä\0"),
+            Err((20, "NUL character"))
+        );
+        // multi-byte characters where the time should be
+        assert!(Timelog::parse_line("2022-05-31 13:5äö: x").is_err());
+        assert!(Timelog::parse_line("ääääääääääääääää").is_err());
     }
 
     #[test]
     fn test_parse_valid() {
-        let entries = Timelog::parse("");
+        let (entries, has_errors) = Timelog::parse("");
         assert_eq!(entries.len(), 0);
+        assert!(!has_errors);
 
-        let entries = Timelog::parse(TWO_DAYS);
+        let (entries, _) = Timelog::parse(TWO_DAYS);
         assert_eq!(entries.len(), 10);
         assert_eq!(&format!("{}", entries[0]), "2022-06-09 06:02: arrived");
         assert_eq!(
@@ -349,15 +1412,76 @@ mod tests {
     }
 
     #[test]
-    #[should_panic]
-    fn test_parse_out_of_order() {
-        Timelog::parse(
+    fn test_parse_errors() {
+        let (entries, errors) = parse_entries(
             "
 2022-06-09 06:02: arrived
 2022-06-09 06:10: ** tea
 2022-06-08 07:32: huh, previous day
+garbage
+2022-06-09 07:00: code
 ",
         );
+        assert_eq!(
+            entries.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            vec![
+                "2022-06-08 07:32: huh, previous day",
+                "2022-06-09 06:02: arrived",
+                "2022-06-09 06:10: ** tea",
+                "2022-06-09 07:00: code",
+            ]
+        );
+        assert_eq!(
+            errors,
+            vec![
+                ParseError {
+                    line: 4,
+                    column: 1,
+                    message: "goes back in time"
+                },
+                ParseError {
+                    line: 5,
+                    column: 1,
+                    message: "expected \"YYYY-MM-DD HH:MM: task\""
+                },
+            ]
+        );
+        assert_eq!(
+            errors[1].to_string(),
+            "line 5, column 1: expected \"YYYY-MM-DD HH:MM: task\""
+        );
+    }
+
+    #[test]
+    fn test_parse_garbage() {
+        // cheap deterministic fuzzing; see fuzz/ for the real thing
+        let pieces = [
+            "2022-06-09",
+            " ",
+            "06:02",
+            ":",
+            ": ",
+            "\t",
+            "\n",
+            "\r\n",
+            "\0",
+            "ä",
+            "**",
+            "x",
+            "9",
+            "-",
+            "2022-02-30 25:00: ",
+        ];
+        let mut state: u32 = 42;
+        for _ in 0..2000 {
+            let mut raw = String::new();
+            for _ in 0..(state % 40) {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                raw.push_str(pieces[(state >> 16) as usize % pieces.len()]);
+            }
+            let (entries, _) = parse_entries(&raw);
+            assert!(entries.windows(2).all(|w| w[0].stop <= w[1].stop));
+        }
     }
 
     #[test]
@@ -365,8 +1489,11 @@ mod tests {
         let tl = Timelog::new_from_string("");
         assert_eq!(tl.get_all().next(), None);
 
-        let tl = Timelog::new_from_file(&PathBuf::from("/nonexisting"));
+        let tl =
+            Timelog::new_from_file(&PathBuf::from("/nonexisting"), &Options::default()).unwrap();
         assert_eq!(tl.get_all().next(), None);
+        // a directory cannot be read
+        assert!(Timelog::new_from_file(&env::temp_dir(), &Options::default()).is_err());
 
         let tl = Timelog::new_from_string(TWO_DAYS);
         let mut entries = tl.get_all();
@@ -410,6 +1537,32 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_get_date_range() {
+        let tl = Timelog::new_from_string(TWO_WEEKS);
+        let entries = tl.get_date_range(
+            &NaiveDate::from_ymd_opt(2022, 6, 3).unwrap(),
+            &NaiveDate::from_ymd_opt(2022, 6, 8).unwrap(),
+        );
+        assert_eq!(entries.len(), 6);
+        assert_eq!(&format!("{}", entries[0]), "2022-06-03 06:00: arrived");
+        assert_eq!(&format!("{}", entries[5]), "2022-06-08 07:10: ** tea");
+
+        // single day
+        let entries = tl.get_date_range(
+            &NaiveDate::from_ymd_opt(2022, 6, 9).unwrap(),
+            &NaiveDate::from_ymd_opt(2022, 6, 9).unwrap(),
+        );
+        assert_eq!(entries.len(), 2);
+
+        // inverted range
+        let entries = tl.get_date_range(
+            &NaiveDate::from_ymd_opt(2022, 6, 9).unwrap(),
+            &NaiveDate::from_ymd_opt(2022, 6, 1).unwrap(),
+        );
+        assert_eq!(entries, &[]);
+    }
+
     #[test]
     fn test_get_week() {
         let tl = Timelog::new_from_string("");
@@ -430,6 +1583,74 @@ mod tests {
         assert_eq!(entries.len(), 7);
         assert_eq!(&format!("{}", entries[0]), "2022-06-08 06:00: arrived");
         assert_eq!(&format!("{}", entries[6]), "2022-06-10 07:00: workw2");
+
+        // across the turn of the year
+        let tl = Timelog::new_from_string("2021-12-31 09:00: arrived\n2022-01-02 10:00: x\n");
+        let entries = tl.get_week(&NaiveDate::from_ymd_opt(2021, 12, 31).unwrap());
+        assert_eq!(entries.len(), 2);
+    }
+
+    #[test]
+    fn test_get_month() {
+        let tl = Timelog::new_from_string(TWO_WEEKS);
+        assert_eq!(
+            tl.get_month(&NaiveDate::from_ymd_opt(2022, 6, 20).unwrap())
+                .len(),
+            13
+        );
+        assert!(tl
+            .get_month(&NaiveDate::from_ymd_opt(2022, 5, 31).unwrap())
+            .is_empty());
+
+        let tl = Timelog::new_from_string(
+            "2022-11-30 10:00: x\n2022-12-01 10:00: a\n2022-12-31 23:00: b\n2023-01-01 10:00: y\n",
+        );
+        let entries = tl.get_month(&NaiveDate::from_ymd_opt(2022, 12, 15).unwrap());
+        assert_eq!(entries.len(), 2);
+        assert_eq!(&format!("{}", entries[1]), "2022-12-31 23:00: b");
+    }
+
+    #[test]
+    fn test_virtual_midnight() {
+        let four = NaiveTime::from_hms_opt(4, 0, 0).unwrap();
+        let day = NaiveDate::from_ymd_opt(2022, 6, 10).unwrap();
+        assert_eq!(day_of(at(11, 2, 30), four), day);
+        assert_eq!(day_of(at(11, 4, 0), four), day.succ_opt().unwrap());
+        assert_eq!(day_of(at(10, 23, 0), four), day);
+        assert_eq!(
+            day_of(at(11, 2, 30), NaiveTime::MIN),
+            day.succ_opt().unwrap()
+        );
+        assert_eq!(
+            day_bounds(&day, four),
+            (at(10, 4, 0), at(11, 3, 59) + Duration::seconds(59))
+        );
+
+        // a session across midnight stays on one day
+        let tl = Timelog::new_from_string(
+            "2022-06-10 20:00: arrived\n2022-06-11 01:30: code\n2022-06-11 09:00: arrived\n",
+        );
+        let (start, end) = day_bounds(&day, four);
+        let entries = tl.get_time_range(start, end);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].task, "code");
+        let (start, end) = day_bounds(&day, NaiveTime::MIN);
+        assert_eq!(tl.get_time_range(start, end).len(), 1);
+        // each timelog splits days at its own virtual midnight
+        let contents = "2022-06-10 20:00: arrived\n2022-06-11 01:30: code\n";
+        let late = Timelog::new_from_contents(
+            contents.to_string(),
+            None,
+            &Options {
+                virtual_midnight: four,
+                ..Options::default()
+            },
+        );
+        assert_eq!(late.get_day(&day).len(), 2);
+        assert_eq!(late.options().day(&late.entries[1]), day);
+        let tl = Timelog::new_from_string(contents);
+        assert_eq!(tl.get_day(&day).len(), 1);
+        assert_eq!(tl.options().day(&tl.entries[1]), day.succ_opt().unwrap());
     }
 
     #[test]
@@ -462,6 +1683,185 @@ mod tests {
         );
     }
 
+    fn at(d: u32, h: u32, m: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2022, 6, d)
+            .unwrap()
+            .and_hms_opt(h, m, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_changes() {
+        let mut tl = Timelog::new_from_string(TWO_DAYS);
+        assert_eq!(tl.changes(), "");
+        tl.add_since("code".to_string(), at(10, 16, 0), at(10, 17, 0));
+        assert_eq!(tl.changes(), "@@ -12,0 +13,1 @@\n+2022-06-10 17:00: code\n");
+
+        let mut tl2 = Timelog::new_from_string(&tl.format_store());
+        assert_eq!(tl2.changes(), "");
+        tl2.remove(at(10, 12, 30));
+        assert_eq!(
+            tl2.changes(),
+            "@@ -8,1 +7,0 @@\n-2022-06-10 12:30: **lunch\n"
+        );
+
+        tl.entries.clear();
+        assert!(tl
+            .changes()
+            .lines()
+            .all(|l| l.starts_with('-') || l.starts_with('@')));
+    }
+
+    #[test]
+    fn test_add_archived() {
+        let mut tl = Timelog::new_from_string(TWO_DAYS);
+        tl.filename = Some(PathBuf::from("/nonexisting/timelog.txt"));
+        let before = tl.get_date_range(&NaiveDate::MIN, &NaiveDate::MAX).len();
+        tl.add_archived(
+            "2021-12-31 08:00: arrived\n2021-12-31 09:00: code\n2022-06-09 06:02: arrived\n",
+        );
+        let day = NaiveDate::from_ymd_opt(2021, 12, 31).unwrap();
+        assert_eq!(tl.get_day(&day).len(), 2);
+        // already in the timelog
+        assert_eq!(
+            tl.get_date_range(&NaiveDate::MIN, &NaiveDate::MAX).len(),
+            before + 2
+        );
+        assert!(tl.save().is_err());
+    }
+
+    #[test]
+    fn test_read_tail() {
+        let path = env::temp_dir().join(format!("rtimelog-tail-test-{}.txt", std::process::id()));
+        // many days, so that the tail spans several blocks
+        let mut contents = String::new();
+        let mut day = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+        for _ in 0..1000 {
+            writeln!(contents, "{} 08:00: arrived", day).unwrap();
+            writeln!(contents, "{} 12:00: code: ümlauts\n", day).unwrap();
+            day = day.succ_opt().unwrap();
+        }
+        fs::write(&path, &contents).unwrap();
+
+        let last = NaiveDate::from_ymd_opt(2022, 9, 26).unwrap();
+        let tail = read_tail(&path, last).unwrap();
+        assert!(tail.len() < contents.len());
+        assert!(contents.ends_with(&tail));
+        let (entries, errors) = parse_entries(&tail);
+        assert!(errors.is_empty());
+        assert!(entries.len() >= 2);
+        assert!(entries[0].stop.date() < last);
+        assert_eq!(entries.last().unwrap().stop.date(), last);
+
+        // everything
+        let start = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+        assert_eq!(read_tail(&path, start).unwrap(), contents);
+        // empty file
+        fs::write(&path, "").unwrap();
+        assert_eq!(read_tail(&path, last).unwrap(), "");
+
+        // a note line which starts with a number does not end the day
+        let mut contents = String::from("2022-09-25 17:00: code\n\n");
+        for minute in 0..300 {
+            writeln!(
+                contents,
+                "2022-09-26 {:02}:{:02}: code",
+                8 + minute / 60,
+                minute % 60
+            )
+            .unwrap();
+        }
+        contents.push_str("1 note about the day, which has many entries\n");
+        fs::write(&path, &contents).unwrap();
+        let (entries, _) = parse_entries(&read_tail(&path, last).unwrap());
+        assert_eq!(entries.len(), 301);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    // written by gtimelog, with an unusual but valid task, and a note line
+    const GTIMELOG: &str = include_str!("../tests/data/gtimelog-timelog.txt");
+
+    #[test]
+    fn test_gtimelog_roundtrip() {
+        let mut tl = Timelog::new_from_string(GTIMELOG);
+        assert_eq!(tl.entries.len(), 7);
+        assert_eq!(tl.contents(), GTIMELOG);
+
+        // appended like gtimelog does
+        tl.add_since("code".to_string(), at(10, 11, 0), at(10, 12, 0));
+        assert_eq!(
+            tl.contents(),
+            GTIMELOG.to_string() + "2022-06-10 12:00: code\n"
+        );
+        tl.add_since("email".to_string(), at(11, 8, 0), at(11, 8, 30));
+        assert_eq!(
+            tl.contents(),
+            GTIMELOG.to_string()
+                + "2022-06-10 12:00: code\n\n2022-06-11 08:00: arrived\n2022-06-11 08:30: email\n"
+        );
+
+        // changing existing entries rewrites the file, but keeps the note
+        tl.remove(at(9, 12, 0));
+        let tl2 = Timelog::new_from_string(&tl.contents());
+        assert_eq!(tl2.get_all().cloned().collect::<Vec<_>>(), tl.entries);
+        assert_eq!(
+            tl.contents(),
+            "2022-06-09 08:55: arrived
+2022-06-09 09:40: gtimelog: review patches
+2022-06-09 17:30: Kunde Müller: on-site support ☕
+
+2022-06-10 09:00: arrived
+2022-06-10 10:15: meeting: planning
+remember to ask about the budget
+2022-06-10 11:00: **
+2022-06-10 12:00: code
+
+2022-06-11 08:00: arrived
+2022-06-11 08:30: email
+"
+        );
+
+        // file without a trailing newline
+        let mut tl = Timelog::new_from_string("2022-06-10 09:00: arrived");
+        tl.add_since("code".to_string(), at(10, 9, 0), at(10, 10, 0));
+        assert_eq!(
+            tl.contents(),
+            "2022-06-10 09:00: arrived\n2022-06-10 10:00: code\n"
+        );
+    }
+
+    #[test]
+    fn test_sources() {
+        let mut tl = Timelog::new_from_string(TWO_DAYS);
+        assert_eq!(tl.source_of(&tl.entries[0]), MANUAL);
+
+        tl.set_source("import:toggl");
+        tl.add_since("code".to_string(), at(11, 9, 0), at(11, 10, 0));
+        tl.set_source(MANUAL);
+        tl.insert_at(at(11, 9, 30), "email".to_string());
+        let sources: Vec<_> = tl.entries[10..].iter().map(|e| tl.source_of(e)).collect();
+        assert_eq!(sources, vec!["import:toggl", MANUAL, "import:toggl"]);
+        assert!(tl.sources_changed);
+
+        // follows shifted entries
+        tl.shift(
+            &NaiveDate::from_ymd_opt(2022, 6, 11).unwrap(),
+            &NaiveDate::from_ymd_opt(2022, 6, 11).unwrap(),
+            Duration::hours(1),
+        )
+        .unwrap();
+        assert_eq!(&format!("{}", tl.entries[12]), "2022-06-11 11:00: code");
+        assert_eq!(tl.source_of(&tl.entries[12]), "import:toggl");
+        assert_eq!(
+            provenance::format(&tl.sources),
+            "import:toggl\t2022-06-11 10:00: arrived\nimport:toggl\t2022-06-11 11:00: code\n"
+        );
+
+        tl.remove(at(11, 10, 0));
+        assert_eq!(tl.sources.len(), 1);
+    }
+
     #[test]
     fn test_add() {
         let mut tl = Timelog::new_from_string("");
@@ -469,4 +1869,298 @@ mod tests {
         assert_eq!(tl.entries.len(), 1);
         assert_eq!(tl.entries[0].task, "think hard");
     }
+
+    #[test]
+    fn test_add_since() {
+        let at = |h, m| {
+            NaiveDate::from_ymd_opt(2022, 6, 10)
+                .unwrap()
+                .and_hms_opt(h, m, 0)
+                .unwrap()
+        };
+
+        // first entry of the day starts it
+        let mut tl = Timelog::new_from_string("2022-06-09 16:00: yesterday's work");
+        tl.add_since("code".to_string(), at(9, 0), at(9, 30));
+        assert_eq!(
+            tl.format_store(),
+            "2022-06-09 16:00: yesterday's work
+
+2022-06-10 09:00: arrived
+2022-06-10 09:30: code
+"
+        );
+
+        // gap gets closed with slack
+        tl.add_since("review".to_string(), at(10, 0), at(10, 30));
+        assert_eq!(tl.entries.len(), 5);
+        assert_eq!(&format!("{}", tl.entries[3]), "2022-06-10 10:00: **");
+        assert_eq!(&format!("{}", tl.entries[4]), "2022-06-10 10:30: review");
+
+        // start before the last entry: just append
+        tl.add_since("email".to_string(), at(10, 15), at(11, 0));
+        assert_eq!(tl.entries.len(), 6);
+        assert_eq!(&format!("{}", tl.entries[5]), "2022-06-10 11:00: email");
+    }
+
+    #[test]
+    fn test_snap_to_grid() {
+        let at = |h, m, s| {
+            NaiveDate::from_ymd_opt(2022, 6, 10)
+                .unwrap()
+                .and_hms_opt(h, m, s)
+                .unwrap()
+        };
+        assert_eq!(snap_to_grid(at(9, 2, 29), 5), at(9, 0, 0));
+        assert_eq!(snap_to_grid(at(9, 2, 30), 5), at(9, 5, 0));
+        assert_eq!(snap_to_grid(at(9, 7, 0), 15), at(9, 0, 0));
+        assert_eq!(snap_to_grid(at(9, 8, 0), 15), at(9, 15, 0));
+        assert_eq!(snap_to_grid(at(9, 8, 59), 1), at(9, 9, 0));
+        assert_eq!(snap_to_grid(at(9, 8, 59), 0), at(9, 8, 0));
+        // stays on the same day
+        assert_eq!(snap_to_grid(at(23, 58, 0), 5), at(23, 55, 0));
+
+        // but not before the last entry
+        let tl = Timelog::new_from_string("2022-06-10 09:03: code\n");
+        assert_eq!(tl.snap_to(at(9, 4, 0), 5), at(9, 5, 0));
+        assert_eq!(tl.snap_to(at(9, 3, 30), 10), at(9, 3, 0));
+        assert_eq!(tl.snap_to(at(9, 4, 0), 0), at(9, 4, 0));
+    }
+
+    #[test]
+    fn test_insert_remove() {
+        let mut tl = Timelog::new_from_string(TWO_DAYS);
+        let at = |d, h, m| {
+            NaiveDate::from_ymd_opt(2022, 6, d)
+                .unwrap()
+                .and_hms_opt(h, m, 0)
+                .unwrap()
+        };
+
+        tl.insert_at(at(9, 8, 0), "review".to_string());
+        assert_eq!(tl.entries.len(), 11);
+        assert_eq!(&format!("{}", tl.entries[3]), "2022-06-09 08:00: review");
+
+        // same time goes after the existing one
+        tl.insert_at(at(10, 7, 0), "coffee".to_string());
+        assert_eq!(&format!("{}", tl.entries[5]), "2022-06-10 07:00: arrived");
+        assert_eq!(&format!("{}", tl.entries[6]), "2022-06-10 07:00: coffee");
+
+        // at the end
+        tl.insert_at(at(10, 17, 0), "email".to_string());
+        assert_eq!(tl.entries[12].task, "email");
+
+        assert_eq!(tl.remove(at(9, 8, 0)).unwrap().task, "review");
+        assert_eq!(tl.remove(at(9, 8, 0)), None);
+        assert_eq!(tl.entries.len(), 12);
+    }
+
+    #[test]
+    fn test_get_range() {
+        let tl = Timelog::new_from_string(TWO_WEEKS);
+        let day = |d| NaiveDate::from_ymd_opt(2022, 6, d).unwrap();
+        assert_eq!(tl.get_range(day(6)..=day(6)), tl.get_day(&day(6)));
+        assert_eq!(
+            tl.get_range(day(6)..day(8)),
+            tl.get_date_range(&day(6), &day(7))
+        );
+        assert_eq!(tl.get_range(..), tl.get_all().cloned().collect::<Vec<_>>());
+        assert_eq!(
+            tl.get_range(day(9)..).len() + tl.get_range(..day(9)).len(),
+            tl.get_all().count()
+        );
+        assert_eq!(
+            tl.get_range((Bound::Excluded(day(8)), Bound::Unbounded)),
+            tl.get_range(day(9)..)
+        );
+        assert!(tl.get_range(day(10)..day(6)).is_empty());
+        assert!(tl.get_range(NaiveDate::MIN..=NaiveDate::MIN).is_empty());
+        assert_eq!(
+            tl.get_range(NaiveDate::MIN..=NaiveDate::MAX).len(),
+            tl.get_all().count()
+        );
+    }
+
+    #[test]
+    fn test_new_from_file_since() {
+        let mut contents = String::new();
+        let mut day = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+        for _ in 0..1000 {
+            writeln!(contents, "{} 08:00: arrived\n{} 12:00: code\n", day, day).unwrap();
+            day = day.succ_opt().unwrap();
+        }
+        let path = temp_timelog("since", &contents);
+        let since = NaiveDate::from_ymd_opt(2022, 9, 1).unwrap();
+        let full = Timelog::new_from_file(&path, &Options::default()).unwrap();
+        let mut tail = Timelog::new_from_file_since(&path, since, &Options::default()).unwrap();
+        assert!(tail.get_all().count() < full.get_all().count());
+        assert_eq!(tail.get_range(since..), full.get_range(since..));
+        assert!(tail.save().is_err());
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_amend_last() {
+        let mut tl = Timelog::new_from_string(TWO_DAYS);
+        let old = tl.amend_last("code: fix tests".to_string()).unwrap();
+        assert_eq!(tl.entries.len(), 10);
+        assert_eq!(tl.entries[9].stop, old.stop);
+        assert_eq!(tl.entries[9].task, "code: fix tests");
+        assert!(!tl.is_append_only());
+
+        assert_eq!(
+            Timelog::new_from_string("").amend_last("x".to_string()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_write_atomically() {
+        let path = env::temp_dir().join(format!("rtimelog-atomic-test-{}.txt", std::process::id()));
+        write_atomically(&path, "one\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "one\n");
+        write_atomically(&path, "two\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "two\n");
+        assert!(!path
+            .with_file_name(format!(
+                ".rtimelog-atomic-test-{}.txt.tmp",
+                std::process::id()
+            ))
+            .exists());
+        fs::remove_file(&path).unwrap();
+    }
+
+    fn temp_timelog(name: &str, contents: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("rtimelog-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&path).unwrap();
+        let path = path.join("timelog.txt");
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_concurrent_append() {
+        let path = temp_timelog("append", "2022-06-10 07:00: arrived\n");
+        let mut tl = Timelog::new_from_file(&path, &Options::default()).unwrap();
+        // another program appends meanwhile
+        let mut other = Timelog::new_from_file(&path, &Options::default()).unwrap();
+        other.insert_at(at(10, 9, 0), "email".to_string());
+        other.save().unwrap();
+
+        tl.insert_at(at(10, 8, 0), "code".to_string());
+        tl.save().unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "2022-06-10 07:00: arrived\n2022-06-10 08:00: code\n2022-06-10 09:00: email\n"
+        );
+        assert_eq!(tl.entries.len(), 3);
+
+        // own changes of existing entries are not merged
+        other.remove(at(10, 7, 0));
+        assert!(other.save().is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 3);
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_reload_if_changed() {
+        let path = temp_timelog("reload", "2022-06-10 07:00: arrived\n");
+        let mut tl = Timelog::new_from_file(&path, &Options::default()).unwrap();
+        assert!(!tl.reload_if_changed());
+
+        fs::write(&path, "2022-06-10 07:00: arrived\n2022-06-10 08:00: code\n").unwrap();
+        assert!(tl.reload_if_changed());
+        assert_eq!(tl.entries.len(), 2);
+        assert!(!tl.reload_if_changed());
+
+        // unsaved changes are kept
+        tl.insert_at(at(10, 9, 0), "email".to_string());
+        fs::write(&path, "").unwrap();
+        assert!(!tl.reload_if_changed());
+        assert_eq!(tl.entries.len(), 3);
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_lock() {
+        let path = temp_timelog("lock", "");
+        let held = lock(&path, LOCK_TIMEOUT).unwrap();
+        let e = lock(&path, std::time::Duration::ZERO).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::WouldBlock);
+        drop(held);
+        assert!(lock(&path, std::time::Duration::ZERO).is_ok());
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_shift() {
+        let mut tl = Timelog::new_from_string(TWO_WEEKS);
+        let day = |d| NaiveDate::from_ymd_opt(2022, 6, d).unwrap();
+
+        assert_eq!(tl.shift(&day(9), &day(9), Duration::minutes(15)), Ok(2));
+        let entries = tl.get_day(&day(9));
+        assert_eq!(&format!("{}", entries[0]), "2022-06-09 06:15: arrived");
+        assert_eq!(&format!("{}", entries[1]), "2022-06-09 07:15: workw2");
+
+        assert_eq!(tl.shift(&day(8), &day(10), Duration::minutes(-30)), Ok(7));
+        assert_eq!(&format!("{}", tl.entries[6]), "2022-06-08 05:30: arrived");
+        assert_eq!(&format!("{}", tl.entries[12]), "2022-06-10 06:30: workw2");
+
+        // empty selection
+        assert_eq!(tl.shift(&day(4), &day(5), Duration::minutes(10)), Ok(0));
+
+        // must not overlap neighbours
+        assert!(tl.shift(&day(8), &day(8), Duration::days(-5)).is_err());
+        assert!(tl.shift(&day(1), &day(1), Duration::days(2)).is_err());
+        // must stay in the calendar
+        assert!(tl
+            .shift(&day(10), &day(10), Duration::hours(9999999999))
+            .is_err());
+        assert!(tl
+            .shift(&day(1), &day(1), Duration::hours(-9999999999))
+            .is_err());
+        assert_eq!(&format!("{}", tl.entries[0]), "2022-06-01 06:00: arrived");
+    }
+
+    #[test]
+    fn test_shifts() {
+        let tl = Timelog::new_from_string(
+            "
+2022-06-08 22:00: arrived
+2022-06-09 02:00: patrol
+2022-06-09 06:00: patrol
+
+2022-06-09 22:00: arrived
+2022-06-10 03:00: patrol
+2022-06-10 06:30: paperwork
+",
+        );
+        let day = |d| NaiveDate::from_ymd_opt(2022, 6, d).unwrap();
+        let at = |d, h| day(d).and_hms_opt(h, 0, 0).unwrap();
+
+        assert!(split_shifts(&[], Duration::hours(6)).is_empty());
+        let shifts = split_shifts(&tl.entries, Duration::hours(6));
+        assert_eq!(shifts.len(), 2);
+        assert_eq!(shifts[0].len(), 3);
+        assert_eq!(shifts[1].len(), 3);
+        // a smaller break splits more
+        assert_eq!(split_shifts(&tl.entries, Duration::hours(4)).len(), 3);
+
+        let shifts = tl.get_shifts(&day(9), &day(9), Duration::hours(6));
+        assert_eq!(shifts.len(), 1);
+        assert_eq!(&format!("{}", shifts[0][0]), "2022-06-09 22:00: arrived");
+        assert_eq!(tl.get_shifts(&day(8), &day(9), Duration::hours(6)).len(), 2);
+        assert!(tl
+            .get_shifts(&day(10), &day(10), Duration::hours(6))
+            .is_empty());
+
+        assert_eq!(tl.get_current_shift(at(10, 8), Duration::hours(6)).len(), 3);
+        assert!(tl
+            .get_current_shift(at(10, 13), Duration::hours(6))
+            .is_empty());
+    }
 }
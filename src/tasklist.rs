@@ -0,0 +1,340 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate chrono;
+extern crate rustyline;
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use chrono::Duration;
+use rustyline::completion::Completer;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+
+use crate::activity::category;
+use crate::markers::{Kind, Markers};
+use crate::store::Entry;
+
+/// name of the cached remote task list in the data directory, same as gtimelog's
+pub const CACHE_FILE: &str = "remote-tasks.txt";
+
+/// name of the local task list in the data directory, same as gtimelog's
+pub const LOCAL_FILE: &str = "tasks.txt";
+
+/**
+ * List of known tasks, in gtimelog's tasks.txt format
+ *
+ * That has one "category: task" per line; empty lines and lines starting with `#` are ignored.
+ */
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TaskList {
+    tasks: Vec<String>,
+}
+
+impl TaskList {
+    pub fn parse(contents: &str) -> TaskList {
+        TaskList {
+            tasks: contents
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                .map(str::to_string)
+                .collect(),
+        }
+    }
+
+    /**
+     * Get the task list from `url`, or from the cached copy if that is younger than `expiry`
+     *
+     * If fetching fails (e.g. when offline), this falls back to the cached copy regardless of its
+     * age.
+     */
+    pub fn load(url: &str, cache: &Path, expiry: Duration) -> Result<TaskList, String> {
+        TaskList::load_with(url, cache, expiry, fetch)
+    }
+
+    fn load_with(
+        url: &str,
+        cache: &Path,
+        expiry: Duration,
+        fetch: impl FnOnce(&str) -> Result<String, String>,
+    ) -> Result<TaskList, String> {
+        let age = fs::metadata(cache)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.elapsed().ok())
+            .and_then(|age| Duration::from_std(age).ok());
+        if age.is_some_and(|age| age < expiry) {
+            if let Ok(contents) = fs::read_to_string(cache) {
+                crate::debug!("using cached task list {}", cache.display());
+                return Ok(TaskList::parse(&contents));
+            }
+        }
+
+        match fetch(url) {
+            Ok(contents) => {
+                crate::info!("fetched task list from {}", url);
+                if let Err(e) = fs::write(cache, &contents) {
+                    eprintln!(
+                        "WARNING: cannot cache task list in {}: {}",
+                        cache.display(),
+                        e
+                    );
+                }
+                Ok(TaskList::parse(&contents))
+            }
+            Err(e) => match fs::read_to_string(cache) {
+                Ok(contents) => {
+                    eprintln!(
+                        "WARNING: cannot fetch task list from {}, using cached copy: {}",
+                        url, e
+                    );
+                    Ok(TaskList::parse(&contents))
+                }
+                Err(_) => Err(format!("cannot fetch task list from {}: {}", url, e)),
+            },
+        }
+    }
+
+    /**
+     * Distinct work tasks of previous entries, most recently used first
+     *
+     * Slacking, ignored entries (according to `markers`), and starting the day are left out.
+     */
+    pub fn from_history<'a>(
+        entries: impl DoubleEndedIterator<Item = &'a Entry>,
+        markers: &Markers,
+    ) -> TaskList {
+        let mut seen = HashSet::new();
+        let tasks = entries
+            .rev()
+            .map(|e| e.task.as_str())
+            .filter(|t| markers.kind(t) == Kind::Work && *t != "arrived")
+            .filter(|t| seen.insert(*t))
+            .map(str::to_string)
+            .collect();
+        TaskList { tasks }
+    }
+
+    pub fn tasks(&self) -> &[String] {
+        &self.tasks
+    }
+
+    /**
+     * Add the tasks of another list which are not in this one yet
+     */
+    pub fn merge(&mut self, other: TaskList) {
+        for task in other.tasks {
+            if !self.tasks.contains(&task) {
+                self.tasks.push(task);
+            }
+        }
+    }
+
+    /**
+     * Add a task to the front, or move it there if it is in the list already
+     */
+    pub fn add(&mut self, task: &str) {
+        self.tasks.retain(|t| t != task);
+        self.tasks.insert(0, task.to_string());
+    }
+
+    /**
+     * Does any task in the list have this category?
+     */
+    pub fn has_category(&self, cat: &str) -> bool {
+        self.tasks.iter().any(|t| category(t) == Some(cat))
+    }
+
+    /**
+     * Tasks which start with `prefix`
+     */
+    pub fn complete(&self, prefix: &str) -> Vec<&str> {
+        self.tasks
+            .iter()
+            .filter(|t| t.starts_with(prefix))
+            .map(String::as_str)
+            .collect()
+    }
+
+    /**
+     * Is the task (or at least its category) in the list?
+     *
+     * Slacking (according to `markers`) and starting the day are always fine.
+     */
+    pub fn is_known(&self, task: &str, markers: &Markers) -> bool {
+        let task = task.trim();
+        if task.is_empty() || markers.kind(task) != Kind::Work || task == "arrived" {
+            return true;
+        }
+        self.tasks.iter().any(|t| t == task) || category(task).is_some_and(|c| self.has_category(c))
+    }
+}
+
+/**
+ * Download the task list with curl, which handles http(s):// and file:// URLs
+ */
+fn fetch(url: &str) -> Result<String, String> {
+    let output = process::Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location"])
+        .args(["--max-time", "10"])
+        .arg(url)
+        .output()
+        .map_err(|e| format!("cannot run curl: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// complete whole entries in the interactive prompt
+impl Completer for TaskList {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let candidates = TaskList::complete(self, &line[..pos]);
+        Ok((0, candidates.into_iter().map(str::to_string).collect()))
+    }
+}
+
+impl Hinter for TaskList {
+    type Hint = String;
+}
+
+impl Highlighter for TaskList {}
+
+impl Validator for TaskList {}
+
+impl Helper for TaskList {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    const TASKS: &str = "
+# shared task list
+rtimelog: code
+rtimelog: review
+customer joe: support
+
+internal: meetings
+";
+
+    #[test]
+    fn test_parse() {
+        let list = TaskList::parse(TASKS);
+        assert_eq!(list.tasks().len(), 4);
+        assert_eq!(
+            list.complete("rtimelog"),
+            vec!["rtimelog: code", "rtimelog: review"]
+        );
+        assert_eq!(list.complete("cust"), vec!["customer joe: support"]);
+        assert!(list.complete("x").is_empty());
+    }
+
+    #[test]
+    fn test_is_known() {
+        let list = TaskList::parse(TASKS);
+        assert!(list.is_known("rtimelog: code", &Markers::default()));
+        assert!(list.is_known(" internal: meetings ", &Markers::default()));
+        // other task in a known category
+        assert!(list.is_known("rtimelog: release", &Markers::default()));
+        assert!(list.is_known("** lunch", &Markers::default()));
+        assert!(list.is_known("arrived", &Markers::default()));
+        assert!(!list.is_known("customer bob: support", &Markers::default()));
+        assert!(!list.is_known("code", &Markers::default()));
+    }
+
+    #[test]
+    fn test_history() {
+        let (entries, _) = crate::store::parse_entries(
+            "2022-06-10 07:00: arrived
+2022-06-10 09:00: rtimelog: code
+2022-06-10 09:30: ** coffee
+2022-06-10 10:00: mail
+2022-06-10 11:00: rtimelog: code
+2022-06-10 11:30: *** dentist
+",
+        );
+        let mut list = TaskList::from_history(entries.iter(), &Markers::default());
+        assert_eq!(list.tasks(), ["rtimelog: code", "mail"]);
+
+        list.merge(TaskList::parse(TASKS));
+        assert_eq!(
+            list.complete("rtimelog"),
+            vec!["rtimelog: code", "rtimelog: review"]
+        );
+        list.add("mail");
+        list.add("new");
+        assert_eq!(&list.tasks()[..3], ["new", "mail", "rtimelog: code"]);
+        assert_eq!(list.tasks().len(), 6);
+    }
+
+    #[test]
+    fn test_load() {
+        let cache = env::temp_dir().join(format!("rtimelog-test-tasks-{}", process::id()));
+        let _ = fs::remove_file(&cache);
+
+        // nothing cached, offline
+        assert!(
+            TaskList::load_with("u", &cache, Duration::hours(1), |_| Err(
+                "offline".to_string()
+            ))
+            .is_err()
+        );
+
+        // fetches and caches
+        let list = TaskList::load_with("u", &cache, Duration::hours(1), |_| Ok(TASKS.to_string()))
+            .unwrap();
+        assert_eq!(list.tasks().len(), 4);
+        assert_eq!(fs::read_to_string(&cache).unwrap(), TASKS);
+
+        // fresh cache is used without fetching
+        let list = TaskList::load_with("u", &cache, Duration::hours(1), |_| {
+            panic!("must not fetch")
+        })
+        .unwrap();
+        assert_eq!(list.tasks().len(), 4);
+
+        // expired cache gets refreshed
+        let list = TaskList::load_with("u", &cache, Duration::zero(), |_| Ok("a: b\n".to_string()))
+            .unwrap();
+        assert_eq!(list.tasks(), ["a: b"]);
+
+        // offline fallback to expired cache
+        let list =
+            TaskList::load_with(
+                "u",
+                &cache,
+                Duration::zero(),
+                |_| Err("offline".to_string()),
+            )
+            .unwrap();
+        assert_eq!(list.tasks(), ["a: b"]);
+
+        fs::remove_file(&cache).unwrap();
+    }
+}
@@ -0,0 +1,294 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate chrono;
+
+use std::fmt;
+
+use chrono::{Duration, NaiveDate};
+
+use crate::activity::{category, Activities, NO_CATEGORY};
+use crate::markers::Kind;
+use crate::store::{Entry, Options};
+
+/**
+ * Work of one team member, from their own timelog
+ */
+struct Person {
+    name: String,
+    total: Duration,
+    categories: Activities,
+    days: Activities,
+}
+
+/**
+ * Combined work report of several people's timelogs over a date range
+ */
+pub struct TeamReport {
+    from: NaiveDate,
+    to: NaiveDate,
+    people: Vec<Person>,
+}
+
+/**
+ * Group key which puts everything except work into "", to leave it out
+ */
+fn work_key(entry: &Entry, options: &Options, key: impl Fn(&Entry) -> String) -> String {
+    match options.markers.kind(&entry.task) {
+        Kind::Work => key(entry),
+        _ => String::new(),
+    }
+}
+
+fn work_groups(a: &Activities) -> impl Iterator<Item = (&str, Duration)> {
+    a.iter()
+        .filter(|a| !a.name().is_empty())
+        .map(|a| (a.name(), a.duration()))
+}
+
+/**
+ * Format a total like activities, "HH h MM min"
+ */
+fn hours_min(d: Duration) -> String {
+    format!("{:>2} h {:>2} min", d.num_hours(), d.num_minutes() % 60)
+}
+
+/**
+ * Format a duration as "H:MM" for the timesheet
+ */
+fn hm(d: Duration) -> String {
+    format!("{}:{:02}", d.num_hours(), d.num_minutes() % 60)
+}
+
+impl TeamReport {
+    /**
+     * Report of `members` as (name, entries), counting the entries between `from` and `to`
+     *
+     * Everyone's entries are split into days and classified with the same `options`.
+     */
+    pub fn new(
+        members: &[(String, Vec<Entry>)],
+        from: NaiveDate,
+        to: NaiveDate,
+        options: &Options,
+    ) -> TeamReport {
+        let people = members
+            .iter()
+            .map(|(name, entries)| {
+                let entries: Vec<Entry> = entries
+                    .iter()
+                    .filter(|e| (from..=to).contains(&options.day(e)))
+                    .cloned()
+                    .collect();
+                let categories = Activities::group_by(
+                    &entries,
+                    |e| {
+                        work_key(e, options, |e| {
+                            category(e.task.as_str()).unwrap_or(NO_CATEGORY).to_string()
+                        })
+                    },
+                    options,
+                );
+                let days = Activities::group_by(
+                    &entries,
+                    |e| work_key(e, options, |e| options.day(e).to_string()),
+                    options,
+                );
+                Person {
+                    name: name.clone(),
+                    total: categories.total_work(),
+                    categories,
+                    days,
+                }
+            })
+            .collect();
+        TeamReport { from, to, people }
+    }
+
+    /**
+     * Total work of each person
+     */
+    pub fn per_person(&self) -> Vec<(&str, Duration)> {
+        self.people
+            .iter()
+            .map(|p| (p.name.as_str(), p.total))
+            .collect()
+    }
+
+    /**
+     * Total work of each category across all people, in order of first occurrence
+     */
+    pub fn per_category(&self) -> Vec<(String, Duration)> {
+        let mut totals: Vec<(String, Duration)> = Vec::new();
+        for p in &self.people {
+            for (cat, d) in work_groups(&p.categories) {
+                match totals.iter_mut().find(|(c, _)| c == cat) {
+                    Some(t) => t.1 += d,
+                    None => totals.push((cat.to_string(), d)),
+                }
+            }
+        }
+        totals
+    }
+
+    /**
+     * Work of each person (in the order of per_person()) on each day of the range
+     */
+    pub fn timesheet(&self) -> Vec<(NaiveDate, Vec<Duration>)> {
+        self.from
+            .iter_days()
+            .take_while(|d| *d <= self.to)
+            .map(|day| {
+                let key = day.to_string();
+                let row = self
+                    .people
+                    .iter()
+                    .map(|p| {
+                        work_groups(&p.days)
+                            .find(|(k, _)| *k == key)
+                            .map_or(Duration::zero(), |(_, d)| d)
+                    })
+                    .collect();
+                (day, row)
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for TeamReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Team work from {} to {}", self.from, self.to)?;
+
+        writeln!(f, "\nPer person:")?;
+        for (name, d) in self.per_person() {
+            writeln!(f, "{}: {}", hours_min(d), name)?;
+        }
+        writeln!(f, "\nPer category:")?;
+        for (cat, d) in self.per_category() {
+            writeln!(f, "{}: {}", hours_min(d), cat)?;
+        }
+
+        writeln!(f, "\nTimesheet:")?;
+        let widths: Vec<usize> = self.people.iter().map(|p| p.name.len().max(6)).collect();
+        write!(f, "{:<10}", "Date")?;
+        for (p, w) in self.people.iter().zip(&widths) {
+            write!(f, "  {:>w$}", p.name, w = w)?;
+        }
+        writeln!(f, "  {:>6}", "Total")?;
+
+        let mut totals = vec![Duration::zero(); self.people.len()];
+        for (day, row) in self.timesheet() {
+            write!(f, "{}", day)?;
+            for ((d, w), total) in row.iter().zip(&widths).zip(totals.iter_mut()) {
+                write!(f, "  {:>w$}", hm(*d), w = w)?;
+                *total += *d;
+            }
+            let sum = row.iter().fold(Duration::zero(), |sum, d| sum + *d);
+            writeln!(f, "  {:>6}", hm(sum))?;
+        }
+        write!(f, "{:<10}", "Total")?;
+        for (d, w) in totals.iter().zip(&widths) {
+            write!(f, "  {:>w$}", hm(*d), w = w)?;
+        }
+        let sum = totals.iter().fold(Duration::zero(), |sum, d| sum + *d);
+        writeln!(f, "  {:>6}", hm(sum))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::parse_entries;
+
+    const ALICE: &str = "
+2022-06-09 08:00: arrived
+2022-06-09 10:00: acme: code
+2022-06-09 10:30: ** coffee
+2022-06-09 12:00: internal: meeting
+2022-06-10 08:00: arrived
+2022-06-10 09:15: acme: review
+2022-06-11 08:00: arrived
+2022-06-11 09:00: acme: code
+";
+
+    const BOB: &str = "
+2022-06-10 09:00: arrived
+2022-06-10 13:00: internal: planning
+2022-06-10 14:00: email
+";
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2022, 6, d).unwrap()
+    }
+
+    fn report() -> TeamReport {
+        let members = vec![
+            ("alice".to_string(), parse_entries(ALICE).0),
+            ("bob".to_string(), parse_entries(BOB).0),
+        ];
+        TeamReport::new(&members, day(9), day(10), &Options::default())
+    }
+
+    #[test]
+    fn test_totals() {
+        let r = report();
+        assert_eq!(
+            r.per_person(),
+            vec![
+                ("alice", Duration::minutes(285)),
+                ("bob", Duration::hours(5))
+            ]
+        );
+        assert_eq!(
+            r.per_category(),
+            vec![
+                ("acme".to_string(), Duration::minutes(195)),
+                ("internal".to_string(), Duration::minutes(330)),
+                (NO_CATEGORY.to_string(), Duration::hours(1)),
+            ]
+        );
+        assert_eq!(
+            r.timesheet(),
+            vec![
+                (day(9), vec![Duration::minutes(210), Duration::zero()]),
+                (day(10), vec![Duration::minutes(75), Duration::hours(5)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            report().to_string(),
+            "Team work from 2022-06-09 to 2022-06-10
+
+Per person:
+ 4 h 45 min: alice
+ 5 h  0 min: bob
+
+Per category:
+ 3 h 15 min: acme
+ 5 h 30 min: internal
+ 1 h  0 min: (no category)
+
+Timesheet:
+Date         alice     bob   Total
+2022-06-09    3:30    0:00    3:30
+2022-06-10    1:15    5:00    6:15
+Total         4:45    5:00    9:45
+"
+        );
+    }
+}
@@ -0,0 +1,201 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate chrono;
+
+use std::fmt;
+
+use chrono::{Duration, NaiveDate};
+
+use crate::activity::{format_duration, Activities};
+use crate::config::Config;
+use crate::leave::marked_days;
+use crate::store::Timelog;
+
+/**
+ * A day in the TOIL (time off in lieu) ledger
+ */
+#[derive(Debug, PartialEq, Eq)]
+pub struct LedgerDay {
+    pub day: NaiveDate,
+    // work done, with on-call multipliers
+    pub worked: Duration,
+    // difference to the expected hours per day
+    pub delta: Duration,
+    // running balance after this day
+    pub balance: Duration,
+    // the day has a TOIL marker
+    pub toil: bool,
+}
+
+/**
+ * Running balance of overtime accrued and time off in lieu taken
+ *
+ * Every day with entries accrues its work minus the expected hours per day, so a TOIL day (marked
+ * with e.g. "*** TOIL") takes the hours that were not worked from the balance. Vacation and sick
+ * days don't count.
+ */
+pub struct Ledger {
+    from: NaiveDate,
+    to: NaiveDate,
+    pub days: Vec<LedgerDay>,
+}
+
+impl Ledger {
+    pub fn new(timelog: &Timelog, from: NaiveDate, to: NaiveDate, config: &Config) -> Ledger {
+        let mut days = Vec::new();
+        let mut balance = Duration::zero();
+
+        // markers like "*** vacation until friday" may start before `from`
+        let all = timelog.get_date_range(&NaiveDate::MIN, &to);
+        let options = timelog.options();
+        let vacation = marked_days(all, &config.vacation_markers, options);
+        let sick = marked_days(all, &config.sick_markers, options);
+        let toil = marked_days(all, &config.toil_markers, options);
+
+        for day in from.iter_days().take_while(|d| *d <= to) {
+            let entries = timelog.get_day(&day);
+            let marked = |days: &[NaiveDate]| days.binary_search(&day).is_ok();
+            if (entries.is_empty() && !marked(&toil)) || marked(&vacation) || marked(&sick) {
+                continue;
+            }
+
+            let worked =
+                Activities::new_from_entries(entries, options).weighted_work(&config.oncall);
+            let delta = worked - config.hours_per_day;
+            balance += delta;
+            days.push(LedgerDay {
+                day,
+                worked,
+                delta,
+                balance,
+                toil: marked(&toil),
+            });
+        }
+
+        Ledger { from, to, days }
+    }
+
+    pub fn balance(&self) -> Duration {
+        self.days.last().map_or(Duration::zero(), |d| d.balance)
+    }
+}
+
+impl fmt::Display for Ledger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "TOIL ledger {} - {}:", self.from, self.to)?;
+        for d in &self.days {
+            let what = if d.toil {
+                "TOIL taken"
+            } else if d.delta < Duration::zero() {
+                "short"
+            } else {
+                "overtime"
+            };
+            let sign = if d.delta > Duration::zero() { "+" } else { "" };
+            writeln!(
+                f,
+                "{}: {}{} {}, balance {}",
+                d.day.format("%Y-%m-%d %a"),
+                sign,
+                format_duration(d.delta),
+                what,
+                format_duration(d.balance)
+            )?;
+        }
+        writeln!(f, "-------")?;
+        writeln!(f, "Balance: {}", format_duration(self.balance()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "
+2022-06-06 08:00: arrived
+2022-06-06 18:00: code
+
+2022-06-07 08:00: arrived
+2022-06-07 12:00: code
+2022-06-07 13:00: standby: pager
+2022-06-07 13:00: *** TOIL
+
+2022-06-08 09:00: *** vacation
+
+2022-06-09 09:00: *** sick
+
+2022-06-10 08:00: arrived
+2022-06-10 15:00: code
+";
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2022, 6, d).unwrap()
+    }
+
+    #[test]
+    fn test_ledger() {
+        let tl = Timelog::new_from_string(LOG);
+        let config = Config::new_from_string("[oncall]\nstandby = 0.5\n");
+        let l = Ledger::new(&tl, day(6), day(12), &config);
+        assert_eq!(l.days.len(), 3);
+        assert_eq!(
+            l.days[1],
+            LedgerDay {
+                day: day(7),
+                worked: Duration::minutes(270),
+                delta: Duration::minutes(-210),
+                balance: Duration::minutes(-90),
+                toil: true,
+            }
+        );
+        assert_eq!(l.balance(), Duration::minutes(-150));
+        assert_eq!(
+            format!("{}", l),
+            "TOIL ledger 2022-06-06 - 2022-06-12:
+2022-06-06 Mon: +2 h 0 min overtime, balance 2 h 0 min
+2022-06-07 Tue: -3 h 30 min TOIL taken, balance -1 h 30 min
+2022-06-10 Fri: -1 h 0 min short, balance -2 h 30 min
+-------
+Balance: -2 h 30 min
+"
+        );
+    }
+
+    #[test]
+    fn test_ledger_multi_day() {
+        let tl = Timelog::new_from_string(
+            "
+2022-06-03 08:00: arrived
+2022-06-03 18:00: code
+
+2022-06-06 09:00: *** TOIL until 2022-06-07
+2022-06-08 09:00: *** vacation until 2022-06-09
+",
+        );
+        let l = Ledger::new(&tl, day(3), day(10), &Config::default());
+        let days: Vec<_> = l.days.iter().map(|d| (d.day, d.toil)).collect();
+        assert_eq!(days, vec![(day(3), false), (day(6), true), (day(7), true)]);
+        assert_eq!(l.balance(), Duration::hours(-14));
+    }
+
+    #[test]
+    fn test_ledger_empty() {
+        let tl = Timelog::new_from_string(LOG);
+        let l = Ledger::new(&tl, day(20), day(30), &Config::default());
+        assert!(l.days.is_empty());
+        assert_eq!(l.balance(), Duration::zero());
+    }
+}
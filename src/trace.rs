@@ -0,0 +1,161 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::env;
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::Instant;
+
+/**
+ * Verbosity of diagnostic messages on stderr
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    // only warnings, the default
+    Warn,
+    // -v
+    Info,
+    // -vv
+    Debug,
+    // -vvv
+    Trace,
+}
+
+impl Level {
+    fn from_u8(n: u8) -> Level {
+        match n {
+            0 => Level::Warn,
+            1 => Level::Info,
+            2 => Level::Debug,
+            _ => Level::Trace,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Level> {
+        match name.trim().to_lowercase().as_str() {
+            "warn" | "warning" => Some(Level::Warn),
+            "info" => Some(Level::Info),
+            "debug" => Some(Level::Debug),
+            "trace" => Some(Level::Trace),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+            Level::Trace => "trace",
+        }
+    }
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(0);
+
+/**
+ * Set the level from the number of -v flags, or $RTIMELOG_LOG if that is more verbose
+ */
+pub fn init(verbosity: u8) {
+    let from_env = env::var("RTIMELOG_LOG")
+        .ok()
+        .and_then(|v| Level::from_name(&v))
+        .unwrap_or(Level::Warn);
+    set_level(from_env.max(Level::from_u8(verbosity)));
+}
+
+pub fn set_level(level: Level) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn enabled(level: Level) -> bool {
+    level as u8 <= LEVEL.load(Ordering::Relaxed)
+}
+
+/**
+ * Print a message if `level` is enabled; usually called through the info!/debug!/trace! macros
+ */
+pub fn log(level: Level, args: fmt::Arguments) {
+    if enabled(level) {
+        eprintln!("rtimelog [{}] {}", level.name(), args);
+    }
+}
+
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        $crate::trace::log($crate::trace::Level::Info, format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        $crate::trace::log($crate::trace::Level::Debug, format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        $crate::trace::log($crate::trace::Level::Trace, format_args!($($arg)*))
+    };
+}
+
+/**
+ * Logs the time from its creation until it gets dropped, at debug level
+ */
+pub struct Timer {
+    what: &'static str,
+    start: Instant,
+}
+
+impl Timer {
+    pub fn new(what: &'static str) -> Timer {
+        Timer {
+            what,
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        log(
+            Level::Debug,
+            format_args!("{} took {:?}", self.what, self.start.elapsed()),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level() {
+        assert_eq!(Level::from_name("DEBUG"), Some(Level::Debug));
+        assert_eq!(Level::from_name("warning"), Some(Level::Warn));
+        assert_eq!(Level::from_name("loud"), None);
+        assert_eq!(Level::from_u8(0), Level::Warn);
+        assert_eq!(Level::from_u8(7), Level::Trace);
+
+        set_level(Level::Info);
+        assert!(enabled(Level::Warn));
+        assert!(enabled(Level::Info));
+        assert!(!enabled(Level::Debug));
+        set_level(Level::Warn);
+    }
+}
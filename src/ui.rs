@@ -0,0 +1,500 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate chrono;
+
+use std::fmt::Write as _;
+use std::io::{self, IsTerminal, Read, Write};
+use std::process::{self, Stdio};
+
+use chrono::{Duration, NaiveDateTime};
+
+use crate::activity::{format_duration, Activities};
+use crate::i18n::tr;
+use crate::markers::Kind;
+use crate::store::{Entry, Options};
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const SLACK_COLOR: &str = "\x1b[2m";
+const IGNORED_COLOR: &str = "\x1b[90m";
+
+/**
+ * Key presses which the ui understands
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    Backspace,
+    Enter,
+    Up,
+    Down,
+    // ^U
+    Kill,
+    // ^C
+    Interrupt,
+    // ^D
+    Eof,
+}
+
+/**
+ * Decode terminal input into keys; returns the keys and the number of bytes used
+ *
+ * An incomplete UTF-8 character at the end is left for the next read. Other control characters
+ * and escape sequences than the arrow keys are dropped.
+ */
+pub fn decode(input: &[u8]) -> (Vec<Key>, usize) {
+    let text = match std::str::from_utf8(input) {
+        Ok(text) => text,
+        // from_utf8 checked this prefix already
+        Err(e) if e.error_len().is_none() => {
+            std::str::from_utf8(&input[..e.valid_up_to()]).unwrap()
+        }
+        Err(_) => return (Vec::new(), input.len()),
+    };
+
+    let mut keys = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\r' | '\n' => keys.push(Key::Enter),
+            '\x7f' | '\x08' => keys.push(Key::Backspace),
+            '\x15' => keys.push(Key::Kill),
+            '\x03' => keys.push(Key::Interrupt),
+            '\x04' => keys.push(Key::Eof),
+            '\x1b' => {
+                if chars.next_if_eq(&'[').is_none() {
+                    continue;
+                }
+                // parameters, up to the final byte
+                let mut last = None;
+                for c in chars.by_ref() {
+                    if ('\x40'..='\x7e').contains(&c) {
+                        last = Some(c);
+                        break;
+                    }
+                }
+                match last {
+                    Some('A') => keys.push(Key::Up),
+                    Some('B') => keys.push(Key::Down),
+                    _ => (),
+                }
+            }
+            c if c.is_control() => (),
+            c => keys.push(Key::Char(c)),
+        }
+    }
+    (keys, text.len())
+}
+
+/**
+ * What to do after a key press
+ */
+#[derive(Debug, PartialEq, Eq)]
+pub enum Action {
+    None,
+    Submit(String),
+    Quit,
+}
+
+/**
+ * The input line, with a history of recent tasks to go through with the arrow keys
+ */
+pub struct Prompt {
+    text: String,
+    // newest first
+    history: Vec<String>,
+    position: Option<usize>,
+}
+
+impl Prompt {
+    pub fn new(history: Vec<String>) -> Prompt {
+        Prompt {
+            text: String::new(),
+            history,
+            position: None,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /**
+     * Put a task back into the input line, like one which could not be saved
+     */
+    pub fn set_text(&mut self, text: String) {
+        self.text = text;
+    }
+
+    pub fn set_history(&mut self, history: Vec<String>) {
+        self.history = history;
+        self.position = None;
+    }
+
+    pub fn key(&mut self, key: Key) -> Action {
+        match key {
+            Key::Char(c) => self.text.push(c),
+            Key::Backspace => {
+                self.text.pop();
+            }
+            // like in a shell, abort the current input
+            Key::Kill | Key::Interrupt => self.text.clear(),
+            Key::Eof if self.text.is_empty() => return Action::Quit,
+            Key::Eof => (),
+            Key::Up => {
+                let next = self.position.map_or(0, |p| p + 1);
+                if let Some(task) = self.history.get(next) {
+                    self.text = task.clone();
+                    self.position = Some(next);
+                }
+            }
+            Key::Down => {
+                self.position = self.position.and_then(|p| p.checked_sub(1));
+                self.text = self
+                    .position
+                    .map_or(String::new(), |p| self.history[p].clone());
+            }
+            Key::Enter => {
+                let text = self.text.trim().to_string();
+                self.text.clear();
+                self.position = None;
+                if !text.is_empty() {
+                    return Action::Submit(text);
+                }
+            }
+        }
+        Action::None
+    }
+}
+
+/**
+ * Activities of the shown entries, kept up to date with Activities::push() and set_now() instead
+ * of counting all entries again every minute
+ *
+ * When the entries do not continue the ones counted so far, like after reloading a changed file or
+ * on a new day, they all get counted again.
+ */
+pub struct LiveActivities {
+    activities: Activities,
+    counted: usize,
+    last: Option<Entry>,
+}
+
+impl LiveActivities {
+    pub fn new(entries: &[Entry], options: &Options) -> LiveActivities {
+        LiveActivities {
+            activities: Activities::new_from_entries(entries, options),
+            counted: entries.len(),
+            last: entries.last().cloned(),
+        }
+    }
+
+    pub fn update(&mut self, entries: &[Entry], now: NaiveDateTime) -> &Activities {
+        let continues = self.counted <= entries.len()
+            && self.counted.checked_sub(1).map(|i| &entries[i]) == self.last.as_ref();
+        if continues {
+            for entry in &entries[self.counted..] {
+                self.activities.push(entry);
+            }
+        } else {
+            self.activities = Activities::new_from_entries(entries, self.activities.options());
+        }
+        self.counted = entries.len();
+        self.last = entries.last().cloned();
+        self.activities.set_now(now);
+        &self.activities
+    }
+}
+
+/**
+ * Contents of the screen, for drawing it with render()
+ */
+pub struct View<'a> {
+    pub title: &'a str,
+    pub activities: &'a Activities,
+    // time since the last entry, if there is one today
+    pub since_last: Option<Duration>,
+    pub hours_per_day: Duration,
+    // feedback about the last input, like a rejected task
+    pub message: &'a str,
+    pub color: bool,
+}
+
+/**
+ * Draw the screen: today's activities, the time since the last entry, and the prompt
+ *
+ * Activities which do not fit into `height` lines get left out. Slack and ignored activities are
+ * dimmed, according to the markers of the activities.
+ */
+pub fn render(view: &View, prompt: &Prompt, height: usize) -> String {
+    let style = |code: &'static str| if view.color { code } else { "" };
+    let reset = style(RESET);
+    let mut out = String::new();
+    // home and clear
+    out.push_str("\x1b[H\x1b[2J");
+    writeln!(out, "{}{}{}", style(BOLD), view.title, reset).unwrap();
+
+    // title, totals, empty line, status, message, prompt
+    let room = height.saturating_sub(6).max(1);
+    let activities: Vec<_> = view.activities.iter().collect();
+    for a in activities.iter().take(room) {
+        let color = match view.activities.options().markers.kind(a.name()) {
+            Kind::Work => "",
+            Kind::Slack => style(SLACK_COLOR),
+            Kind::Ignored => style(IGNORED_COLOR),
+        };
+        writeln!(
+            out,
+            "{}{}{}",
+            color,
+            a,
+            if color.is_empty() { "" } else { reset }
+        )
+        .unwrap();
+    }
+    if activities.len() > room {
+        writeln!(out, "  ... {}", activities.len() - room).unwrap();
+    }
+    writeln!(
+        out,
+        "{}: {} / {}; {}: {}",
+        tr("Total work done"),
+        format_duration(view.activities.total_work()),
+        format_duration(view.hours_per_day),
+        tr("Total slacking"),
+        format_duration(view.activities.total_slack())
+    )
+    .unwrap();
+
+    let since = match view.since_last {
+        None => tr("no entries yet today").to_string(),
+        Some(d) => format!("{} {}", format_duration(d), tr("since last entry")),
+    };
+    writeln!(out, "\n{}{}{}", style(BOLD), since, reset).unwrap();
+    writeln!(out, "{}", view.message).unwrap();
+    write!(out, "> {}", prompt.text()).unwrap();
+    out
+}
+
+/**
+ * The terminal in non-canonical mode on the alternate screen, restored when dropped
+ *
+ * This runs `stty`, like other features use external programs.
+ */
+pub struct Terminal {
+    saved: String,
+}
+
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = process::Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+impl Terminal {
+    pub fn enter() -> io::Result<Terminal> {
+        if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+            return Err(io::Error::other("not a terminal"));
+        }
+        let saved = stty(&["-g"])?;
+        // reads return after a second without input, for updating the time
+        stty(&["-icanon", "-echo", "-isig", "min", "0", "time", "10"])?;
+        print!("\x1b[?1049h");
+        io::stdout().flush()?;
+        Ok(Terminal { saved })
+    }
+
+    /**
+     * Number of lines of the terminal
+     */
+    pub fn height(&self) -> usize {
+        stty(&["size"])
+            .ok()
+            .and_then(|s| s.split_whitespace().next()?.parse().ok())
+            .unwrap_or(24)
+    }
+
+    /**
+     * Keys pressed within about a second; `pending` keeps incomplete input between calls
+     */
+    pub fn read_keys(&mut self, pending: &mut Vec<u8>) -> io::Result<Vec<Key>> {
+        let mut buf = [0; 256];
+        let n = io::stdin().read(&mut buf)?;
+        pending.extend_from_slice(&buf[..n]);
+        let (keys, used) = decode(pending);
+        pending.drain(..used);
+        Ok(keys)
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        print!("\x1b[?1049l");
+        let _ = io::stdout().flush();
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::parse_entries;
+    use chrono::NaiveDate;
+
+    fn at(h: u32, m: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2022, 6, 10)
+            .unwrap()
+            .and_hms_opt(h, m, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(
+            decode(b"ab\x7f\r"),
+            (
+                vec![Key::Char('a'), Key::Char('b'), Key::Backspace, Key::Enter],
+                4
+            )
+        );
+        assert_eq!(
+            decode(b"\x1b[A\x1b[B\x1b[1;5C\x03\x04\x15\x01"),
+            (
+                vec![Key::Up, Key::Down, Key::Interrupt, Key::Eof, Key::Kill],
+                16
+            )
+        );
+        // incomplete UTF-8 character
+        assert_eq!(decode("ä".as_bytes()), (vec![Key::Char('ä')], 2));
+        assert_eq!(decode(&"xä".as_bytes()[..2]), (vec![Key::Char('x')], 1));
+    }
+
+    #[test]
+    fn test_prompt() {
+        let mut p = Prompt::new(vec!["review".to_string(), "code".to_string()]);
+        assert_eq!(p.key(Key::Enter), Action::None);
+        for c in "mail ".chars() {
+            p.key(Key::Char(c));
+        }
+        p.key(Key::Backspace);
+        assert_eq!(p.text(), "mail");
+        assert_eq!(p.key(Key::Enter), Action::Submit("mail".to_string()));
+        assert_eq!(p.text(), "");
+
+        p.key(Key::Up);
+        assert_eq!(p.text(), "review");
+        p.key(Key::Up);
+        p.key(Key::Up);
+        assert_eq!(p.text(), "code");
+        p.key(Key::Down);
+        assert_eq!(p.text(), "review");
+        p.key(Key::Down);
+        assert_eq!(p.text(), "");
+
+        p.set_text("mail".to_string());
+        assert_eq!(p.key(Key::Enter), Action::Submit("mail".to_string()));
+
+        p.key(Key::Char('x'));
+        assert_eq!(p.key(Key::Eof), Action::None);
+        p.key(Key::Interrupt);
+        assert_eq!(p.text(), "");
+        assert_eq!(p.key(Key::Eof), Action::Quit);
+    }
+
+    #[test]
+    fn test_render() {
+        let (entries, _) = parse_entries(
+            "2022-06-10 07:00: arrived
+2022-06-10 09:00: code
+2022-06-10 09:30: ** coffee
+2022-06-10 10:00: review
+",
+        );
+        let activities = Activities::new_from_entries(&entries, &Options::default());
+        let mut prompt = Prompt::new(Vec::new());
+        prompt.key(Key::Char('m'));
+        let view = View {
+            title: "Today",
+            activities: &activities,
+            since_last: Some(Duration::minutes(47)),
+            hours_per_day: Duration::hours(8),
+            message: "",
+            color: false,
+        };
+        assert_eq!(
+            render(&view, &prompt, 24),
+            "\x1b[H\x1b[2JToday
+ 2 h  0 min: code
+ 0 h 30 min: ** coffee
+ 0 h 30 min: review
+Total work done: 2 h 30 min / 8 h 0 min; Total slacking: 0 h 30 min
+
+0 h 47 min since last entry
+
+> m"
+        );
+
+        // too little room for all activities
+        let screen = render(&view, &prompt, 7);
+        assert!(screen.contains(" 2 h  0 min: code\n  ... 2\nTotal"));
+
+        let screen = render(
+            &View {
+                color: true,
+                since_last: None,
+                ..view
+            },
+            &prompt,
+            24,
+        );
+        assert!(screen.contains("\x1b[2m 0 h 30 min: ** coffee\x1b[0m\n"));
+        assert!(screen.contains("no entries yet today"));
+    }
+
+    #[test]
+    fn test_live_activities() {
+        let (entries, _) = parse_entries(
+            "2022-06-10 07:00: arrived
+2022-06-10 09:00: code
+2022-06-10 09:30: ** coffee
+",
+        );
+        let mut live = LiveActivities::new(&entries[..2], &Options::default());
+        assert_eq!(
+            live.update(&entries[..2], at(9, 10)).pending(),
+            Duration::minutes(10)
+        );
+
+        let a = live.update(&entries, at(9, 45));
+        assert_eq!(a.total_work(), Duration::hours(2));
+        assert_eq!(a.total_slack(), Duration::minutes(30));
+        assert_eq!(a.pending(), Duration::minutes(15));
+
+        // changed entries get counted again
+        let (changed, _) = parse_entries("2022-06-10 08:00: arrived\n2022-06-10 08:20: mail\n");
+        let a = live.update(&changed, at(9, 0));
+        assert_eq!(a.total_work(), Duration::minutes(20));
+        assert_eq!(a.total_slack(), Duration::zero());
+        assert_eq!(a.iter().count(), 1);
+    }
+}
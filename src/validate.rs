@@ -0,0 +1,150 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate chrono;
+
+use chrono::{Duration, NaiveDateTime};
+
+use crate::activity::category;
+use crate::markers::{Kind, Markers};
+use crate::regex::Regex;
+use crate::tasklist::TaskList;
+
+/**
+ * Team conventions which new entries must follow, from the [validation] section
+ */
+#[derive(Debug, Default)]
+pub struct Rules {
+    /// work tasks must match this
+    pub task_pattern: Option<Regex>,
+    /// the category of work tasks must be in this list
+    pub categories: Option<TaskList>,
+    /// entries must not reach back further than this
+    pub max_backdate: Option<Duration>,
+    /// which tasks are slacking or ignored, and thus exempt from the pattern and categories
+    pub markers: Markers,
+}
+
+impl Rules {
+    /**
+     * Check a new entry for `task` which covers the time since `start`
+     *
+     * Slacking, ignored entries, and starting the day only need to respect the backdate window.
+     */
+    pub fn check(
+        &self,
+        task: &str,
+        start: NaiveDateTime,
+        now: NaiveDateTime,
+    ) -> Result<(), String> {
+        if let Some(max) = self.max_backdate {
+            if start < now - max {
+                return Err(format!(
+                    "\"{}\" starts at {}, which is more than {} hours back",
+                    task,
+                    start.format("%Y-%m-%d %H:%M"),
+                    max.num_minutes() as f64 / 60.0
+                ));
+            }
+        }
+
+        let task = task.trim();
+        if self.markers.kind(task) != Kind::Work || task == "arrived" {
+            return Ok(());
+        }
+        if let Some(pattern) = &self.task_pattern {
+            if !pattern.is_match(task) {
+                return Err(format!(
+                    "\"{}\" does not match the task pattern {}",
+                    task,
+                    pattern.as_str()
+                ));
+            }
+        }
+        if let Some(list) = &self.categories {
+            match category(task) {
+                None => return Err(format!("\"{}\" has no category", task)),
+                Some(c) if !list.has_category(c) => {
+                    return Err(format!("\"{}\" has the unknown category \"{}\"", task, c))
+                }
+                Some(_) => (),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(h: u32, m: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2022, 6, 10)
+            .unwrap()
+            .and_hms_opt(h, m, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_no_rules() {
+        let rules = Rules::default();
+        assert!(rules.check("anything goes", at(1, 0), at(12, 0)).is_ok());
+    }
+
+    #[test]
+    fn test_check() {
+        let rules = Rules {
+            task_pattern: Some(Regex::new("^[a-z]+: ").unwrap()),
+            categories: Some(TaskList::parse("acme: code\ninternal: meetings\n")),
+            max_backdate: Some(Duration::hours(2)),
+            markers: Markers::default(),
+        };
+        let now = at(12, 0);
+
+        assert!(rules.check("acme: review", at(11, 0), now).is_ok());
+        assert!(rules.check("internal: meetings", at(10, 0), now).is_ok());
+        // exempt from the pattern and category
+        assert!(rules.check("arrived", now, now).is_ok());
+        assert!(rules.check("** lunch", now, now).is_ok());
+        assert!(rules.check("*** vacation", now, now).is_ok());
+
+        assert_eq!(
+            rules.check("acme: review", at(9, 59), now),
+            Err(
+                "\"acme: review\" starts at 2022-06-10 09:59, which is more than 2 hours back"
+                    .to_string()
+            )
+        );
+        assert!(rules.check("** lunch", at(9, 0), now).is_err());
+        assert_eq!(
+            rules.check("Acme: review", now, now),
+            Err("\"Acme: review\" does not match the task pattern ^[a-z]+: ".to_string())
+        );
+        assert_eq!(
+            rules.check("other: review", now, now),
+            Err("\"other: review\" has the unknown category \"other\"".to_string())
+        );
+
+        let rules = Rules {
+            categories: Some(TaskList::parse("acme: code\n")),
+            ..Rules::default()
+        };
+        assert_eq!(
+            rules.check("review", now, now),
+            Err("\"review\" has no category".to_string())
+        );
+    }
+}
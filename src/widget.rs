@@ -0,0 +1,122 @@
+// Copyright (C) 2022 Martin Pitt <martin@piware.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+extern crate chrono;
+
+use chrono::{Duration, NaiveDateTime};
+
+use crate::activity::Activities;
+use crate::store::{Entry, Options};
+
+/// number of cells in the progress bar
+const BAR_WIDTH: i64 = 5;
+
+fn format_short(d: Duration) -> String {
+    format!("{}:{:02}", d.num_hours(), d.num_minutes() % 60)
+}
+
+/**
+ * Compact one-line status for tmux or prompt status bars
+ *
+ * Shows the last task and the time since it, and today's work compared to `hours_per_day`, like
+ * `acme:code 0:47 | day 6:05/7:30 ▰▰▰▰▱`. `today` are the entries of the current day.
+ */
+pub fn render(
+    today: &[Entry],
+    now: NaiveDateTime,
+    hours_per_day: Duration,
+    options: &Options,
+) -> String {
+    let work = Activities::new_from_entries(today, options).total_work();
+    let mut bar = String::new();
+    let filled = if hours_per_day > Duration::zero() {
+        (work.num_minutes() * BAR_WIDTH / hours_per_day.num_minutes()).min(BAR_WIDTH)
+    } else {
+        0
+    };
+    for i in 0..BAR_WIDTH {
+        bar.push(if i < filled { '▰' } else { '▱' });
+    }
+    let day = format!(
+        "day {}/{} {}",
+        format_short(work),
+        format_short(hours_per_day),
+        bar
+    );
+
+    match today.last() {
+        Some(last) => {
+            let task = match last.task.split_once(':') {
+                Some((category, rest)) => format!("{}:{}", category.trim(), rest.trim()),
+                None => last.task.to_string(),
+            };
+            let since = (now - last.stop).max(Duration::zero());
+            format!("{} {} | {}", task, format_short(since), day)
+        }
+        None => day,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::parse_entries;
+    use chrono::NaiveDate;
+
+    fn at(h: u32, m: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2022, 6, 10)
+            .unwrap()
+            .and_hms_opt(h, m, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_render() {
+        let hours = Duration::minutes(450);
+        assert_eq!(
+            render(&[], at(9, 0), hours, &Options::default()),
+            "day 0:00/7:30 ▱▱▱▱▱"
+        );
+
+        let (entries, _) = parse_entries(
+            "2022-06-10 07:00: arrived
+2022-06-10 12:05: acme: code
+2022-06-10 12:30: **lunch
+2022-06-10 13:30: acme: code
+",
+        );
+        assert_eq!(
+            render(&entries[..1], at(7, 10), hours, &Options::default()),
+            "arrived 0:10 | day 0:00/7:30 ▱▱▱▱▱"
+        );
+        assert_eq!(
+            render(&entries, at(14, 17), hours, &Options::default()),
+            "acme:code 0:47 | day 6:05/7:30 ▰▰▰▰▱"
+        );
+        assert_eq!(
+            render(
+                &entries,
+                at(14, 17),
+                Duration::hours(4),
+                &Options::default()
+            ),
+            "acme:code 0:47 | day 6:05/4:00 ▰▰▰▰▰"
+        );
+        assert_eq!(
+            render(&entries[..3], at(12, 40), hours, &Options::default()),
+            "**lunch 0:10 | day 5:05/7:30 ▰▰▰▱▱"
+        );
+    }
+}